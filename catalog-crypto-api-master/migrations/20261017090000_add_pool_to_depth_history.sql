@@ -0,0 +1,6 @@
+-- Depth history used to be fetched for ETH.ETH only, so existing rows belong to that pool
+ALTER TABLE `depth_intervals`
+    ADD COLUMN pool VARCHAR(64) NOT NULL DEFAULT 'ETH.ETH' AFTER id,
+    ADD INDEX idx_depth_pool_time_range (pool, start_time, end_time);
+
+ALTER TABLE `depth_intervals` ALTER COLUMN pool DROP DEFAULT;
@@ -0,0 +1,11 @@
+-- Registry of the pools known to midgard, kept in sync by the pools cron
+CREATE TABLE `pools` (
+    id BIGINT AUTO_INCREMENT PRIMARY KEY,
+    asset VARCHAR(64) NOT NULL,
+    status VARCHAR(16) NOT NULL,
+    decimals INT NULL,
+    first_seen TIMESTAMP NOT NULL,
+    last_synced TIMESTAMP NOT NULL,
+    UNIQUE KEY uq_pools_asset (asset),
+    INDEX idx_pools_status (status)
+) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4 COLLATE = utf8mb4_unicode_ci;
@@ -0,0 +1,9 @@
+-- Where each backfill cron stopped, pool is empty for the datasets that are not per pool
+CREATE TABLE `ingestion_checkpoints` (
+    id BIGINT AUTO_INCREMENT PRIMARY KEY,
+    dataset VARCHAR(32) NOT NULL,
+    pool VARCHAR(64) NOT NULL DEFAULT '',
+    last_end_time TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+    UNIQUE KEY uq_checkpoint_dataset_pool (dataset, pool)
+) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4 COLLATE = utf8mb4_unicode_ci;
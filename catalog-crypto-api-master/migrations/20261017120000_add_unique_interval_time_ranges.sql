@@ -0,0 +1,42 @@
+-- Overlapping fetchers could store the same interval twice, keep the oldest copy of each
+DELETE newer FROM `depth_intervals` AS newer
+JOIN `depth_intervals` AS older
+    ON older.pool = newer.pool
+    AND older.start_time = newer.start_time
+    AND older.end_time = newer.end_time
+    AND older.id < newer.id;
+
+DELETE newer FROM `earning_intervals` AS newer
+JOIN `earning_intervals` AS older
+    ON older.start_time = newer.start_time
+    AND older.end_time = newer.end_time
+    AND older.id < newer.id;
+
+DELETE newer FROM `swap_intervals` AS newer
+JOIN `swap_intervals` AS older
+    ON older.start_time = newer.start_time
+    AND older.end_time = newer.end_time
+    AND older.id < newer.id;
+
+DELETE newer FROM `runepool_unit_intervals` AS newer
+JOIN `runepool_unit_intervals` AS older
+    ON older.start_time = newer.start_time
+    AND older.end_time = newer.end_time
+    AND older.id < newer.id;
+
+-- The unique keys replace the plain time range indexes they cover
+ALTER TABLE `depth_intervals`
+    DROP INDEX idx_depth_pool_time_range,
+    ADD UNIQUE KEY uq_depth_pool_time_range (pool, start_time, end_time);
+
+ALTER TABLE `earning_intervals`
+    DROP INDEX idx_earnings_time_range,
+    ADD UNIQUE KEY uq_earnings_time_range (start_time, end_time);
+
+ALTER TABLE `swap_intervals`
+    DROP INDEX idx_swap_time_range,
+    ADD UNIQUE KEY uq_swap_time_range (start_time, end_time);
+
+ALTER TABLE `runepool_unit_intervals`
+    DROP INDEX idx_runepool_units_time_range,
+    ADD UNIQUE KEY uq_runepool_units_time_range (start_time, end_time);
@@ -0,0 +1,17 @@
+-- revision counts how many times midgard sent different values for an interval after it was
+-- first stored, updated_at only moves when one of the values actually changes
+ALTER TABLE `depth_intervals`
+    ADD COLUMN revision INT UNSIGNED NOT NULL DEFAULT 0,
+    ADD COLUMN updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP;
+
+ALTER TABLE `earning_intervals`
+    ADD COLUMN revision INT UNSIGNED NOT NULL DEFAULT 0,
+    ADD COLUMN updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP;
+
+ALTER TABLE `swap_intervals`
+    ADD COLUMN revision INT UNSIGNED NOT NULL DEFAULT 0,
+    ADD COLUMN updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP;
+
+ALTER TABLE `runepool_unit_intervals`
+    ADD COLUMN revision INT UNSIGNED NOT NULL DEFAULT 0,
+    ADD COLUMN updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP;
@@ -0,0 +1,55 @@
+-- Postgres has no unsigned integers, signed columns let every backend decode the same models.
+-- Midgard amounts are 1e8 fixed point and stay far below the signed 64 bit limit
+ALTER TABLE `depth_intervals`
+    MODIFY COLUMN asset_depth BIGINT NOT NULL,
+    MODIFY COLUMN liquidity_units BIGINT NOT NULL,
+    MODIFY COLUMN members_count INT NOT NULL,
+    MODIFY COLUMN rune_depth BIGINT NOT NULL,
+    MODIFY COLUMN synth_supply BIGINT NOT NULL,
+    MODIFY COLUMN synth_units BIGINT NOT NULL,
+    MODIFY COLUMN units BIGINT NOT NULL,
+    MODIFY COLUMN revision INT NOT NULL DEFAULT 0;
+
+ALTER TABLE `earning_intervals`
+    MODIFY COLUMN block_rewards BIGINT NOT NULL,
+    MODIFY COLUMN bonding_earnings BIGINT NOT NULL,
+    MODIFY COLUMN earnings BIGINT NOT NULL,
+    MODIFY COLUMN liquidity_earnings BIGINT NOT NULL,
+    MODIFY COLUMN liquidity_fees BIGINT NOT NULL,
+    MODIFY COLUMN revision INT NOT NULL DEFAULT 0;
+
+ALTER TABLE `swap_intervals`
+    MODIFY COLUMN from_trade_count BIGINT NOT NULL,
+    MODIFY COLUMN from_trade_fees BIGINT NOT NULL,
+    MODIFY COLUMN from_trade_volume BIGINT NOT NULL,
+    MODIFY COLUMN from_trade_volume_usd BIGINT NOT NULL,
+    MODIFY COLUMN synth_mint_count BIGINT NOT NULL,
+    MODIFY COLUMN synth_mint_fees BIGINT NOT NULL,
+    MODIFY COLUMN synth_mint_volume BIGINT NOT NULL,
+    MODIFY COLUMN synth_mint_volume_usd BIGINT NOT NULL,
+    MODIFY COLUMN synth_redeem_count BIGINT NOT NULL,
+    MODIFY COLUMN synth_redeem_fees BIGINT NOT NULL,
+    MODIFY COLUMN synth_redeem_volume BIGINT NOT NULL,
+    MODIFY COLUMN synth_redeem_volume_usd BIGINT NOT NULL,
+    MODIFY COLUMN to_asset_count BIGINT NOT NULL,
+    MODIFY COLUMN to_asset_fees BIGINT NOT NULL,
+    MODIFY COLUMN to_asset_volume BIGINT NOT NULL,
+    MODIFY COLUMN to_asset_volume_usd BIGINT NOT NULL,
+    MODIFY COLUMN to_rune_count BIGINT NOT NULL,
+    MODIFY COLUMN to_rune_fees BIGINT NOT NULL,
+    MODIFY COLUMN to_rune_volume BIGINT NOT NULL,
+    MODIFY COLUMN to_rune_volume_usd BIGINT NOT NULL,
+    MODIFY COLUMN to_trade_count BIGINT NOT NULL,
+    MODIFY COLUMN to_trade_fees BIGINT NOT NULL,
+    MODIFY COLUMN to_trade_volume BIGINT NOT NULL,
+    MODIFY COLUMN to_trade_volume_usd BIGINT NOT NULL,
+    MODIFY COLUMN total_count BIGINT NOT NULL,
+    MODIFY COLUMN total_fees BIGINT NOT NULL,
+    MODIFY COLUMN total_volume BIGINT NOT NULL,
+    MODIFY COLUMN total_volume_usd BIGINT NOT NULL,
+    MODIFY COLUMN revision INT NOT NULL DEFAULT 0;
+
+ALTER TABLE `runepool_unit_intervals`
+    MODIFY COLUMN count BIGINT NOT NULL,
+    MODIFY COLUMN units BIGINT NOT NULL,
+    MODIFY COLUMN revision INT NOT NULL DEFAULT 0;
@@ -0,0 +1,12 @@
+-- Where each ranged backfill (the `backfill` command) stopped, keyed by the range it was asked
+-- for so running the same command again picks up from there
+CREATE TABLE `backfill_progress` (
+    id BIGINT AUTO_INCREMENT PRIMARY KEY,
+    dataset VARCHAR(32) NOT NULL,
+    pool VARCHAR(64) NOT NULL DEFAULT '',
+    range_start TIMESTAMP NOT NULL,
+    range_end TIMESTAMP NOT NULL,
+    last_end_time TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+    UNIQUE KEY uq_backfill_progress_range (dataset, pool, range_start, range_end)
+) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4 COLLATE = utf8mb4_unicode_ci;
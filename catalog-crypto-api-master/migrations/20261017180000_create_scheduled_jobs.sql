@@ -0,0 +1,12 @@
+-- Operator commands for the scheduled jobs (pause, run now, fetch again from a point in time)
+-- and how their latest run went. Any replica writes the commands, the one holding the
+-- ingestion lease picks them up
+CREATE TABLE `scheduled_jobs` (
+    name VARCHAR(64) NOT NULL PRIMARY KEY,
+    paused BOOLEAN NOT NULL DEFAULT FALSE,
+    run_requested_at TIMESTAMP NULL,
+    rewind_to TIMESTAMP NULL,
+    last_started_at TIMESTAMP NULL,
+    last_finished_at TIMESTAMP NULL,
+    last_error TEXT NULL
+) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4 COLLATE = utf8mb4_unicode_ci;
@@ -0,0 +1,24 @@
+-- Postgres schema, applied by the app itself when DATABASE_URL is postgres:. It starts from the
+-- current shape of the mysql tables, amounts are BIGINT since postgres has no unsigned integers
+CREATE TABLE depth_intervals (
+    id BIGSERIAL PRIMARY KEY,
+    pool VARCHAR(64) NOT NULL,
+    start_time TIMESTAMPTZ NOT NULL,
+    end_time TIMESTAMPTZ NOT NULL,
+    asset_depth BIGINT NOT NULL,
+    asset_price DOUBLE PRECISION NOT NULL,
+    asset_price_usd DOUBLE PRECISION NOT NULL,
+    liquidity_units BIGINT NOT NULL,
+    luvi DOUBLE PRECISION NOT NULL,
+    members_count INT NOT NULL,
+    rune_depth BIGINT NOT NULL,
+    synth_supply BIGINT NOT NULL,
+    synth_units BIGINT NOT NULL,
+    units BIGINT NOT NULL,
+    revision INT NOT NULL DEFAULT 0,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT uq_depth_pool_time_range UNIQUE (pool, start_time, end_time)
+);
+
+CREATE INDEX idx_time_range ON depth_intervals (start_time, end_time);
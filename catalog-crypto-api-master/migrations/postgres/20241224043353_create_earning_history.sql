@@ -0,0 +1,20 @@
+CREATE TABLE earning_intervals (
+    id BIGSERIAL PRIMARY KEY,
+    start_time TIMESTAMPTZ NOT NULL,
+    end_time TIMESTAMPTZ NOT NULL,
+    avg_node_count DOUBLE PRECISION NOT NULL,
+    block_rewards BIGINT NOT NULL,
+    bonding_earnings BIGINT NOT NULL,
+    earnings BIGINT NOT NULL,
+    liquidity_earnings BIGINT NOT NULL,
+    liquidity_fees BIGINT NOT NULL,
+    rune_price_usd DOUBLE PRECISION NOT NULL,
+    pools JSONB NOT NULL,
+    revision INT NOT NULL DEFAULT 0,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT uq_earnings_time_range UNIQUE (start_time, end_time)
+);
+
+-- Backs the `pool` filter, which looks for the pool inside the json array
+CREATE INDEX idx_earnings_pools ON earning_intervals USING GIN (pools);
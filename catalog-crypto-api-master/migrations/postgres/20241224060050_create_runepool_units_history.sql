@@ -0,0 +1,11 @@
+CREATE TABLE runepool_unit_intervals (
+    id BIGSERIAL PRIMARY KEY,
+    start_time TIMESTAMPTZ NOT NULL,
+    end_time TIMESTAMPTZ NOT NULL,
+    count BIGINT NOT NULL,
+    units BIGINT NOT NULL,
+    revision INT NOT NULL DEFAULT 0,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT uq_runepool_units_time_range UNIQUE (start_time, end_time)
+);
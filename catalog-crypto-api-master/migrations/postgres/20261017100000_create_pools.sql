@@ -0,0 +1,12 @@
+-- Registry of the pools known to midgard, kept in sync by the pools cron
+CREATE TABLE pools (
+    id BIGSERIAL PRIMARY KEY,
+    asset VARCHAR(64) NOT NULL,
+    status VARCHAR(16) NOT NULL,
+    decimals INT NULL,
+    first_seen TIMESTAMPTZ NOT NULL,
+    last_synced TIMESTAMPTZ NOT NULL,
+    CONSTRAINT uq_pools_asset UNIQUE (asset)
+);
+
+CREATE INDEX idx_pools_status ON pools (status);
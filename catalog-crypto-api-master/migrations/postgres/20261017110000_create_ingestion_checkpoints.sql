@@ -0,0 +1,9 @@
+-- Where each backfill cron stopped, pool is empty for the datasets that are not per pool
+CREATE TABLE ingestion_checkpoints (
+    id BIGSERIAL PRIMARY KEY,
+    dataset VARCHAR(32) NOT NULL,
+    pool VARCHAR(64) NOT NULL DEFAULT '',
+    last_end_time TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT uq_checkpoint_dataset_pool UNIQUE (dataset, pool)
+);
@@ -0,0 +1,12 @@
+-- Where each ranged backfill (the `backfill` command) stopped, keyed by the range it was asked
+-- for so running the same command again picks up from there
+CREATE TABLE backfill_progress (
+    id BIGSERIAL PRIMARY KEY,
+    dataset VARCHAR(32) NOT NULL,
+    pool VARCHAR(64) NOT NULL DEFAULT '',
+    range_start TIMESTAMPTZ NOT NULL,
+    range_end TIMESTAMPTZ NOT NULL,
+    last_end_time TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT uq_backfill_progress_range UNIQUE (dataset, pool, range_start, range_end)
+);
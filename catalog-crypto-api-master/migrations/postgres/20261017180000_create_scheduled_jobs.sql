@@ -0,0 +1,12 @@
+-- Operator commands for the scheduled jobs (pause, run now, fetch again from a point in time)
+-- and how their latest run went. Any replica writes the commands, the one holding the
+-- ingestion lease picks them up
+CREATE TABLE scheduled_jobs (
+    name VARCHAR(64) NOT NULL PRIMARY KEY,
+    paused BOOLEAN NOT NULL DEFAULT FALSE,
+    run_requested_at TIMESTAMPTZ NULL,
+    rewind_to TIMESTAMPTZ NULL,
+    last_started_at TIMESTAMPTZ NULL,
+    last_finished_at TIMESTAMPTZ NULL,
+    last_error TEXT NULL
+);
@@ -0,0 +1,24 @@
+-- Sqlite schema, applied by the app itself when DATABASE_URL is sqlite:. Same tables as the
+-- postgres ones, timestamps are stored as rfc3339 text
+CREATE TABLE depth_intervals (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    pool VARCHAR(64) NOT NULL,
+    start_time TIMESTAMP NOT NULL,
+    end_time TIMESTAMP NOT NULL,
+    asset_depth BIGINT NOT NULL,
+    asset_price DOUBLE NOT NULL,
+    asset_price_usd DOUBLE NOT NULL,
+    liquidity_units BIGINT NOT NULL,
+    luvi DOUBLE NOT NULL,
+    members_count INT NOT NULL,
+    rune_depth BIGINT NOT NULL,
+    synth_supply BIGINT NOT NULL,
+    synth_units BIGINT NOT NULL,
+    units BIGINT NOT NULL,
+    revision INT NOT NULL DEFAULT 0,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT uq_depth_pool_time_range UNIQUE (pool, start_time, end_time)
+);
+
+CREATE INDEX idx_time_range ON depth_intervals (start_time, end_time);
@@ -0,0 +1,17 @@
+CREATE TABLE earning_intervals (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    start_time TIMESTAMP NOT NULL,
+    end_time TIMESTAMP NOT NULL,
+    avg_node_count DOUBLE NOT NULL,
+    block_rewards BIGINT NOT NULL,
+    bonding_earnings BIGINT NOT NULL,
+    earnings BIGINT NOT NULL,
+    liquidity_earnings BIGINT NOT NULL,
+    liquidity_fees BIGINT NOT NULL,
+    rune_price_usd DOUBLE NOT NULL,
+    pools TEXT NOT NULL, -- json array of per pool earnings
+    revision INT NOT NULL DEFAULT 0,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT uq_earnings_time_range UNIQUE (start_time, end_time)
+);
@@ -0,0 +1,45 @@
+CREATE TABLE swap_intervals (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    start_time TIMESTAMP NOT NULL,
+    end_time TIMESTAMP NOT NULL,
+    average_slip DOUBLE NOT NULL,
+    from_trade_average_slip DOUBLE NOT NULL,
+    from_trade_count BIGINT NOT NULL,
+    from_trade_fees BIGINT NOT NULL,
+    from_trade_volume BIGINT NOT NULL,
+    from_trade_volume_usd BIGINT NOT NULL,
+    rune_price_usd DOUBLE NOT NULL,
+    synth_mint_average_slip DOUBLE NOT NULL,
+    synth_mint_count BIGINT NOT NULL,
+    synth_mint_fees BIGINT NOT NULL,
+    synth_mint_volume BIGINT NOT NULL,
+    synth_mint_volume_usd BIGINT NOT NULL,
+    synth_redeem_average_slip DOUBLE NOT NULL,
+    synth_redeem_count BIGINT NOT NULL,
+    synth_redeem_fees BIGINT NOT NULL,
+    synth_redeem_volume BIGINT NOT NULL,
+    synth_redeem_volume_usd BIGINT NOT NULL,
+    to_asset_average_slip DOUBLE NOT NULL,
+    to_asset_count BIGINT NOT NULL,
+    to_asset_fees BIGINT NOT NULL,
+    to_asset_volume BIGINT NOT NULL,
+    to_asset_volume_usd BIGINT NOT NULL,
+    to_rune_average_slip DOUBLE NOT NULL,
+    to_rune_count BIGINT NOT NULL,
+    to_rune_fees BIGINT NOT NULL,
+    to_rune_volume BIGINT NOT NULL,
+    to_rune_volume_usd BIGINT NOT NULL,
+    to_trade_average_slip DOUBLE NOT NULL,
+    to_trade_count BIGINT NOT NULL,
+    to_trade_fees BIGINT NOT NULL,
+    to_trade_volume BIGINT NOT NULL,
+    to_trade_volume_usd BIGINT NOT NULL,
+    total_count BIGINT NOT NULL,
+    total_fees BIGINT NOT NULL,
+    total_volume BIGINT NOT NULL,
+    total_volume_usd BIGINT NOT NULL,
+    revision INT NOT NULL DEFAULT 0,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT uq_swap_time_range UNIQUE (start_time, end_time)
+);
@@ -0,0 +1,9 @@
+-- Where each backfill cron stopped, pool is empty for the datasets that are not per pool
+CREATE TABLE ingestion_checkpoints (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    dataset VARCHAR(32) NOT NULL,
+    pool VARCHAR(64) NOT NULL DEFAULT '',
+    last_end_time TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT uq_checkpoint_dataset_pool UNIQUE (dataset, pool)
+);
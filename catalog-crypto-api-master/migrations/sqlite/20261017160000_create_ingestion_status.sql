@@ -0,0 +1,8 @@
+-- Outcome of the latest fetches per dataset, written by the crons and read by GET /status
+CREATE TABLE ingestion_status (
+    dataset VARCHAR(32) NOT NULL PRIMARY KEY,
+    last_success_at TIMESTAMP NULL,
+    last_error TEXT NULL,
+    last_error_at TIMESTAMP NULL,
+    consecutive_failures INT NOT NULL DEFAULT 0
+);
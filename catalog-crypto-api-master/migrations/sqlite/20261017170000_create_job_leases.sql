@@ -0,0 +1,8 @@
+-- One row per lease, whoever holds an unexpired one runs the jobs behind it. The holder renews
+-- expires_at while it's alive, another instance takes the lease over once it runs out
+CREATE TABLE job_leases (
+    name VARCHAR(64) NOT NULL PRIMARY KEY,
+    holder VARCHAR(128) NOT NULL DEFAULT '',
+    expires_at TIMESTAMP NOT NULL,
+    renewed_at TIMESTAMP NULL
+);
@@ -0,0 +1,66 @@
+use crate::api::error::ApiError;
+use axum::{
+    extract::{Request, State},
+    http::{header::AUTHORIZATION, HeaderMap},
+    middleware::Next,
+    response::Response,
+};
+use tracing::warn;
+
+// Guards the /admin routes, layered with the token from ADMIN_TOKEN. Without a token they answer
+// 403 so a deployment that never set one doesn't expose them
+pub async fn require_admin_token(
+    State(token): State<Option<String>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let Some(token) = token else {
+        return Err(ApiError::Forbidden(
+            "Admin routes are disabled, set ADMIN_TOKEN to enable them".to_string(),
+        ));
+    };
+
+    if !is_authorized(request.headers(), &token) {
+        warn!(
+            "Rejected an unauthorized request to {}",
+            request.uri().path()
+        );
+        return Err(ApiError::Unauthorized);
+    }
+
+    Ok(next.run(request).await)
+}
+
+fn is_authorized(headers: &HeaderMap, token: &str) -> bool {
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|given| constant_time_eq(given.trim().as_bytes(), token.as_bytes()))
+}
+
+// Looks at every byte whatever the first mismatch, so the time taken doesn't tell how much of a
+// guess was right
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(authorization: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, authorization.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn only_accepts_the_admin_token() {
+        assert!(is_authorized(&headers("Bearer s3cret"), "s3cret"));
+        assert!(!is_authorized(&headers("Bearer s3cre"), "s3cret"));
+        assert!(!is_authorized(&headers("Bearer s3cret!"), "s3cret"));
+        assert!(!is_authorized(&headers("s3cret"), "s3cret"));
+        assert!(!is_authorized(&HeaderMap::new(), "s3cret"));
+    }
+}
@@ -0,0 +1,162 @@
+use crate::core::models::common::TimeRangeError;
+use axum::extract::rejection::{JsonRejection, QueryRejection};
+use axum::http::{header::WWW_AUTHENTICATE, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::Serialize;
+use serde_json::{json, Value};
+use tracing::error;
+use utoipa::ToSchema;
+
+// Everything a handler can fail with. Database errors only reach the logs, the client gets a
+// generic message so table and column names don't leak
+#[derive(Debug, thiserror::Error)]
+pub enum ApiError {
+    #[error("{message}")]
+    InvalidParams {
+        message: String,
+        details: Option<Value>,
+    },
+    #[error("{0}")]
+    NotFound(String),
+    #[error("Missing or invalid admin token")]
+    Unauthorized,
+    #[error("{0}")]
+    Forbidden(String),
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+impl ApiError {
+    pub fn invalid_params(message: impl Into<String>) -> Self {
+        ApiError::InvalidParams {
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    // A bad value for one parameter, named in the details so clients can point at the field
+    pub fn invalid_param(param: &str, message: impl Into<String>) -> Self {
+        ApiError::InvalidParams {
+            message: message.into(),
+            details: Some(json!({ "param": param })),
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::InvalidParams { .. } => StatusCode::BAD_REQUEST,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Unauthorized => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::InvalidParams { .. } => "invalid_params",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::Unauthorized => "unauthorized",
+            ApiError::Forbidden(_) => "forbidden",
+            ApiError::Database(_) => "internal_error",
+        }
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        ApiError::invalid_params(rejection.body_text())
+    }
+}
+
+impl From<TimeRangeError> for ApiError {
+    fn from(error: TimeRangeError) -> Self {
+        ApiError::invalid_param(error.param, error.message)
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        ApiError::invalid_params(rejection.body_text())
+    }
+}
+
+// The body of every error response
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorResponse {
+    // Stable, for clients to match on: invalid_params, not_found, unauthorized, forbidden or
+    // internal_error
+    pub code: String,
+    pub message: String,
+    // More about the error when there is any, e.g. the parameter that was rejected
+    #[schema(value_type = Option<Object>)]
+    pub details: Option<Value>,
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        let code = self.code().to_string();
+        let (message, details) = match self {
+            ApiError::Database(e) => {
+                error!("Database error: {}", e);
+                ("Internal server error".to_string(), None)
+            }
+            ApiError::InvalidParams { message, details } => (message, details),
+            other => (other.to_string(), None),
+        };
+
+        let body = Json(ErrorResponse {
+            code,
+            message,
+            details,
+        });
+        if status == StatusCode::UNAUTHORIZED {
+            return (status, [(WWW_AUTHENTICATE, "Bearer")], body).into_response();
+        }
+
+        (status, body).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::to_bytes;
+
+    async fn render(error: ApiError) -> (StatusCode, Value) {
+        let response = error.into_response();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn keeps_database_details_out_of_the_response() {
+        let (status, body) = render(ApiError::Database(sqlx::Error::ColumnNotFound(
+            "swap_intervals.total_volume".to_string(),
+        )))
+        .await;
+
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(
+            body,
+            json!({
+                "code": "internal_error",
+                "message": "Internal server error",
+                "details": null
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn names_the_rejected_parameter() {
+        let (status, body) =
+            render(ApiError::invalid_param("date_range", "Invalid date range")).await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "invalid_params");
+        assert_eq!(body["details"]["param"], "date_range");
+    }
+}
@@ -1,2 +1,5 @@
+pub mod auth;
+pub mod error;
 pub mod routes;
 pub mod server;
+pub mod state;
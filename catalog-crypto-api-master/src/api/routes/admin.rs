@@ -0,0 +1,146 @@
+use crate::api::error::{ApiError, ErrorResponse};
+use crate::core::models::common::Dataset;
+use crate::core::models::gaps::{GapsQueryParams, GapsResponse};
+use crate::services::repository::traits::GapRepository;
+use axum::extract::{rejection::QueryRejection, Query, State};
+use axum::Json;
+use std::sync::Arc;
+use tracing::info;
+
+#[utoipa::path(
+    get,
+    path = "/admin/gaps",
+    operation_id = "get_gaps",
+    tag = "admin",
+    security(("admin_token" = [])),
+    params(
+        ("dataset" = Dataset, Query, description = "Series to check (depth/earnings/swap/runepool)"),
+        ("pool" = Option<String>, Query, description = "Only check this pool, depth history only")
+    ),
+    responses(
+        (status = 200, description = "Missing and overlapping ranges in the stored intervals, oldest first", body = GapsResponse),
+        (status = 400, description = "Invalid query parameters", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid admin token", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    )
+)]
+pub async fn get_gaps(
+    State(repo): State<Arc<dyn GapRepository>>,
+    params: Result<Query<GapsQueryParams>, QueryRejection>,
+) -> Result<Json<GapsResponse>, ApiError> {
+    let Query(params) = params?;
+
+    info!("Received gaps request with params: {:#?}", params);
+
+    if params.pool.is_some() && params.dataset != Dataset::Depth {
+        return Err(ApiError::invalid_param(
+            "pool",
+            "Only depth history is kept per pool",
+        ));
+    }
+
+    let gaps = repo
+        .find_gaps(params.dataset, params.pool.as_deref())
+        .await?;
+    info!("Found {} gaps in {} history", gaps.len(), params.dataset);
+
+    Ok(Json(GapsResponse {
+        dataset: params.dataset,
+        gaps,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::models::depth_history::DepthInterval;
+    use crate::services::repository::memory::MemoryStore;
+    use axum::body::to_bytes;
+    use axum::http::StatusCode;
+    use axum::response::IntoResponse;
+    use chrono::{TimeZone, Utc};
+    use serde_json::json;
+
+    // 2023-11-14 00:00 UTC
+    const DAY_START: i64 = 1699920000;
+
+    fn interval(pool: &str, start: i64, end: i64) -> DepthInterval {
+        DepthInterval {
+            asset_depth: 1000,
+            asset_price: 1.0,
+            asset_price_usd: 2.0,
+            end_time: Utc.timestamp_opt(DAY_START + end, 0).unwrap(),
+            id: 0,
+            liquidity_units: 500,
+            luvi: 1.0,
+            members_count: 10,
+            pool: pool.to_string(),
+            revision: 0,
+            rune_depth: 2000,
+            start_time: Utc.timestamp_opt(DAY_START + start, 0).unwrap(),
+            synth_supply: 0,
+            synth_units: 0,
+            units: 100,
+        }
+    }
+
+    async fn get(query: &str) -> (StatusCode, serde_json::Value) {
+        let store = MemoryStore::new();
+        store.add_depth(&[
+            // BTC.BTC is missing its third hour
+            interval("BTC.BTC", 0, 3600),
+            interval("BTC.BTC", 3600, 7200),
+            interval("BTC.BTC", 10800, 14400),
+            // ETH.ETH has a half hour interval on top of its second hour
+            interval("ETH.ETH", 0, 3600),
+            interval("ETH.ETH", 3600, 7200),
+            interval("ETH.ETH", 5400, 9000),
+        ]);
+
+        let uri = format!("http://localhost/admin/gaps?{}", query)
+            .parse()
+            .unwrap();
+        let response = get_gaps(State(Arc::new(store)), Query::try_from_uri(&uri))
+            .await
+            .into_response();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn reports_missing_and_overlapping_hours() {
+        let (status, body) = get("dataset=depth").await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body["gaps"],
+            json!([
+                {
+                    "pool": "BTC.BTC",
+                    "kind": "missing",
+                    "startTime": (DAY_START + 7200).to_string(),
+                    "endTime": (DAY_START + 10800).to_string()
+                },
+                {
+                    "pool": "ETH.ETH",
+                    "kind": "overlap",
+                    "startTime": (DAY_START + 5400).to_string(),
+                    "endTime": (DAY_START + 7200).to_string()
+                }
+            ])
+        );
+
+        let (_, body) = get("dataset=depth&pool=ETH.ETH").await;
+        assert_eq!(body["gaps"].as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn rejects_pools_outside_depth_history() {
+        let (status, _) = get("dataset=swap&pool=BTC.BTC").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _) = get("dataset=trades").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
@@ -1,15 +1,17 @@
-use crate::core::models::common::{DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+use super::history::history_page;
+use crate::api::error::{ApiError, ErrorResponse};
+use crate::core::models::common::Interval;
 use crate::core::models::depth_history::{
-    DepthHistoryQueryParams, DepthHistoryResponse, DepthInterval, MetaStats,
+    DepthHistoryQueryParams, DepthHistoryResponse, DepthSortField,
 };
+use crate::core::models::fields::Projected;
+use crate::services::repository::traits::{DepthRepository, PoolRepository};
 use axum::{
-    extract::{Query, State},
-    response::IntoResponse,
+    extract::{rejection::QueryRejection, Query, State},
     Json,
 };
-use serde_json::json;
-use sqlx::MySqlPool;
-use tracing::{debug, error, info};
+use std::sync::Arc;
+use tracing::info;
 
 #[utoipa::path(
     get,
@@ -17,141 +19,60 @@ use tracing::{debug, error, info};
     operation_id = "get_depth_history",
     tag = "depth",
     params(
-        ("date_range" = Option<String>, Query, description = "Date range in format YYYY-MM-DD,YYYY-MM-DD"),
+        ("date_range" = Option<String>, Query, description = "Whole days in format YYYY-MM-DD,YYYY-MM-DD, both included. Can't be combined with `from` or `to`"),
+        ("from" = Option<String>, Query, description = "Intervals starting at or after this time, unix seconds or RFC3339 like `2024-01-01T00:00:00Z`"),
+        ("to" = Option<String>, Query, description = "Intervals starting before this time, unix seconds or RFC3339. Has to be after `from`"),
+        ("pool" = Option<String>, Query, description = "Filter by pool, e.g. `BTC.BTC`. Returns every tracked pool when omitted"),
         ("liquidity_gt" = Option<u64>, Query, description = "Filter by minimum liquidity. Default is `0`"),
-        ("sort_by" = Option<String>, Query, description = "Field to sort by. Default is `start_time`"),
-        ("order" = Option<String>, Query, description = "Sort order (asc/desc). Default is `desc`"),
-        ("page" = Option<u32>, Query, description = "Page number. Default is `0`"),
-        ("limit" = Option<u32>, Query, description = "Items per page. Default is `100`")
+        ("filter" = Option<String>, Query, description = "Conditions on the hourly rows, `column:op:value` separated by commas, e.g. `asset_price_usd:gte:1500,units:between:1000:2000`. The operators are lt, lte, gt, gte, eq and between, which takes `column:between:low:high` and includes both. Columns are the `sort_by` fields other than the times, `from` and `to` cover those"),
+        ("fields" = Option<String>, Query, description = "Columns to return in the intervals, e.g. `start_time,asset_price_usd,pool`. Takes the `sort_by` names and `pool`, every column is returned when omitted"),
+        ("interval" = Option<Interval>, Query, description = "Roll the hourly data up into buckets (hour/day/week/month/quarter/year), each bucket takes the values of its last hour"),
+        ("count" = Option<u32>, Query, description = "Number of intervals to return, takes precedence over `limit`"),
+        ("sort_by" = Option<DepthSortField>, Query, description = "Field to sort by. Default is `start_time`, `timestamp` is accepted as an alias"),
+        ("order" = Option<String>, Query, description = "Sort order (asc/desc). Default is `asc`"),
+        ("cursor" = Option<String>, Query, description = "`next_cursor` or `prev_cursor` of a previous response, the page continues from there. Needs the default `start_time` sorting and can't be combined with `page`"),
+        ("page" = Option<u32>, Query, description = "Page number. Default is `0`. Deep pages are slow and shift when new hours come in, `cursor` is better for walking the whole history"),
+        ("limit" = Option<u32>, Query, description = "Items per page. Default is `30`, at most `400`")
     ),
     responses(
-        (status = 200, description = "List of depth history intervals", body = DepthHistoryResponse),
-        (status = 500, description = "Internal server error")
+        (status = 200, description = "List of depth history intervals with the total count and pages, meta is null without a `pool` (it compares one pool's first and last hour) or when nothing matches", body = DepthHistoryResponse),
+        (status = 400, description = "Invalid query parameters or unknown pool", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
     )
 )]
 pub async fn get_depth_history(
-    State(pool): State<MySqlPool>,
-    Query(params): Query<DepthHistoryQueryParams>,
-) -> impl IntoResponse {
-    info!("Received depth history request with params: {:#?}", params);
-
-    let limit = params.limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE);
-    let offset = params.page.unwrap_or(0) * limit;
-    debug!("Using limit: {}, offset: {}", limit, offset);
+    State(repo): State<Arc<dyn DepthRepository>>,
+    State(pools): State<Arc<dyn PoolRepository>>,
+    params: Result<Query<DepthHistoryQueryParams>, QueryRejection>,
+) -> Result<Json<Projected<DepthHistoryResponse>>, ApiError> {
+    let Query(params) = params?;
 
-    let mut query = sqlx::QueryBuilder::new("SELECT * FROM `depth_intervals` WHERE 1=1");
-
-    // Handle date range
-    if let Some((start, end)) = params.parse_date_range() {
-        debug!("Date range filter: start={}, end={}", start, end);
-        query.push(" AND start_time >= ");
-        query.push_bind(start.naive_utc());
-        query.push(" AND end_time <= ");
-        query.push_bind(end.naive_utc());
-    } else {
-        debug!("No date range provided or invalid format");
-    }
+    info!("Received depth history request with params: {:#?}", params);
 
-    // Handle liquidity filter
-    if let Some(min_liquidity) = params.liquidity_gt {
-        debug!("Liquidity filter: > {}", min_liquidity);
-        query.push(" AND liquidity_units > ");
-        query.push_bind(min_liquidity);
+    if let Some(pool_name) = &params.pool {
+        if !pools.is_known_pool(pool_name).await? {
+            return Err(ApiError::invalid_param(
+                "pool",
+                format!("Unknown pool {}, see /pools for the known pools", pool_name),
+            ));
+        }
     }
 
-    // TODO // Handle interval next time if you can
-    // if let Some(interval) = &params.interval {
-    //     debug!("Interval filter: {}", interval);
-    //     query.push(" AND `interval` = ");
-    //     query.push_bind(interval.to_string());
-    // }
-
-    // Handle sorting
-    let sort_field = params.get_sort_field();
-    let sort_order = if params.order.as_deref() == Some("desc") {
-        "DESC"
-    } else {
-        "ASC"
-    };
-    debug!("Sorting by {} {}", sort_field, sort_order);
-    query.push(" ORDER BY ");
-    query.push(sort_field);
-    query.push(if params.order.as_deref() == Some("desc") {
-        " DESC"
-    } else {
-        " ASC"
-    });
-
-    // Add pagination
-    query.push(" LIMIT ");
-    query.push_bind(limit);
-    query.push(" OFFSET ");
-    query.push_bind(offset);
+    // Without a pool the first and last hour could belong to different pools, so there is no meta
+    let page = history_page(repo.as_ref(), &params, params.pool.is_some()).await?;
+    info!(
+        "Successfully retrieved {} depth intervals",
+        page.intervals.len()
+    );
 
-    let query_sql = query.sql();
-    debug!("Executing SQL query: {}", query_sql);
-
-    match query
-        .build_query_as::<DepthInterval>()
-        .fetch_all(&pool)
-        .await
-    {
-        Ok(intervals) => {
-            info!("Successfully retrieved {} depth intervals", intervals.len());
-
-            if intervals.is_empty() {
-                return Json(json!({
-                    "success": true,
-                    "data": "no data found in the database for the given params"
-                }))
-                .into_response();
-            }
-
-            // Calculate meta statistics
-            let meta_stats =
-                if let (Some(first), Some(last)) = (intervals.first(), intervals.last()) {
-                    MetaStats {
-                        start_time: first.start_time,
-                        end_time: last.end_time,
-                        start_asset_depth: first.asset_depth,
-                        end_asset_depth: last.asset_depth,
-                        start_rune_depth: first.rune_depth,
-                        end_rune_depth: last.rune_depth,
-                        start_lp_units: first.liquidity_units,
-                        end_lp_units: last.liquidity_units,
-                        start_member_count: first.members_count,
-                        end_member_count: last.members_count,
-                        start_synth_units: first.synth_units,
-                        end_synth_units: last.synth_units,
-                        // TODO Calculate this
-                        luvi_increase: 0.0,
-                        price_shift_loss: 0.0,
-                    }
-                } else {
-                    return Json(json!({
-                        "success": true,
-                        "data": "no data found"
-                    }))
-                    .into_response();
-                };
-
-            let response = DepthHistoryResponse {
-                intervals,
-                meta_stats,
-            };
-
-            Json(response).into_response()
-        }
-        Err(e) => {
-            error!("Database error when fetching depth intervals: {}", e);
-            (
-                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({
-                    "success": false,
-                    "error": format!("Database error: {}", e)
-                })),
-            )
-                .into_response()
-        }
-    }
+    Ok(Json(Projected {
+        response: DepthHistoryResponse {
+            intervals: page.intervals,
+            meta_stats: page.meta_stats,
+            next_cursor: page.next_cursor,
+            prev_cursor: page.prev_cursor,
+            pagination: page.pagination,
+        },
+        fields: page.fields,
+    }))
 }
@@ -1,35 +1,15 @@
-use crate::core::models::common::{DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
-use crate::core::models::earnings_history::{EarningsHistoryQueryParams, Pool};
-use crate::core::models::earnings_history::{EarningsHistoryResponse, IntervalData, MetaStats};
-use axum::http::StatusCode;
-use axum::Json;
-use axum::{
-    extract::{Query, State},
-    response::IntoResponse,
+use super::history::history_page;
+use crate::api::error::{ApiError, ErrorResponse};
+use crate::core::models::common::Interval;
+use crate::core::models::earnings_history::{
+    EarningsHistoryQueryParams, EarningsHistoryResponse, EarningsSortField,
 };
-use chrono::{DateTime, Utc};
-use serde_json::json;
-use serde_json::Value as JsonValue;
-use sqlx::prelude::FromRow;
-use sqlx::MySqlPool;
-use tracing::{debug, error, info};
-use utoipa::ToSchema;
-
-// !Just cuz in the models, we have intervalData which contains Vec<Pool> and rust don't know how to deserialize it
-// !So we need to create a new struct to deserialize it (Only solution i found)
-#[derive(Debug, FromRow, ToSchema, Clone)]
-struct EarningIntervalDB {
-    pub avg_node_count: f64,
-    pub block_rewards: u64,
-    pub bonding_earnings: u64,
-    pub earnings: u64,
-    pub end_time: DateTime<Utc>,
-    pub liquidity_earnings: u64,
-    pub liquidity_fees: u64,
-    pub rune_price_usd: f64,
-    pub start_time: DateTime<Utc>,
-    pub pools: JsonValue,
-}
+use crate::core::models::fields::Projected;
+use crate::services::repository::traits::{EarningsRepository, PoolRepository};
+use axum::extract::{rejection::QueryRejection, Query, State};
+use axum::Json;
+use std::sync::Arc;
+use tracing::info;
 
 // #[derive(Debug, Serialize, ToSchema)]
 // struct IntervalResponse {
@@ -59,173 +39,64 @@ struct EarningIntervalDB {
     path = "/earning_history",
     tag = "earnings",
     params(
-        ("date_range" = Option<String>, Query, description = "Date range in format YYYY-MM-DD,YYYY-MM-DD"),
+        ("date_range" = Option<String>, Query, description = "Whole days in format YYYY-MM-DD,YYYY-MM-DD, both included. Can't be combined with `from` or `to`"),
+        ("from" = Option<String>, Query, description = "Intervals starting at or after this time, unix seconds or RFC3339 like `2024-01-01T00:00:00Z`"),
+        ("to" = Option<String>, Query, description = "Intervals starting before this time, unix seconds or RFC3339. Has to be after `from`"),
         ("earnings_gt" = Option<u64>, Query, description = "Filter by minimum earnings. Default is `0`"),
         ("block_rewards_gt" = Option<u64>, Query, description = "Filter by minimum block rewards. Default is `0`"),
         ("node_count_gt" = Option<u64>, Query, description = "Filter by minimum node count. Default is `0`"),
+        ("filter" = Option<String>, Query, description = "Conditions on the hourly rows, `column:op:value` separated by commas, e.g. `rune_price_usd:gte:1.5,liquidity_fees:lt:100000`. The operators are lt, lte, gt, gte, eq and between, which takes `column:between:low:high` and includes both. Columns are the `sort_by` fields other than the times, `from` and `to` cover those"),
+        ("fields" = Option<String>, Query, description = "Columns to return in the intervals, e.g. `start_time,earnings,pools`. Takes the `sort_by` names and `pools`, every column is returned when omitted"),
         ("pool" = Option<String>, Query, description = "Filter by pool,(only returns data that contain the given pool name in the pools array)"),
-        ("sort_by" = Option<String>, Query, description = "Field to sort by. Default is `start_time`"),
-        ("order" = Option<String>, Query, description = "Sort order (asc/desc). Default is `desc`"),
-        ("page" = Option<u32>, Query, description = "Page number. Default is `0`"),
-        ("limit" = Option<u32>, Query, description = "Items per page. Default is `100`")
+        ("interval" = Option<Interval>, Query, description = "Roll the hourly data up into buckets (hour/day/week/month/quarter/year). Earnings are summed, node count and rune price are averaged"),
+        ("count" = Option<u32>, Query, description = "Number of intervals to return, takes precedence over `limit`"),
+        ("sort_by" = Option<EarningsSortField>, Query, description = "Field to sort by. Default is `start_time`, `timestamp` is accepted as an alias"),
+        ("order" = Option<String>, Query, description = "Sort order (asc/desc). Default is `asc`"),
+        ("cursor" = Option<String>, Query, description = "`next_cursor` or `prev_cursor` of a previous response, the page continues from there. Needs the default `start_time` sorting and can't be combined with `page`"),
+        ("page" = Option<u32>, Query, description = "Page number. Default is `0`. Deep pages are slow and shift when new hours come in, `cursor` is better for walking the whole history"),
+        ("limit" = Option<u32>, Query, description = "Items per page. Default is `30`, at most `400`")
     ),
     responses(
-        (status = 200, description = "List of earnings history intervals", body = EarningsHistoryResponse),
-        (status = 500, description = "Internal server error")
+        (status = 200, description = "List of earnings history intervals with the total count and pages, meta covers the whole filtered range and is null when nothing matches", body = EarningsHistoryResponse),
+        (status = 400, description = "Invalid query parameters or unknown pool", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
     )
 )]
 pub async fn get_earnings_history(
-    State(pool): State<MySqlPool>,
-    Query(params): Query<EarningsHistoryQueryParams>,
-) -> impl IntoResponse {
+    State(repo): State<Arc<dyn EarningsRepository>>,
+    State(pools): State<Arc<dyn PoolRepository>>,
+    params: Result<Query<EarningsHistoryQueryParams>, QueryRejection>,
+) -> Result<Json<Projected<EarningsHistoryResponse>>, ApiError> {
+    let Query(params) = params?;
+
     info!(
         "Received earnings history request with params: {:#?}",
         params
     );
 
-    let limit = params.limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE);
-    let offset = params.page.unwrap_or(0) * limit;
-    debug!("Using limit: {}, offset: {}", limit, offset);
-
-    let mut query = sqlx::QueryBuilder::new("SELECT * FROM `earning_intervals` WHERE 1=1");
-
-    // Add filters
-    if let Some((start, end)) = params.parse_date_range() {
-        debug!("Date range filter: start={}, end={}", start, end);
-        query
-            .push(" AND start_time >= ")
-            .push_bind(start)
-            .push(" AND end_time <= ")
-            .push_bind(end);
-    }
-
-    if let Some(min_earnings) = params.earnings_gt {
-        debug!("Earnings filter: > {}", min_earnings);
-        query.push(" AND earnings > ").push_bind(min_earnings);
-    }
-
-    if let Some(min_rewards) = params.block_rewards_gt {
-        debug!("Block rewards filter: > {}", min_rewards);
-        query.push(" AND block_rewards > ").push_bind(min_rewards);
-    }
-
-    if let Some(min_nodes) = params.node_count_gt {
-        debug!("Node count filter: > {}", min_nodes);
-        query.push(" AND avg_node_count > ").push_bind(min_nodes);
-    }
-
     if let Some(pool_name) = &params.pool {
-        debug!("Pool filter: {}", pool_name);
-        query.push(" AND JSON_CONTAINS(pools, JSON_ARRAY(JSON_OBJECT('pool', ");
-        query.push_bind(pool_name);
-        query.push(")))");
+        if !pools.is_known_pool(pool_name).await? {
+            return Err(ApiError::invalid_param(
+                "pool",
+                format!("Unknown pool {}, see /pools for the known pools", pool_name),
+            ));
+        }
     }
 
-    // Add sorting
-    let sort_field = params.get_sort_field();
-    let sort_order = if params.order.as_deref() == Some("desc") {
-        "DESC"
-    } else {
-        "ASC"
-    };
-    debug!("Sorting by {} {}", sort_field, sort_order);
-    query
-        .push(" ORDER BY ")
-        .push(sort_field)
-        .push(" ")
-        .push(sort_order);
-
-    query.push(" LIMIT ").push_bind(limit as i64);
-    query.push(" OFFSET ").push_bind(offset as i64);
-
-    let query_string = query.sql();
-    debug!("Executing query: {}", query_string);
-
-    match query
-        .build_query_as::<EarningIntervalDB>()
-        .fetch_all(&pool)
-        .await
-    {
-        Ok(db_intervals) => {
-            if db_intervals.is_empty() {
-                return Json(json!({
-                    "success": true,
-                    "data": "no data found in the database for the given params"
-                }))
-                .into_response();
-            }
-
-            let intervals: Result<Vec<IntervalData>, serde_json::Error> = db_intervals
-                .iter()
-                .map(|db| {
-                    let pools: Vec<Pool> = serde_json::from_value(db.pools.clone())?;
-
-                    Ok(IntervalData {
-                        start_time: db.start_time,
-                        end_time: db.end_time,
-                        avg_node_count: db.avg_node_count,
-                        block_rewards: db.block_rewards,
-                        bonding_earnings: db.bonding_earnings,
-                        earnings: db.earnings,
-                        liquidity_earnings: db.liquidity_earnings,
-                        liquidity_fees: db.liquidity_fees,
-                        rune_price_usd: db.rune_price_usd,
-                        pools,
-                    })
-                })
-                .collect();
-
-            match intervals {
-                Ok(intervals) => {
-                    // Calculate meta statistics
-                    let meta_stats = if let (Some(_first), Some(last)) =
-                        (db_intervals.first(), db_intervals.last())
-                    {
-                        MetaStats {
-                            avg_node_count: last.avg_node_count,
-                            block_rewards: last.block_rewards,
-                            bonding_earnings: last.bonding_earnings,
-                            earnings: last.earnings,
-                            end_time: last.end_time,
-                            liquidity_earnings: last.liquidity_earnings,
-                            liquidity_fees: last.liquidity_fees,
-                            pools: serde_json::from_value(last.pools.clone()).unwrap_or_default(),
-                        }
-                    } else {
-                        return Json(json!({
-                            "success": true,
-                            "data": "no data found"
-                        }))
-                        .into_response();
-                    };
-
-                    let response = EarningsHistoryResponse {
-                        intervals,
-                        meta_stats,
-                    };
+    let page = history_page(repo.as_ref(), &params, true).await?;
+    info!(
+        "Successfully retrieved {} earnings intervals",
+        page.intervals.len()
+    );
 
-                    Json(response).into_response()
-                }
-                Err(e) => {
-                    error!("Error parsing intervals: {}", e);
-                    (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(json!({
-                            "success": false,
-                            "error": format!("Error parsing JSON pools data: {}", e)
-                        })),
-                    )
-                        .into_response()
-                }
-            }
-        }
-        Err(e) => {
-            error!("Database error when fetching earnings intervals: {}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Database error: {}", e),
-            )
-                .into_response()
-        }
-    }
+    Ok(Json(Projected {
+        response: EarningsHistoryResponse {
+            intervals: page.intervals,
+            meta_stats: page.meta_stats,
+            next_cursor: page.next_cursor,
+            prev_cursor: page.prev_cursor,
+            pagination: page.pagination,
+        },
+        fields: page.fields,
+    }))
 }
@@ -0,0 +1,241 @@
+use crate::api::error::ApiError;
+use crate::core::models::common::{
+    Interval, TimeRange, TimeRangeError, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE,
+};
+use crate::core::models::depth_history::{self, DepthHistoryQueryParams, DepthInterval};
+use crate::core::models::earnings_history::{self, EarningsHistoryQueryParams, IntervalData};
+use crate::core::models::pagination::{cursor_page, parse_cursor, Cursor, Pagination};
+use crate::core::models::runepool_units_history::{
+    self, RunepoolUnitsHistoryQueryParams, RunepoolUnitsInterval,
+};
+use crate::core::models::swap_history::{SwapHistoryQueryParams, SwapInterval, SwapMeta};
+use crate::services::repository::traits::{
+    DepthRepository, EarningsRepository, RunepoolRepository, SwapRepository,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use tracing::debug;
+
+// The query parameters every history route takes, whatever the dataset's own filters are
+pub trait HistoryParams: Send + Sync {
+    fn time_range(&self) -> Result<TimeRange, TimeRangeError>;
+    fn check_filters(&self) -> Result<(), String>;
+    fn projection(&self) -> Result<Option<Vec<&'static str>>, String>;
+    fn get_sort_field(&self) -> &str;
+    fn interval(&self) -> Option<&Interval>;
+    fn cursor(&self) -> &Option<String>;
+    fn page(&self) -> Option<u32>;
+    // count works like midgard's, the number of intervals to return
+    fn page_size(&self) -> Option<u32>;
+}
+
+// Where a row sits for the cursors
+pub trait HistoryRow: Send {
+    fn cursor_key(&self) -> (DateTime<Utc>, i64);
+}
+
+// The dataset repositories as the history routes use them
+#[async_trait]
+pub trait HistoryRepository: Send + Sync {
+    type Params: HistoryParams;
+    type Row: HistoryRow;
+    type Meta: Send;
+
+    async fn query_intervals(
+        &self,
+        params: &Self::Params,
+        limit: u32,
+        offset: u32,
+        cursor: Option<&Cursor>,
+    ) -> Result<Vec<Self::Row>, sqlx::Error>;
+
+    async fn count_intervals(&self, params: &Self::Params) -> Result<u64, sqlx::Error>;
+
+    async fn fetch_meta(&self, params: &Self::Params) -> Result<Self::Meta, sqlx::Error>;
+}
+
+macro_rules! history_params {
+    ($($params:ty),*) => {$(
+        impl HistoryParams for $params {
+            fn time_range(&self) -> Result<TimeRange, TimeRangeError> {
+                <$params>::time_range(self)
+            }
+
+            fn check_filters(&self) -> Result<(), String> {
+                self.filters().map(|_| ())
+            }
+
+            fn projection(&self) -> Result<Option<Vec<&'static str>>, String> {
+                <$params>::projection(self)
+            }
+
+            fn get_sort_field(&self) -> &str {
+                <$params>::get_sort_field(self)
+            }
+
+            fn interval(&self) -> Option<&Interval> {
+                self.interval.as_ref()
+            }
+
+            fn cursor(&self) -> &Option<String> {
+                &self.cursor
+            }
+
+            fn page(&self) -> Option<u32> {
+                self.page
+            }
+
+            fn page_size(&self) -> Option<u32> {
+                self.count.or(self.limit)
+            }
+        }
+    )*};
+}
+
+history_params!(
+    DepthHistoryQueryParams,
+    EarningsHistoryQueryParams,
+    SwapHistoryQueryParams,
+    RunepoolUnitsHistoryQueryParams
+);
+
+macro_rules! history_rows {
+    ($($row:ty),*) => {$(
+        impl HistoryRow for $row {
+            fn cursor_key(&self) -> (DateTime<Utc>, i64) {
+                (self.start_time, self.id)
+            }
+        }
+    )*};
+}
+
+history_rows!(
+    DepthInterval,
+    IntervalData,
+    SwapInterval,
+    RunepoolUnitsInterval
+);
+
+macro_rules! history_repository {
+    ($($repo:ident => ($params:ty, $row:ty, $meta:ty)),*) => {$(
+        #[async_trait]
+        impl<'a> HistoryRepository for dyn $repo + 'a {
+            type Params = $params;
+            type Row = $row;
+            type Meta = $meta;
+
+            async fn query_intervals(
+                &self,
+                params: &$params,
+                limit: u32,
+                offset: u32,
+                cursor: Option<&Cursor>,
+            ) -> Result<Vec<$row>, sqlx::Error> {
+                $repo::query_intervals(self, params, limit, offset, cursor).await
+            }
+
+            async fn count_intervals(&self, params: &$params) -> Result<u64, sqlx::Error> {
+                $repo::count_intervals(self, params).await
+            }
+
+            async fn fetch_meta(&self, params: &$params) -> Result<$meta, sqlx::Error> {
+                $repo::fetch_meta(self, params).await
+            }
+        }
+    )*};
+}
+
+history_repository!(
+    DepthRepository => (DepthHistoryQueryParams, DepthInterval, depth_history::MetaStats),
+    EarningsRepository => (EarningsHistoryQueryParams, IntervalData, earnings_history::MetaStats),
+    SwapRepository => (SwapHistoryQueryParams, SwapInterval, SwapMeta),
+    RunepoolRepository => (
+        RunepoolUnitsHistoryQueryParams,
+        RunepoolUnitsInterval,
+        runepool_units_history::MetaStats
+    )
+);
+
+// One page of a history route, what goes into its response
+pub struct HistoryPage<T, M> {
+    pub intervals: Vec<T>,
+    pub meta_stats: Option<M>,
+    pub next_cursor: Option<String>,
+    pub prev_cursor: Option<String>,
+    pub pagination: Pagination,
+    pub fields: Option<Vec<&'static str>>,
+}
+
+// Checks the query, reads the page by offset or cursor and counts the whole filtered range. The
+// meta is over that range too, so a page past the end still gets it, and is left out when nothing
+// matched or the route has no meta to give for this query
+pub async fn history_page<R>(
+    repo: &R,
+    params: &R::Params,
+    with_meta: bool,
+) -> Result<HistoryPage<R::Row, R::Meta>, ApiError>
+where
+    R: HistoryRepository + ?Sized,
+{
+    params.time_range()?;
+    params
+        .check_filters()
+        .map_err(|e| ApiError::invalid_param("filter", e))?;
+    let fields = params
+        .projection()
+        .map_err(|e| ApiError::invalid_param("fields", e))?;
+
+    if let Some(interval) = params.interval() {
+        interval
+            .check_rollup()
+            .map_err(|e| ApiError::invalid_param("interval", e))?;
+    }
+
+    let cursor = parse_cursor(params.cursor(), params.page(), params.get_sort_field())
+        .map_err(|e| ApiError::invalid_param("cursor", e))?;
+
+    let limit = params
+        .page_size()
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .min(MAX_PAGE_SIZE);
+    let offset = params
+        .page()
+        .unwrap_or(0)
+        .checked_mul(limit)
+        .ok_or_else(|| ApiError::invalid_param("page", "page is too far out for this limit"))?;
+    debug!("Using limit: {}, offset: {}", limit, offset);
+
+    // The row past the page tells whether there is another one
+    let rows = repo
+        .query_intervals(params, limit + 1, offset, cursor.as_ref())
+        .await?;
+    let page = cursor_page(
+        rows,
+        limit,
+        offset,
+        cursor.as_ref(),
+        params.get_sort_field() == "start_time",
+        R::Row::cursor_key,
+    );
+    let total = repo.count_intervals(params).await?;
+
+    let meta_stats = if total == 0 || !with_meta {
+        None
+    } else {
+        Some(repo.fetch_meta(params).await?)
+    };
+
+    Ok(HistoryPage {
+        intervals: page.rows,
+        meta_stats,
+        next_cursor: page.next_cursor,
+        prev_cursor: page.prev_cursor,
+        pagination: Pagination::new(
+            total,
+            cursor.is_none().then(|| params.page().unwrap_or(0)),
+            limit,
+            page.has_more,
+        ),
+        fields,
+    })
+}
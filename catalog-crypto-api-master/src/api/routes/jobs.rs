@@ -0,0 +1,292 @@
+use crate::api::error::{ApiError, ErrorResponse};
+use crate::core::models::common::{Dataset, HISTORY_START_TIMESTAMP};
+use crate::core::models::jobs::{CheckpointRequest, JobInfo, JobsResponse};
+use crate::services::repository::traits::JobRepository;
+use crate::services::spawn::job_schedules;
+use axum::extract::{rejection::JsonRejection, Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+use tracing::info;
+
+// The schedule of the job, 404 for names the scheduler doesn't know
+fn find_schedule(name: &str) -> Result<String, ApiError> {
+    job_schedules()
+        .into_iter()
+        .find(|(job, _)| *job == name)
+        .map(|(_, schedule)| schedule)
+        .ok_or_else(|| ApiError::NotFound(format!("Unknown job {}", name)))
+}
+
+// Answers a command with the job as it stands after it
+async fn job_info(
+    repo: &dyn JobRepository,
+    name: &str,
+    schedule: &str,
+) -> Result<Json<JobInfo>, ApiError> {
+    let state = repo.job_state(name).await?;
+    Ok(Json(JobInfo::new(name, schedule, state)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/jobs",
+    operation_id = "list_jobs",
+    tag = "admin",
+    security(("admin_token" = [])),
+    responses(
+        (status = 200, description = "Every scheduled job with its commands and latest run", body = JobsResponse),
+        (status = 401, description = "Missing or invalid admin token", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    )
+)]
+pub async fn list_jobs(
+    State(repo): State<Arc<dyn JobRepository>>,
+) -> Result<Json<JobsResponse>, ApiError> {
+    let mut jobs = Vec::new();
+    for (name, schedule) in job_schedules() {
+        let state = repo.job_state(name).await?;
+        jobs.push(JobInfo::new(name, &schedule, state));
+    }
+
+    Ok(Json(JobsResponse { jobs }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/jobs/{name}/pause",
+    operation_id = "pause_job",
+    tag = "admin",
+    security(("admin_token" = [])),
+    params(("name" = String, Path, description = "Job name, see GET /admin/jobs")),
+    responses(
+        (status = 200, description = "The job skips its scheduled runs until it's resumed", body = JobInfo),
+        (status = 401, description = "Missing or invalid admin token", body = ErrorResponse),
+        (status = 404, description = "Unknown job", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    )
+)]
+pub async fn pause_job(
+    State(repo): State<Arc<dyn JobRepository>>,
+    Path(name): Path<String>,
+) -> Result<Json<JobInfo>, ApiError> {
+    set_paused(repo.as_ref(), &name, true).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/jobs/{name}/resume",
+    operation_id = "resume_job",
+    tag = "admin",
+    security(("admin_token" = [])),
+    params(("name" = String, Path, description = "Job name, see GET /admin/jobs")),
+    responses(
+        (status = 200, description = "The job is back on its schedule", body = JobInfo),
+        (status = 401, description = "Missing or invalid admin token", body = ErrorResponse),
+        (status = 404, description = "Unknown job", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    )
+)]
+pub async fn resume_job(
+    State(repo): State<Arc<dyn JobRepository>>,
+    Path(name): Path<String>,
+) -> Result<Json<JobInfo>, ApiError> {
+    set_paused(repo.as_ref(), &name, false).await
+}
+
+async fn set_paused(
+    repo: &dyn JobRepository,
+    name: &str,
+    paused: bool,
+) -> Result<Json<JobInfo>, ApiError> {
+    let schedule = find_schedule(name)?;
+
+    repo.set_paused(name, paused).await?;
+    info!("Job {} {}", name, if paused { "paused" } else { "resumed" });
+
+    job_info(repo, name, &schedule).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/jobs/{name}/run",
+    operation_id = "run_job",
+    tag = "admin",
+    security(("admin_token" = [])),
+    params(("name" = String, Path, description = "Job name, see GET /admin/jobs")),
+    responses(
+        (status = 202, description = "The job runs within a few seconds, paused or not. A run that is still going is let finish first", body = JobInfo),
+        (status = 401, description = "Missing or invalid admin token", body = ErrorResponse),
+        (status = 404, description = "Unknown job", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    )
+)]
+pub async fn run_job(
+    State(repo): State<Arc<dyn JobRepository>>,
+    Path(name): Path<String>,
+) -> Result<(StatusCode, Json<JobInfo>), ApiError> {
+    let schedule = find_schedule(&name)?;
+
+    repo.request_run(&name, Utc::now()).await?;
+    info!("Requested a run of job {}", name);
+
+    let job = job_info(repo.as_ref(), &name, &schedule).await?;
+    Ok((StatusCode::ACCEPTED, job))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/jobs/{name}/checkpoint",
+    operation_id = "reset_job_checkpoint",
+    tag = "admin",
+    security(("admin_token" = [])),
+    params(("name" = String, Path, description = "Dataset job: depth, earnings, swap or runepool")),
+    request_body = CheckpointRequest,
+    responses(
+        (status = 200, description = "The job's next run fetches everything from the timestamp up to now again", body = JobInfo),
+        (status = 400, description = "Invalid timestamp, or a job without a checkpoint", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid admin token", body = ErrorResponse),
+        (status = 404, description = "Unknown job", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    )
+)]
+pub async fn reset_checkpoint(
+    State(repo): State<Arc<dyn JobRepository>>,
+    Path(name): Path<String>,
+    body: Result<Json<CheckpointRequest>, JsonRejection>,
+) -> Result<Json<JobInfo>, ApiError> {
+    let schedule = find_schedule(&name)?;
+
+    if Dataset::try_from(name.clone()).is_err() {
+        return Err(ApiError::invalid_params(format!(
+            "Job {} doesn't keep a checkpoint",
+            name
+        )));
+    }
+
+    let Json(request) = body?;
+
+    let rewind_to = DateTime::from_timestamp(request.timestamp, 0)
+        .filter(|time| request.timestamp >= HISTORY_START_TIMESTAMP && *time < Utc::now())
+        .ok_or_else(|| {
+            ApiError::invalid_param(
+                "timestamp",
+                format!(
+                    "timestamp must be between {} and now",
+                    HISTORY_START_TIMESTAMP
+                ),
+            )
+        })?;
+
+    repo.set_rewind(&name, rewind_to).await?;
+    info!("Job {} will fetch again from {}", name, rewind_to);
+
+    job_info(repo.as_ref(), &name, &schedule).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::repository::memory::MemoryStore;
+    use axum::body::to_bytes;
+    use axum::response::{IntoResponse, Response};
+
+    async fn body(response: Response) -> (StatusCode, serde_json::Value) {
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    fn checkpoint(timestamp: i64) -> Result<Json<CheckpointRequest>, JsonRejection> {
+        Ok(Json(CheckpointRequest { timestamp }))
+    }
+
+    #[tokio::test]
+    async fn pauses_resumes_and_runs_jobs() {
+        let repo: Arc<dyn JobRepository> = Arc::new(MemoryStore::new());
+
+        let (status, job) = body(
+            pause_job(State(repo.clone()), Path("swap".to_string()))
+                .await
+                .into_response(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(job["paused"], true);
+
+        let (status, job) = body(
+            run_job(State(repo.clone()), Path("swap".to_string()))
+                .await
+                .into_response(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert!(job["runRequestedAt"].is_string());
+
+        let (_, jobs) = body(list_jobs(State(repo.clone())).await.into_response()).await;
+        let names: Vec<&str> = jobs["jobs"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|job| job["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "pools",
+                "depth",
+                "earnings",
+                "swap",
+                "runepool",
+                "gap_repair"
+            ]
+        );
+        assert_eq!(jobs["jobs"][3]["paused"], true);
+        assert_eq!(jobs["jobs"][3]["schedule"], "0 * * * *");
+        assert_eq!(jobs["jobs"][2]["paused"], false);
+
+        let (_, job) = body(
+            resume_job(State(repo.clone()), Path("swap".to_string()))
+                .await
+                .into_response(),
+        )
+        .await;
+        assert_eq!(job["paused"], false);
+
+        let (status, _) = body(
+            pause_job(State(repo), Path("trades".to_string()))
+                .await
+                .into_response(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn resets_the_checkpoint_of_dataset_jobs_only() {
+        let repo: Arc<dyn JobRepository> = Arc::new(MemoryStore::new());
+        let reset = |name: &str, timestamp: i64| {
+            reset_checkpoint(
+                State(repo.clone()),
+                Path(name.to_string()),
+                checkpoint(timestamp),
+            )
+        };
+
+        // 2024-01-01 00:00 UTC
+        let (status, job) = body(reset("depth", 1704067200).await.into_response()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(job["rewindTo"], "1704067200");
+
+        let (status, _) = body(reset("gap_repair", 1704067200).await.into_response()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _) = body(reset("depth", 0).await.into_response()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let tomorrow = Utc::now().timestamp() + 86400;
+        let (status, _) = body(reset("depth", tomorrow).await.into_response()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
@@ -1,4 +1,9 @@
+pub mod admin;
 pub mod depth;
 pub mod earnings;
+mod history;
+pub mod jobs;
+pub mod pools;
 pub mod runepool;
+pub mod status;
 pub mod swap;
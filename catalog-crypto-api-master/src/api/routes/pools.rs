@@ -0,0 +1,98 @@
+use crate::api::error::{ApiError, ErrorResponse};
+use crate::core::models::pools::{PoolRecord, PoolsQueryParams};
+use crate::services::repository::traits::PoolRepository;
+use axum::extract::{rejection::QueryRejection, Path, Query, State};
+use axum::Json;
+use std::sync::Arc;
+use tracing::{debug, info};
+
+#[utoipa::path(
+    get,
+    path = "/pools",
+    operation_id = "get_pools",
+    tag = "pools",
+    params(
+        ("status" = Option<String>, Query, description = "Filter by pool status (available/staged/suspended)")
+    ),
+    responses(
+        (status = 200, description = "List of pools known to midgard", body = Vec<PoolRecord>),
+        (status = 400, description = "Invalid query parameters", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    )
+)]
+pub async fn get_pools(
+    State(pools): State<Arc<dyn PoolRepository>>,
+    params: Result<Query<PoolsQueryParams>, QueryRejection>,
+) -> Result<Json<Vec<PoolRecord>>, ApiError> {
+    let Query(params) = params?;
+
+    info!("Received pools request with params: {:#?}", params);
+
+    if let Some(status) = &params.status {
+        debug!("Status filter: {}", status);
+    }
+
+    let records = pools.list_pools(params.status.as_deref()).await?;
+    info!("Successfully retrieved {} pools", records.len());
+
+    Ok(Json(records))
+}
+
+#[utoipa::path(
+    get,
+    path = "/pools/{asset}",
+    operation_id = "get_pool",
+    tag = "pools",
+    params(
+        ("asset" = String, Path, description = "Pool asset, e.g. `BTC.BTC`")
+    ),
+    responses(
+        (status = 200, description = "The pool", body = PoolRecord),
+        (status = 404, description = "Pool not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    )
+)]
+pub async fn get_pool(
+    State(pools): State<Arc<dyn PoolRepository>>,
+    Path(asset): Path<String>,
+) -> Result<Json<PoolRecord>, ApiError> {
+    info!("Received pool request for {}", asset);
+
+    match pools.get_pool(&asset).await? {
+        Some(record) => Ok(Json(record)),
+        None => Err(ApiError::NotFound(format!("Pool {} not found", asset))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::models::pools::PoolDetail;
+    use crate::services::repository::memory::MemoryStore;
+    use axum::http::StatusCode;
+    use axum::response::IntoResponse;
+
+    #[tokio::test]
+    async fn returns_not_found_for_unknown_pools() {
+        let store = MemoryStore::new();
+        store
+            .store_pools(&[PoolDetail {
+                asset: "BTC.BTC".to_string(),
+                status: "available".to_string(),
+                native_decimal: "8".to_string(),
+            }])
+            .await
+            .unwrap();
+        let store = Arc::new(store);
+
+        let known = get_pool(State(store.clone()), Path("BTC.BTC".to_string()))
+            .await
+            .into_response();
+        let unknown = get_pool(State(store), Path("ETH.ETH".to_string()))
+            .await
+            .into_response();
+
+        assert_eq!(known.status(), StatusCode::OK);
+        assert_eq!(unknown.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -1,17 +1,15 @@
-use crate::core::models::common::{DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+use super::history::history_page;
+use crate::api::error::{ApiError, ErrorResponse};
+use crate::core::models::common::Interval;
+use crate::core::models::fields::Projected;
 use crate::core::models::runepool_units_history::{
-    MetaStats, RunepoolUnitsHistoryQueryParams, RunepoolUnitsHistoryResponse, RunepoolUnitsInterval,
+    RunepoolUnitsHistoryQueryParams, RunepoolUnitsHistoryResponse, RunepoolUnitsSortField,
 };
-use axum::http::StatusCode;
-
+use crate::services::repository::traits::RunepoolRepository;
+use axum::extract::{rejection::QueryRejection, Query, State};
 use axum::Json;
-use axum::{
-    extract::{Query, State},
-    response::IntoResponse,
-};
-use serde_json::json;
-use sqlx::MySqlPool;
-use tracing::{debug, error, info};
+use std::sync::Arc;
+use tracing::info;
 
 #[utoipa::path(
     get,
@@ -19,120 +17,51 @@ use tracing::{debug, error, info};
     path = "/runepool_units_history",
     tag = "runepool",
     params(
-        ("date_range" = Option<String>, Query, description = "Date range in format YYYY-MM-DD,YYYY-MM-DD"),
+        ("date_range" = Option<String>, Query, description = "Whole days in format YYYY-MM-DD,YYYY-MM-DD, both included. Can't be combined with `from` or `to`"),
+        ("from" = Option<String>, Query, description = "Intervals starting at or after this time, unix seconds or RFC3339 like `2024-01-01T00:00:00Z`"),
+        ("to" = Option<String>, Query, description = "Intervals starting before this time, unix seconds or RFC3339. Has to be after `from`"),
         ("units_gt" = Option<u64>, Query, description = "Filter by minimum units. Default is `0`"),
-        ("sort_by" = Option<String>, Query, description = "Field to sort by. Default is `start_time`"),
-        ("order" = Option<String>, Query, description = "Sort order (asc/desc). Default is `desc`"),
-        ("page" = Option<u32>, Query, description = "Page number. Default is `0`"),
-        ("limit" = Option<u32>, Query, description = "Items per page. Default is `100`")
+        ("filter" = Option<String>, Query, description = "Conditions on the hourly rows, `column:op:value` separated by commas, e.g. `count:gte:10,units:lt:5000000`. The operators are lt, lte, gt, gte, eq and between, which takes `column:between:low:high` and includes both. Columns are the `sort_by` fields other than the times, `from` and `to` cover those"),
+        ("fields" = Option<String>, Query, description = "Columns to return in the intervals, e.g. `start_time,units`. Takes the `sort_by` names, every column is returned when omitted"),
+        ("interval" = Option<Interval>, Query, description = "Roll the hourly data up into buckets (hour/day/week/month/quarter/year), each bucket reports its last count and units"),
+        ("count" = Option<u32>, Query, description = "Number of intervals to return, takes precedence over `limit`"),
+        ("sort_by" = Option<RunepoolUnitsSortField>, Query, description = "Field to sort by. Default is `start_time`, `timestamp` is accepted as an alias"),
+        ("order" = Option<String>, Query, description = "Sort order (asc/desc). Default is `asc`"),
+        ("cursor" = Option<String>, Query, description = "`next_cursor` or `prev_cursor` of a previous response, the page continues from there. Needs the default `start_time` sorting and can't be combined with `page`"),
+        ("page" = Option<u32>, Query, description = "Page number. Default is `0`. Deep pages are slow and shift when new hours come in, `cursor` is better for walking the whole history"),
+        ("limit" = Option<u32>, Query, description = "Items per page. Default is `30`, at most `400`")
     ),
     responses(
-        (status = 200, description = "List of runepool units history intervals", body = RunepoolUnitsHistoryResponse),
-        (status = 500, description = "Internal server error")
+        (status = 200, description = "List of runepool units history intervals with the total count and pages, meta covers the whole filtered range and is null when nothing matches", body = RunepoolUnitsHistoryResponse),
+        (status = 400, description = "Invalid query parameters", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
     )
 )]
 pub async fn get_runepool_units_history(
-    State(pool): State<MySqlPool>,
-    Query(params): Query<RunepoolUnitsHistoryQueryParams>,
-) -> impl IntoResponse {
+    State(repo): State<Arc<dyn RunepoolRepository>>,
+    params: Result<Query<RunepoolUnitsHistoryQueryParams>, QueryRejection>,
+) -> Result<Json<Projected<RunepoolUnitsHistoryResponse>>, ApiError> {
+    let Query(params) = params?;
+
     info!(
         "Received runepool units history request with params: {:#?}",
         params
     );
 
-    let limit = params.limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE);
-    let offset = params.page.unwrap_or(0) * limit;
-    debug!("Using limit: {}, offset: {}", limit, offset);
-
-    let mut query = sqlx::QueryBuilder::new("SELECT * FROM `runepool_unit_intervals` WHERE 1=1");
-
-    if let Some((start, end)) = params.parse_date_range() {
-        debug!("Date range filter: start={}, end={}", start, end);
-        query
-            .push(" AND start_time >= ")
-            .push_bind(start)
-            .push(" AND end_time <= ")
-            .push_bind(end);
-    }
-
-    if let Some(min_units) = params.units_gt {
-        debug!("Units filter: > {}", min_units);
-        query.push(" AND units > ").push_bind(min_units);
-    }
-
-    let sort_field = params.get_sort_field();
-    let sort_order = if params.order.as_deref() == Some("desc") {
-        "DESC"
-    } else {
-        "ASC"
-    };
-
-    query
-        .push(" ORDER BY ")
-        .push(sort_field)
-        .push(" ")
-        .push(sort_order);
-
-    query.push(" LIMIT ").push_bind(limit as i64);
-    query.push(" OFFSET ").push_bind(offset as i64);
-
-    let query_string = query.sql();
-    debug!("Executing query: {}", query_string);
-
-    match query
-        .build_query_as::<RunepoolUnitsInterval>()
-        .fetch_all(&pool)
-        .await
-    {
-        Ok(intervals) => {
-            info!(
-                "Successfully retrieved {} runepool unit intervals",
-                intervals.len()
-            );
-
-            if intervals.is_empty() {
-                return Json(json!({
-                    "success": true,
-                    "data": "no data found in the database for the given params"
-                }))
-                .into_response();
-            }
-
-            let meta_stats =
-                if let (Some(first), Some(last)) = (intervals.first(), intervals.last()) {
-                    MetaStats {
-                        start_time: first.start_time,
-                        end_time: last.end_time,
-                        start_count: first.count,
-                        end_count: last.count,
-                        start_units: first.units,
-                        end_units: last.units,
-                    }
-                } else {
-                    return Json(json!({
-                        "success": true,
-                        "data": "no data found in the database for the given params"
-                    }))
-                    .into_response();
-                };
-
-            let response = RunepoolUnitsHistoryResponse {
-                intervals,
-                meta_stats,
-            };
+    let page = history_page(repo.as_ref(), &params, true).await?;
+    info!(
+        "Successfully retrieved {} runepool units intervals",
+        page.intervals.len()
+    );
 
-            Json(response).into_response()
-        }
-        Err(e) => {
-            error!("Database error: {}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({
-                    "success": false,
-                    "error": format!("Database error: {}", e)
-                })),
-            )
-                .into_response()
-        }
-    }
+    Ok(Json(Projected {
+        response: RunepoolUnitsHistoryResponse {
+            intervals: page.intervals,
+            meta_stats: page.meta_stats,
+            next_cursor: page.next_cursor,
+            prev_cursor: page.prev_cursor,
+            pagination: page.pagination,
+        },
+        fields: page.fields,
+    }))
 }
@@ -0,0 +1,135 @@
+use crate::api::error::{ApiError, ErrorResponse};
+use crate::core::models::{common::Dataset, status::StatusResponse};
+use crate::services::{client::get_stale_after_minutes, repository::traits::StatusRepository};
+use axum::extract::State;
+use axum::Json;
+use chrono::{Duration, Utc};
+use std::sync::Arc;
+use tracing::warn;
+
+#[utoipa::path(
+    get,
+    path = "/status",
+    operation_id = "get_status",
+    tag = "status",
+    responses(
+        (status = 200, description = "How current every dataset is and how its last fetches went. A dataset is stale once its newest interval ended longer ago than staleAfterSeconds (STALE_AFTER_MINUTES, two hours by default) or when nothing is stored yet", body = StatusResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    )
+)]
+pub async fn get_status(
+    State(repo): State<Arc<dyn StatusRepository>>,
+) -> Result<Json<StatusResponse>, ApiError> {
+    let now = Utc::now();
+    let stale_after = Duration::minutes(get_stale_after_minutes());
+
+    let mut datasets = Vec::new();
+    for dataset in [
+        Dataset::Depth,
+        Dataset::Earnings,
+        Dataset::Swap,
+        Dataset::Runepool,
+    ] {
+        let status = repo.dataset_status(dataset).await?;
+        datasets.push(status.check_freshness(now, stale_after));
+    }
+
+    for status in datasets.iter().filter(|status| status.stale) {
+        warn!(
+            "{} history is stale, {} failed fetches in a row",
+            status.dataset, status.consecutive_failures
+        );
+    }
+
+    Ok(Json(StatusResponse {
+        healthy: datasets.iter().all(|status| !status.stale),
+        stale_after_seconds: stale_after.num_seconds(),
+        datasets,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::models::{
+        runepool_units_history::RunepoolUnitsInterval, status::IngestionState,
+    };
+    use crate::services::repository::memory::MemoryStore;
+    use axum::body::to_bytes;
+    use axum::http::StatusCode;
+    use axum::response::IntoResponse;
+    use serde_json::json;
+
+    fn interval(end_time: chrono::DateTime<Utc>) -> RunepoolUnitsInterval {
+        RunepoolUnitsInterval {
+            count: 1,
+            end_time,
+            id: 0,
+            revision: 0,
+            start_time: end_time - Duration::hours(1),
+            units: 100,
+        }
+    }
+
+    async fn get(store: MemoryStore) -> serde_json::Value {
+        let response = get_status(State(Arc::new(store))).await.into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn reports_stale_and_failing_datasets() {
+        let store = MemoryStore::new();
+        let recent = Utc::now() - Duration::minutes(30);
+        let failed_at = Utc::now() - Duration::minutes(5);
+        store.add_runepool(&[interval(recent - Duration::hours(1)), interval(recent)]);
+        store.set_ingestion_state(
+            Dataset::Runepool,
+            IngestionState {
+                last_success_at: Some(recent),
+                last_error: Some("midgard returned 503".to_string()),
+                last_error_at: Some(failed_at),
+                consecutive_failures: 2,
+            },
+        );
+
+        let body = get(store).await;
+
+        assert_eq!(body["healthy"], json!(false));
+        assert_eq!(body["staleAfterSeconds"], json!(7200));
+
+        let runepool = &body["datasets"][3];
+        assert_eq!(runepool["dataset"], json!("runepool"));
+        assert_eq!(
+            runepool["latestEndTime"],
+            json!(recent.timestamp().to_string())
+        );
+        assert_eq!(runepool["rowCount"], json!(2));
+        assert_eq!(runepool["lastError"], json!("midgard returned 503"));
+        assert_eq!(
+            runepool["lastErrorAt"],
+            json!(failed_at.timestamp().to_string())
+        );
+        assert_eq!(runepool["consecutiveFailures"], json!(2));
+        assert_eq!(runepool["stale"], json!(false));
+
+        // Nothing stored for depth history yet
+        let depth = &body["datasets"][0];
+        assert_eq!(depth["latestEndTime"], json!(null));
+        assert_eq!(depth["lagSeconds"], json!(null));
+        assert_eq!(depth["stale"], json!(true));
+    }
+
+    #[tokio::test]
+    async fn flags_datasets_older_than_two_hours() {
+        let store = MemoryStore::new();
+        store.add_runepool(&[interval(Utc::now() - Duration::hours(3))]);
+
+        let body = get(store).await;
+
+        let runepool = &body["datasets"][3];
+        assert_eq!(runepool["stale"], json!(true));
+        assert!(runepool["lagSeconds"].as_i64().unwrap() >= 3 * 3600);
+    }
+}
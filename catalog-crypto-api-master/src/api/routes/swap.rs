@@ -1,17 +1,14 @@
-use crate::core::models::common::{DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
-use crate::core::models::swap_history::SwapHistoryQueryParams;
+use super::history::history_page;
+use crate::api::error::{ApiError, ErrorResponse};
+use crate::core::models::common::Interval;
+use crate::core::models::fields::Projected;
 use crate::core::models::swap_history::SwapHistoryResponse;
-use crate::core::models::swap_history::SwapInterval;
-use crate::core::models::swap_history::SwapMeta;
-use axum::http::StatusCode;
+use crate::core::models::swap_history::{SwapHistoryQueryParams, SwapSortField};
+use crate::services::repository::traits::SwapRepository;
+use axum::extract::{rejection::QueryRejection, Query, State};
 use axum::Json;
-use axum::{
-    extract::{Query, State},
-    response::IntoResponse,
-};
-use serde_json::json;
-use sqlx::MySqlPool;
-use tracing::{debug, error, info};
+use std::sync::Arc;
+use tracing::info;
 
 #[utoipa::path(
     get,
@@ -19,152 +16,49 @@ use tracing::{debug, error, info};
     operation_id = "get_swap_history",
     tag = "swap",
     params(
-        ("date_range" = Option<String>, Query, description = "Date range in format YYYY-MM-DD,YYYY-MM-DD"),
-        ("page" = Option<u32>, Query, description = "Page number. Default is `0`"),
-        ("limit" = Option<u32>, Query, description = "Items per page. Default is `100`"),
-        ("sort_by" = Option<String>, Query, description = "Field to sort by. Default is `start_time`"),
-        ("order" = Option<String>, Query, description = "Sort order (asc/desc). Default is `desc`"),
+        ("date_range" = Option<String>, Query, description = "Whole days in format YYYY-MM-DD,YYYY-MM-DD, both included. Can't be combined with `from` or `to`"),
+        ("from" = Option<String>, Query, description = "Intervals starting at or after this time, unix seconds or RFC3339 like `2024-01-01T00:00:00Z`"),
+        ("to" = Option<String>, Query, description = "Intervals starting before this time, unix seconds or RFC3339. Has to be after `from`"),
+        ("page" = Option<u32>, Query, description = "Page number. Default is `0`. Deep pages are slow and shift when new hours come in, `cursor` is better for walking the whole history"),
+        ("limit" = Option<u32>, Query, description = "Items per page. Default is `30`, at most `400`"),
+        ("interval" = Option<Interval>, Query, description = "Roll the hourly data up into buckets (hour/day/week/month/quarter/year). Volumes, fees and counts are summed, slips are count-weighted averages"),
+        ("count" = Option<u32>, Query, description = "Number of intervals to return, takes precedence over `limit`"),
+        ("sort_by" = Option<SwapSortField>, Query, description = "Field to sort by. Default is `start_time`, `timestamp`, `volume`, `fees` and `count` are accepted as aliases"),
+        ("order" = Option<String>, Query, description = "Sort order (asc/desc). Default is `asc`"),
+        ("cursor" = Option<String>, Query, description = "`next_cursor` or `prev_cursor` of a previous response, the page continues from there. Needs the default `start_time` sorting and can't be combined with `page`"),
         ("volume_gt" = Option<u64>, Query, description = "Filter by minimum volume. Default is `0`"),
-        ("fees_gt" = Option<u64>, Query, description = "Filter by minimum fees. Default is `0`")
+        ("fees_gt" = Option<u64>, Query, description = "Filter by minimum fees. Default is `0`"),
+        ("filter" = Option<String>, Query, description = "Conditions on the hourly rows, `column:op:value` separated by commas, e.g. `total_count:lt:100,average_slip:between:1:5`. The operators are lt, lte, gt, gte, eq and between, which takes `column:between:low:high` and includes both. Columns are the `sort_by` fields other than the times, `from` and `to` cover those"),
+        ("fields" = Option<String>, Query, description = "Columns to return in the intervals, e.g. `start_time,total_volume,total_fees`. Takes the `sort_by` names, every column is returned when omitted")
     ),
     responses(
-        (status = 200, description = "List of swap history intervals", body = SwapHistoryResponse),
-        (status = 500, description = "Internal server error")
+        (status = 200, description = "List of swap history intervals with the total count and pages, meta covers the whole filtered range and is null when nothing matches", body = SwapHistoryResponse),
+        (status = 400, description = "Invalid query parameters", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
     )
 )]
 pub async fn get_swap_history(
-    State(pool): State<MySqlPool>,
-    Query(params): Query<SwapHistoryQueryParams>,
-) -> impl IntoResponse {
-    info!("Received swap history request with params: {:#?}", params);
-
-    let limit = params.limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE);
-    let offset = params.page.unwrap_or(0) * limit;
-    debug!("Using limit: {}, offset: {}", limit, offset);
-
-    let mut query = sqlx::QueryBuilder::new("SELECT * FROM `swap_intervals` WHERE 1=1");
-
-    if let Some((start, end)) = params.parse_date_range() {
-        debug!("Date range filter: start={}, end={}", start, end);
-        query
-            .push(" AND start_time >= ")
-            .push_bind(start)
-            .push(" AND end_time <= ")
-            .push_bind(end);
-    }
-
-    if let Some(min_volume) = params.volume_gt {
-        debug!("Volume filter: > {}", min_volume);
-        query.push(" AND total_volume > ").push_bind(min_volume);
-    }
-
-    if let Some(min_fees) = params.fees_gt {
-        debug!("Fees filter: > {}", min_fees);
-        query.push(" AND total_fees > ").push_bind(min_fees);
-    }
-
-    let sort_field = params.get_sort_field();
-    let sort_order = if params.order.as_deref() == Some("desc") {
-        "DESC"
-    } else {
-        "ASC"
-    };
+    State(repo): State<Arc<dyn SwapRepository>>,
+    params: Result<Query<SwapHistoryQueryParams>, QueryRejection>,
+) -> Result<Json<Projected<SwapHistoryResponse>>, ApiError> {
+    let Query(params) = params?;
 
-    query
-        .push(" ORDER BY ")
-        .push(sort_field)
-        .push(" ")
-        .push(sort_order);
-
-    query.push(" LIMIT ").push_bind(limit as i64);
-    query.push(" OFFSET ").push_bind(offset as i64);
-
-    let query_string = query.sql();
-    debug!("Executing query: {}", query_string);
-
-    match query
-        .build_query_as::<SwapInterval>()
-        .fetch_all(&pool)
-        .await
-    {
-        Ok(intervals) => {
-            info!("Successfully retrieved {} swap intervals", intervals.len());
-
-            if intervals.is_empty() {
-                return Json(json!({
-                    "success": true,
-                    "data": "no data found in the database for the given params"
-                }))
-                .into_response();
-            }
-
-            let meta_stats =
-                if let (Some(first), Some(last)) = (intervals.first(), intervals.last()) {
-                    SwapMeta {
-                        average_slip: last.average_slip,
-                        end_time: last.end_time,
-                        from_trade_average_slip: last.from_trade_average_slip,
-                        from_trade_count: last.from_trade_count,
-                        from_trade_fees: last.from_trade_fees,
-                        from_trade_volume: last.from_trade_volume,
-                        from_trade_volume_usd: last.from_trade_volume_usd,
-                        rune_price_usd: last.rune_price_usd,
-                        start_time: first.start_time,
-                        synth_mint_average_slip: last.synth_mint_average_slip,
-                        synth_mint_count: last.synth_mint_count,
-                        synth_mint_fees: last.synth_mint_fees,
-                        synth_mint_volume: last.synth_mint_volume,
-                        synth_mint_volume_usd: last.synth_mint_volume_usd,
-                        synth_redeem_average_slip: last.synth_redeem_average_slip,
-                        synth_redeem_count: last.synth_redeem_count,
-                        synth_redeem_fees: last.synth_redeem_fees,
-                        synth_redeem_volume: last.synth_redeem_volume,
-                        synth_redeem_volume_usd: last.synth_redeem_volume_usd,
-                        to_asset_average_slip: last.to_asset_average_slip,
-                        to_asset_count: last.to_asset_count,
-                        to_asset_fees: last.to_asset_fees,
-                        to_asset_volume: last.to_asset_volume,
-                        to_asset_volume_usd: last.to_asset_volume_usd,
-                        to_rune_average_slip: last.to_rune_average_slip,
-                        to_rune_count: last.to_rune_count,
-                        to_rune_fees: last.to_rune_fees,
-                        to_rune_volume: last.to_rune_volume,
-                        to_rune_volume_usd: last.to_rune_volume_usd,
-                        to_trade_average_slip: last.to_trade_average_slip,
-                        to_trade_count: last.to_trade_count,
-                        to_trade_fees: last.to_trade_fees,
-                        to_trade_volume: last.to_trade_volume,
-                        to_trade_volume_usd: last.to_trade_volume_usd,
-                        total_count: last.total_count,
-                        total_fees: last.total_fees,
-                        total_volume: last.total_volume,
-                        total_volume_usd: last.total_volume_usd,
-                    }
-                } else {
-                    return Json(json!({
-                        "success": true,
-                        "data": "no data found"
-                    }))
-                    .into_response();
-                };
+    info!("Received swap history request with params: {:#?}", params);
 
-            let response = SwapHistoryResponse {
-                intervals,
-                meta_stats,
-            };
+    let page = history_page(repo.as_ref(), &params, true).await?;
+    info!(
+        "Successfully retrieved {} swap intervals",
+        page.intervals.len()
+    );
 
-            Json(response).into_response()
-        }
-        Err(e) => {
-            error!("Database error: {}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({
-                    "success": false,
-                    "error": format!("Database error: {}", e)
-                })),
-            )
-                .into_response()
-        }
-    }
+    Ok(Json(Projected {
+        response: SwapHistoryResponse {
+            intervals: page.intervals,
+            meta_stats: page.meta_stats,
+            next_cursor: page.next_cursor,
+            prev_cursor: page.prev_cursor,
+            pagination: page.pagination,
+        },
+        fields: page.fields,
+    }))
 }
@@ -1,36 +1,77 @@
-use super::{
-    depth_history::fetch_initial_depth_history, earning_history::fetch_initial_earnings_history,
-    runepool_units_history::fetch_initial_runepool_units_history,
-    swap_history::fetch_initial_swap_history,
+use crate::config::connect::Database;
+use crate::core::models::{
+    common::Interval, depth_history::DepthHistoryParams, earnings_history::EarningsHistoryParams,
+    runepool_units_history::RunepoolUnitsHistoryParams, swap_history::SwapHistoryParams,
 };
-use crate::services::repository::{depth, earnings, runepool, swap};
+use crate::services::client::MidgardClient;
+use crate::services::repository::{depth, earnings, pools::get_tracked_pools, runepool, swap};
+use chrono::Utc;
 
-pub async fn fetch_and_store_depth_history(pool: &sqlx::MySqlPool) {
-    tracing::info!("Fetching initial depth history...");
-    match fetch_initial_depth_history().await {
-        Ok(initial_data) => {
-            tracing::info!("Successfully fetched initial depth history");
-            match depth::store_intervals(pool, &initial_data.intervals).await {
-                Ok(_) => tracing::info!(
-                    "Successfully stored {} intervals",
-                    initial_data.intervals.len()
-                ),
-                Err(e) => tracing::error!("Failed to store intervals: {}", e),
+// The latest 400 hourly intervals, the most midgard hands out in one go
+macro_rules! initial_params {
+    ($params:ident) => {
+        $params {
+            interval: Some(Interval::Hour),
+            count: Some(400),
+            from: None,
+            to: Some(Utc::now()),
+        }
+    };
+}
+
+pub async fn fetch_and_store_depth_history(db: &Database, midgard: &MidgardClient) {
+    let pool_names = match get_tracked_pools(db).await {
+        Ok(pool_names) => pool_names,
+        Err(e) => {
+            tracing::error!("Failed to load the pools to fetch depth history for: {}", e);
+            return;
+        }
+    };
+
+    for pool_name in pool_names {
+        tracing::info!("Fetching initial depth history for {}...", pool_name);
+        match midgard
+            .depth_history(&pool_name, &initial_params!(DepthHistoryParams))
+            .await
+        {
+            Ok(initial_data) => {
+                tracing::info!(
+                    "Successfully fetched initial depth history for {}",
+                    pool_name
+                );
+                let stored = depth::store_intervals(db, &initial_data.intervals).await;
+                match stored {
+                    Ok(summary) => tracing::info!(
+                        "Successfully stored {} intervals ({})",
+                        initial_data.intervals.len(),
+                        summary
+                    ),
+                    Err(e) => tracing::error!("Failed to store intervals: {}", e),
+                }
             }
+            Err(e) => tracing::error!(
+                "Failed to fetch initial depth history for {}: {}",
+                pool_name,
+                e
+            ),
         }
-        Err(e) => tracing::error!("Failed to fetch initial depth history: {}", e),
     }
 }
 
-pub async fn fetch_and_store_earnings_history(pool: &sqlx::MySqlPool) {
+pub async fn fetch_and_store_earnings_history(db: &Database, midgard: &MidgardClient) {
     tracing::info!("Fetching initial earnings history...");
-    match fetch_initial_earnings_history().await {
+    match midgard
+        .earnings_history(&initial_params!(EarningsHistoryParams))
+        .await
+    {
         Ok(initial_data) => {
             tracing::info!("Successfully fetched initial earnings history");
-            match earnings::store_intervals(pool, &initial_data.intervals).await {
-                Ok(_) => tracing::info!(
-                    "Successfully stored {} intervals",
-                    initial_data.intervals.len()
+            let stored = earnings::store_intervals(db, &initial_data.intervals).await;
+            match stored {
+                Ok(summary) => tracing::info!(
+                    "Successfully stored {} intervals ({})",
+                    initial_data.intervals.len(),
+                    summary
                 ),
                 Err(e) => tracing::error!("Failed to store intervals: {}", e),
             }
@@ -39,15 +80,20 @@ pub async fn fetch_and_store_earnings_history(pool: &sqlx::MySqlPool) {
     }
 }
 
-pub async fn fetch_and_store_swap_history(pool: &sqlx::MySqlPool) {
+pub async fn fetch_and_store_swap_history(db: &Database, midgard: &MidgardClient) {
     tracing::info!("Fetching initial swap history...");
-    match fetch_initial_swap_history().await {
+    match midgard
+        .swap_history(&initial_params!(SwapHistoryParams))
+        .await
+    {
         Ok(initial_data) => {
             tracing::info!("Successfully fetched initial swap history");
-            match swap::store_intervals(pool, &initial_data.intervals).await {
-                Ok(_) => tracing::info!(
-                    "Successfully stored {} intervals",
-                    initial_data.intervals.len()
+            let stored = swap::store_intervals(db, &initial_data.intervals).await;
+            match stored {
+                Ok(summary) => tracing::info!(
+                    "Successfully stored {} intervals ({})",
+                    initial_data.intervals.len(),
+                    summary
                 ),
                 Err(e) => tracing::error!("Failed to store intervals: {}", e),
             }
@@ -56,15 +102,20 @@ pub async fn fetch_and_store_swap_history(pool: &sqlx::MySqlPool) {
     }
 }
 
-pub async fn fetch_and_store_runepool_units_history(pool: &sqlx::MySqlPool) {
+pub async fn fetch_and_store_runepool_units_history(db: &Database, midgard: &MidgardClient) {
     tracing::info!("Fetching initial runepool units history...");
-    match fetch_initial_runepool_units_history().await {
+    match midgard
+        .runepool_units_history(&initial_params!(RunepoolUnitsHistoryParams))
+        .await
+    {
         Ok(initial_data) => {
             tracing::info!("Successfully fetched initial runepool units history");
-            match runepool::store_intervals(pool, &initial_data.intervals).await {
-                Ok(_) => tracing::info!(
-                    "Successfully stored {} intervals",
-                    initial_data.intervals.len()
+            let stored = runepool::store_intervals(db, &initial_data.intervals).await;
+            match stored {
+                Ok(summary) => tracing::info!(
+                    "Successfully stored {} intervals ({})",
+                    initial_data.intervals.len(),
+                    summary
                 ),
                 Err(e) => tracing::error!("Failed to store intervals: {}", e),
             }
@@ -1,5 +1 @@
-pub mod depth_history;
-pub mod earning_history;
 pub mod fetch;
-pub mod runepool_units_history;
-pub mod swap_history;
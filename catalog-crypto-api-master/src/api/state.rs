@@ -0,0 +1,95 @@
+use crate::services::repository::traits::{
+    DepthRepository, EarningsRepository, GapRepository, JobRepository, PoolRepository,
+    RunepoolRepository, StatusRepository, SwapRepository,
+};
+use axum::extract::FromRef;
+use std::sync::Arc;
+
+// Router state, each handler pulls out only the repository it needs so it never sees which
+// storage is behind it
+#[derive(Clone)]
+pub struct AppState {
+    pub depth: Arc<dyn DepthRepository>,
+    pub earnings: Arc<dyn EarningsRepository>,
+    pub swap: Arc<dyn SwapRepository>,
+    pub runepool: Arc<dyn RunepoolRepository>,
+    pub pools: Arc<dyn PoolRepository>,
+    pub gaps: Arc<dyn GapRepository>,
+    pub status: Arc<dyn StatusRepository>,
+    pub jobs: Arc<dyn JobRepository>,
+}
+
+impl AppState {
+    pub fn new<S>(store: S) -> Self
+    where
+        S: DepthRepository
+            + EarningsRepository
+            + SwapRepository
+            + RunepoolRepository
+            + PoolRepository
+            + GapRepository
+            + StatusRepository
+            + JobRepository
+            + 'static,
+    {
+        let store = Arc::new(store);
+        Self {
+            depth: store.clone(),
+            earnings: store.clone(),
+            swap: store.clone(),
+            runepool: store.clone(),
+            pools: store.clone(),
+            gaps: store.clone(),
+            status: store.clone(),
+            jobs: store,
+        }
+    }
+}
+
+impl FromRef<AppState> for Arc<dyn DepthRepository> {
+    fn from_ref(state: &AppState) -> Self {
+        state.depth.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<dyn EarningsRepository> {
+    fn from_ref(state: &AppState) -> Self {
+        state.earnings.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<dyn SwapRepository> {
+    fn from_ref(state: &AppState) -> Self {
+        state.swap.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<dyn RunepoolRepository> {
+    fn from_ref(state: &AppState) -> Self {
+        state.runepool.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<dyn PoolRepository> {
+    fn from_ref(state: &AppState) -> Self {
+        state.pools.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<dyn GapRepository> {
+    fn from_ref(state: &AppState) -> Self {
+        state.gaps.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<dyn StatusRepository> {
+    fn from_ref(state: &AppState) -> Self {
+        state.status.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<dyn JobRepository> {
+    fn from_ref(state: &AppState) -> Self {
+        state.jobs.clone()
+    }
+}
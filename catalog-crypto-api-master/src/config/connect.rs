@@ -1,10 +1,98 @@
-use sqlx::{mysql::MySqlPoolOptions, MySqlPool};
-use std::time::Duration;
-
-pub async fn connect_database(url: &str) -> Result<MySqlPool, sqlx::Error> {
-    MySqlPoolOptions::new()
-        .max_connections(5)
-        .acquire_timeout(Duration::from_secs(3))
-        .connect(url)
-        .await
+use sqlx::{
+    mysql::MySqlPoolOptions,
+    postgres::{PgConnectOptions, PgPoolOptions},
+    sqlite::{SqliteConnectOptions, SqlitePoolOptions},
+    MySqlPool, PgPool, SqlitePool,
+};
+use std::{str::FromStr, time::Duration};
+
+// Which storage engine DATABASE_URL points at, the repositories use it to pick their SQL flavour
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    MySql,
+    Postgres,
+    Sqlite,
+}
+
+#[derive(Debug, Clone)]
+pub enum Database {
+    MySql(MySqlPool),
+    Postgres(PgPool),
+    Sqlite(SqlitePool),
+}
+
+impl Database {
+    pub fn backend(&self) -> Backend {
+        match self {
+            Database::MySql(_) => Backend::MySql,
+            Database::Postgres(_) => Backend::Postgres,
+            Database::Sqlite(_) => Backend::Sqlite,
+        }
+    }
+}
+
+// Runs the same sqlx code against whichever pool `db` holds, `$pool` is bound to the concrete
+// pool so the body is type checked once per backend
+macro_rules! with_pool {
+    ($db:expr, $pool:ident => $body:expr) => {
+        match $db {
+            $crate::config::connect::Database::MySql($pool) => $body,
+            $crate::config::connect::Database::Postgres($pool) => $body,
+            $crate::config::connect::Database::Sqlite($pool) => $body,
+        }
+    };
+}
+pub(crate) use with_pool;
+
+// Every backend brings its schema up to date before it's handed out. Each has its own migrations
+// and sqlx records the ones that ran, so starting again only applies what is new
+pub async fn connect_database(url: &str) -> Result<Database, sqlx::Error> {
+    if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+        // Buckets are computed with date_trunc, which works in the session time zone
+        let options = PgConnectOptions::from_str(url)?.options([("timezone", "UTC")]);
+
+        let pool = PgPoolOptions::new()
+            .max_connections(5)
+            .acquire_timeout(Duration::from_secs(3))
+            .connect_with(options)
+            .await?;
+        sqlx::migrate!("./migrations/postgres").run(&pool).await?;
+
+        return Ok(Database::Postgres(pool));
+    }
+
+    if url.starts_with("mysql://") || url.starts_with("mariadb://") {
+        let pool = MySqlPoolOptions::new()
+            .max_connections(5)
+            .acquire_timeout(Duration::from_secs(3))
+            .connect(url)
+            .await?;
+        sqlx::migrate!("./migrations").run(&pool).await?;
+
+        return Ok(Database::MySql(pool));
+    }
+
+    if url.starts_with("sqlite:") {
+        let options = SqliteConnectOptions::from_str(url)?.create_if_missing(true);
+
+        // A single connection keeps the crons' transactions from running into sqlite's database
+        // lock, and keeps an in-memory database alive for as long as the pool is
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect_with(options)
+            .await?;
+        sqlx::migrate!("./migrations/sqlite").run(&pool).await?;
+
+        return Ok(Database::Sqlite(pool));
+    }
+
+    Err(sqlx::Error::Configuration(
+        format!(
+            "Unsupported DATABASE_URL scheme in {}, expected mysql://, postgres:// or sqlite:",
+            url.split("://").next().unwrap_or_default()
+        )
+        .into(),
+    ))
 }
@@ -1,15 +1,57 @@
-use chrono::{DateTime, NaiveDateTime, Utc};
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
 use super::{
-    depth_history::DepthHistoryQueryParams, earnings_history::EarningsHistoryQueryParams,
-    runepool_units_history::RunepoolUnitsHistoryQueryParams, swap_history::SwapHistoryQueryParams,
+    depth_history::{DepthHistoryQueryParams, DepthSortField},
+    earnings_history::{EarningsHistoryQueryParams, EarningsSortField},
+    fields::parse_fields,
+    filters::{parse_filters, Column, Filter},
+    runepool_units_history::{RunepoolUnitsHistoryQueryParams, RunepoolUnitsSortField},
+    swap_history::{SwapHistoryQueryParams, SwapSortField},
 };
 
 pub const DEFAULT_PAGE_SIZE: u32 = 30;
 pub const MAX_PAGE_SIZE: u32 = 400;
 
+// 2022-04-01, where the backfill crons start when they have no checkpoint yet
+pub const HISTORY_START_TIMESTAMP: i64 = 1648771200;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Dataset {
+    Depth,
+    Earnings,
+    Swap,
+    Runepool,
+}
+
+impl std::fmt::Display for Dataset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let dataset_str = match self {
+            Dataset::Depth => "depth",
+            Dataset::Earnings => "earnings",
+            Dataset::Swap => "swap",
+            Dataset::Runepool => "runepool",
+        };
+        write!(f, "{}", dataset_str)
+    }
+}
+
+impl TryFrom<String> for Dataset {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        match s.to_lowercase().as_str() {
+            "depth" => Ok(Dataset::Depth),
+            "earnings" => Ok(Dataset::Earnings),
+            "swap" => Ok(Dataset::Swap),
+            "runepool" => Ok(Dataset::Runepool),
+            _ => Err("Invalid dataset".to_string()),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum Interval {
@@ -38,6 +80,18 @@ impl std::fmt::Display for Interval {
     }
 }
 
+impl Interval {
+    // Everything is stored hourly so 5min buckets can't be built from it
+    pub fn check_rollup(&self) -> Result<(), String> {
+        match self {
+            Interval::FiveMin => {
+                Err("5min interval is not supported, the data is stored hourly".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
 impl TryFrom<String> for Interval {
     type Error = String;
 
@@ -56,128 +110,250 @@ impl TryFrom<String> for Interval {
 }
 
 impl DepthHistoryQueryParams {
-    // Helper method to parse date range
-    pub fn parse_date_range(&self) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
-        self.date_range.as_ref().and_then(|range| {
-            let parts: Vec<&str> = range.split(',').collect();
-            if parts.len() == 2 {
-                let start = NaiveDateTime::parse_from_str(
-                    &format!("{} 00:00:00", parts[0]),
-                    "%Y-%m-%d %H:%M:%S",
-                )
-                .ok()?;
-                let end = NaiveDateTime::parse_from_str(
-                    &format!("{} 23:59:59", parts[1]),
-                    "%Y-%m-%d %H:%M:%S",
-                )
-                .ok()?;
-                Some((
-                    DateTime::from_naive_utc_and_offset(start, Utc),
-                    DateTime::from_naive_utc_and_offset(end, Utc),
-                ))
-            } else {
-                None
-            }
-        })
+    pub fn time_range(&self) -> Result<TimeRange, TimeRangeError> {
+        TimeRange::from_params(&self.date_range, &self.from, &self.to)
     }
 
-    // Helper method to map timestamp to actual db field
+    // Helper method to map the sort field to the actual db column
     pub fn get_sort_field(&self) -> &str {
-        match self.sort_field.as_deref() {
-            Some("timestamp") => "start_time", // Map timestamp to start_time
-            Some(field) => field,
-            None => "start_time", // Default sort field
-        }
+        self.sort_field.unwrap_or_default().column()
+    }
+
+    pub fn filters(&self) -> Result<Vec<Filter<DepthSortField>>, String> {
+        parse_filters(&self.filter)
+    }
+
+    pub fn projection(&self) -> Result<Option<Vec<&'static str>>, String> {
+        parse_fields::<DepthSortField>(&self.fields, &["pool"])
     }
 }
 
 impl EarningsHistoryQueryParams {
-    // Helper method to parse date range
-    pub fn parse_date_range(&self) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
-        self.date_range.as_ref().and_then(|range| {
-            let parts: Vec<&str> = range.split(',').collect();
-            if parts.len() == 2 {
-                let start = NaiveDateTime::parse_from_str(
-                    &format!("{} 00:00:00", parts[0]),
-                    "%Y-%m-%d %H:%M:%S",
-                )
-                .ok()?;
-                let end = NaiveDateTime::parse_from_str(
-                    &format!("{} 23:59:59", parts[1]),
-                    "%Y-%m-%d %H:%M:%S",
-                )
-                .ok()?;
-                Some((
-                    DateTime::from_naive_utc_and_offset(start, Utc),
-                    DateTime::from_naive_utc_and_offset(end, Utc),
-                ))
-            } else {
-                None
-            }
-        })
+    pub fn time_range(&self) -> Result<TimeRange, TimeRangeError> {
+        TimeRange::from_params(&self.date_range, &self.from, &self.to)
     }
 
-    // Helper method to map timestamp to actual db field
+    // Helper method to map the sort field to the actual db column
     pub fn get_sort_field(&self) -> &str {
-        match self.sort_by.as_deref() {
-            Some("timestamp") => "start_time", // Map timestamp to start_time
-            Some(field) => field,
-            None => "start_time", // Default sort field
-        }
+        self.sort_by.unwrap_or_default().column()
+    }
+
+    pub fn filters(&self) -> Result<Vec<Filter<EarningsSortField>>, String> {
+        parse_filters(&self.filter)
+    }
+
+    pub fn projection(&self) -> Result<Option<Vec<&'static str>>, String> {
+        parse_fields::<EarningsSortField>(&self.fields, &["pools"])
     }
 }
 
 impl SwapHistoryQueryParams {
     pub fn get_sort_field(&self) -> &str {
-        match self.sort_by.as_deref() {
-            Some("volume") => "total_volume",
-            Some("fees") => "total_fees",
-            Some("count") => "total_count",
-            Some("timestamp") => "start_time",
-            _ => "start_time",
-        }
+        self.sort_by.unwrap_or_default().column()
+    }
+
+    pub fn time_range(&self) -> Result<TimeRange, TimeRangeError> {
+        TimeRange::from_params(&self.date_range, &self.from, &self.to)
     }
 
-    pub fn parse_date_range(&self) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
-        parse_date_range(&self.date_range)
+    pub fn filters(&self) -> Result<Vec<Filter<SwapSortField>>, String> {
+        parse_filters(&self.filter)
+    }
+
+    pub fn projection(&self) -> Result<Option<Vec<&'static str>>, String> {
+        parse_fields::<SwapSortField>(&self.fields, &[])
     }
 }
 
 impl RunepoolUnitsHistoryQueryParams {
     pub fn get_sort_field(&self) -> &str {
-        match self.sort_by.as_deref() {
-            Some("units") => "units",
-            Some("count") => "count",
-            Some("timestamp") => "start_time",
-            _ => "start_time",
+        self.sort_by.unwrap_or_default().column()
+    }
+
+    pub fn time_range(&self) -> Result<TimeRange, TimeRangeError> {
+        TimeRange::from_params(&self.date_range, &self.from, &self.to)
+    }
+
+    pub fn filters(&self) -> Result<Vec<Filter<RunepoolUnitsSortField>>, String> {
+        parse_filters(&self.filter)
+    }
+
+    pub fn projection(&self) -> Result<Option<Vec<&'static str>>, String> {
+        parse_fields::<RunepoolUnitsSortField>(&self.fields, &[])
+    }
+}
+
+// The span a history query covers, intervals starting from `start` up to but not including `end`.
+// Either end can be left open
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TimeRange {
+    pub start: Option<DateTime<Utc>>,
+    pub end: Option<DateTime<Utc>>,
+}
+
+// A range parameter that was rejected, named so the 400 can point at it
+#[derive(Debug, PartialEq)]
+pub struct TimeRangeError {
+    pub param: &'static str,
+    pub message: String,
+}
+
+impl TimeRangeError {
+    fn new(param: &'static str, message: impl Into<String>) -> Self {
+        TimeRangeError {
+            param,
+            message: message.into(),
+        }
+    }
+}
+
+impl TimeRange {
+    // Reads `from`/`to`, unix seconds like midgard takes or RFC3339, or the older `date_range` of
+    // whole days. Anything it can't make sense of is an error, never a missing bound
+    pub fn from_params(
+        date_range: &Option<String>,
+        from: &Option<String>,
+        to: &Option<String>,
+    ) -> Result<Self, TimeRangeError> {
+        if let Some(range) = date_range {
+            if from.is_some() || to.is_some() {
+                return Err(TimeRangeError::new(
+                    "date_range",
+                    "date_range can't be combined with from or to",
+                ));
+            }
+            return parse_date_range(range);
+        }
+
+        let bound = |param: &'static str, value: &Option<String>| {
+            value
+                .as_deref()
+                .map(|value| {
+                    parse_time(value).ok_or_else(|| {
+                        TimeRangeError::new(
+                            param,
+                            format!(
+                                "Invalid {} {}, expected unix seconds or an RFC3339 time",
+                                param, value
+                            ),
+                        )
+                    })
+                })
+                .transpose()
+        };
+        let range = TimeRange {
+            start: bound("from", from)?,
+            end: bound("to", to)?,
+        };
+
+        match (range.start, range.end) {
+            (Some(start), Some(end)) if start >= end => {
+                Err(TimeRangeError::new("to", "to has to be after from"))
+            }
+            _ => Ok(range),
         }
     }
 
-    pub fn parse_date_range(&self) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
-        parse_date_range(&self.date_range)
+    pub fn contains(&self, time: DateTime<Utc>) -> bool {
+        self.start.is_none_or(|start| time >= start) && self.end.is_none_or(|end| time < end)
     }
 }
 
-fn parse_date_range(date_range: &Option<String>) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
-    date_range.as_ref().and_then(|range| {
-        let parts: Vec<&str> = range.split(',').collect();
-        if parts.len() == 2 {
-            let start = NaiveDateTime::parse_from_str(
-                &format!("{} 00:00:00", parts[0]),
-                "%Y-%m-%d %H:%M:%S",
-            )
-            .ok()?;
-            let end = NaiveDateTime::parse_from_str(
-                &format!("{} 23:59:59", parts[1]),
-                "%Y-%m-%d %H:%M:%S",
+fn parse_time(value: &str) -> Option<DateTime<Utc>> {
+    if !value.is_empty() && value.bytes().all(|byte| byte.is_ascii_digit()) {
+        return DateTime::from_timestamp(value.parse().ok()?, 0);
+    }
+
+    DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+// Both days count, so the range ends where the day after the last one starts
+fn parse_date_range(range: &str) -> Result<TimeRange, TimeRangeError> {
+    let day = |day: &str| NaiveDate::parse_from_str(day, "%Y-%m-%d").ok();
+    let (start, end) = range
+        .split_once(',')
+        .and_then(|(start, end)| Some((day(start)?, day(end)?)))
+        .ok_or_else(|| {
+            TimeRangeError::new(
+                "date_range",
+                format!(
+                    "Invalid date_range {}, expected YYYY-MM-DD,YYYY-MM-DD",
+                    range
+                ),
             )
-            .ok()?;
-            Some((
-                DateTime::from_naive_utc_and_offset(start, Utc),
-                DateTime::from_naive_utc_and_offset(end, Utc),
-            ))
-        } else {
-            None
-        }
+        })?;
+
+    if start > end {
+        return Err(TimeRangeError::new(
+            "date_range",
+            format!("date_range {} ends before it starts", range),
+        ));
+    }
+
+    let midnight = |day: NaiveDate| day.and_time(NaiveTime::MIN).and_utc();
+    Ok(TimeRange {
+        start: Some(midnight(start)),
+        end: end.succ_opt().map(midnight),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(
+        date_range: Option<&str>,
+        from: Option<&str>,
+        to: Option<&str>,
+    ) -> Result<TimeRange, TimeRangeError> {
+        let param = |value: Option<&str>| value.map(str::to_string);
+        TimeRange::from_params(&param(date_range), &param(from), &param(to))
+    }
+
+    fn time(timestamp: i64) -> Option<DateTime<Utc>> {
+        DateTime::from_timestamp(timestamp, 0)
+    }
+
+    #[test]
+    fn reads_unix_seconds_rfc3339_and_whole_days() {
+        // 2023-11-14 00:00 to 2023-11-16 00:00 UTC
+        let days = TimeRange {
+            start: time(1699920000),
+            end: time(1700092800),
+        };
+        assert_eq!(range(Some("2023-11-14,2023-11-15"), None, None), Ok(days));
+        assert_eq!(
+            range(None, Some("1699920000"), Some("2023-11-16T02:00:00+02:00")),
+            Ok(days)
+        );
+        assert_eq!(
+            range(None, None, Some("1700092800")),
+            Ok(TimeRange {
+                start: None,
+                end: time(1700092800)
+            })
+        );
+        assert!(days.contains(time(1699920000).unwrap()));
+        assert!(!days.contains(time(1700092800).unwrap()));
+
+        for (date_range, from, to, param) in [
+            (Some("2023-11-14"), None, None, "date_range"),
+            (Some("2023-11-15,2023-11-14"), None, None, "date_range"),
+            (
+                Some("2023-11-14,2023-11-15"),
+                Some("1699920000"),
+                None,
+                "date_range",
+            ),
+            (None, Some("yesterday"), None, "from"),
+            (None, Some("-1"), None, "from"),
+            (None, None, Some("2023-11-14"), "to"),
+            (None, Some("1700092800"), Some("1699920000"), "to"),
+            (None, Some("1699920000"), Some("1699920000"), "to"),
+        ] {
+            assert_eq!(range(date_range, from, to).unwrap_err().param, param);
+        }
+    }
+}
@@ -5,6 +5,8 @@ use sqlx::prelude::FromRow;
 use utoipa::ToSchema;
 
 use super::common::Interval;
+use super::filters::{Column, ColumnKind};
+use super::pagination::Pagination;
 
 mod float_serialization {
     use serde::{de::Deserializer, ser::Serializer, Deserialize};
@@ -93,10 +95,14 @@ mod u32_serialization {
     }
 }
 
-#[derive(Table, Debug, Serialize, Deserialize, FromRow, Clone, ToSchema)]
+#[derive(Table, Debug, Default, Serialize, Deserialize, FromRow, Clone, PartialEq, ToSchema)]
 #[table_name("`depth_intervals`")]
+// Amounts are stored as signed integers (postgres has no unsigned ones) and converted on decode.
+// Columns a `fields` query leaves out decode to their defaults
+#[sqlx(default)]
 pub struct DepthInterval {
     #[serde(rename = "assetDepth", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub asset_depth: u64,
     #[serde(rename = "assetPrice", with = "float_serialization")]
     pub asset_price: f64,
@@ -104,21 +110,38 @@ pub struct DepthInterval {
     pub asset_price_usd: f64,
     #[serde(rename = "endTime", with = "timestamp_serialization")]
     pub end_time: DateTime<Utc>,
+    // Row id, breaks start time ties for the pagination cursors. Midgard doesn't send it
+    #[serde(skip)]
+    #[sqlx(default)]
+    pub id: i64,
     #[serde(rename = "liquidityUnits", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub liquidity_units: u64,
     #[serde(with = "float_serialization")]
     pub luvi: f64,
     #[serde(rename = "membersCount", with = "u32_serialization")]
+    #[sqlx(try_from = "i32")]
     pub members_count: u32,
+    // Midgard does not send this back, it is filled in from the pool we asked for
+    #[serde(default)]
+    pub pool: String,
+    // Bumped every time midgard sends different values for an interval that was already stored
+    #[serde(default)]
+    #[sqlx(default, try_from = "i32")]
+    pub revision: u32,
     #[serde(rename = "runeDepth", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub rune_depth: u64,
     #[serde(rename = "startTime", with = "timestamp_serialization")]
     pub start_time: DateTime<Utc>,
     #[serde(rename = "synthSupply", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub synth_supply: u64,
     #[serde(rename = "synthUnits", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub synth_units: u64,
     #[serde(with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub units: u64,
 }
 
@@ -157,8 +180,15 @@ pub struct MetaStats {
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct DepthHistoryResponse {
     pub intervals: Vec<DepthInterval>,
+    // Midgard always sends it, our handlers leave it out for an empty page
     #[serde(rename = "meta")]
-    pub meta_stats: MetaStats,
+    pub meta_stats: Option<MetaStats>,
+    // Pass back as `cursor` for the following or preceding page, null at either end
+    pub next_cursor: Option<String>,
+    pub prev_cursor: Option<String>,
+    // Ours only, left at the default when reading midgard's responses
+    #[serde(default)]
+    pub pagination: Pagination,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -169,14 +199,72 @@ pub struct DepthHistoryParams {
     pub to: Option<DateTime<Utc>>,
 }
 
+// Columns `sort_by` and `filter` accept, anything else is rejected before it gets near the query
+#[derive(Debug, Deserialize, Clone, Copy, Default, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DepthSortField {
+    #[default]
+    #[serde(alias = "timestamp")]
+    StartTime,
+    EndTime,
+    AssetDepth,
+    AssetPrice,
+    AssetPriceUsd,
+    LiquidityUnits,
+    Luvi,
+    MembersCount,
+    RuneDepth,
+    SynthSupply,
+    SynthUnits,
+    Units,
+    Revision,
+}
+
+impl Column for DepthSortField {
+    fn column(&self) -> &'static str {
+        match self {
+            DepthSortField::StartTime => "start_time",
+            DepthSortField::EndTime => "end_time",
+            DepthSortField::AssetDepth => "asset_depth",
+            DepthSortField::AssetPrice => "asset_price",
+            DepthSortField::AssetPriceUsd => "asset_price_usd",
+            DepthSortField::LiquidityUnits => "liquidity_units",
+            DepthSortField::Luvi => "luvi",
+            DepthSortField::MembersCount => "members_count",
+            DepthSortField::RuneDepth => "rune_depth",
+            DepthSortField::SynthSupply => "synth_supply",
+            DepthSortField::SynthUnits => "synth_units",
+            DepthSortField::Units => "units",
+            DepthSortField::Revision => "revision",
+        }
+    }
+
+    fn kind(&self) -> Option<ColumnKind> {
+        match self {
+            DepthSortField::StartTime | DepthSortField::EndTime => None,
+            DepthSortField::AssetPrice | DepthSortField::AssetPriceUsd | DepthSortField::Luvi => {
+                Some(ColumnKind::Float)
+            }
+            _ => Some(ColumnKind::Int),
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct DepthHistoryQueryParams {
     pub date_range: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub pool: Option<String>,
     pub liquidity_gt: Option<u64>,
-    // pub interval: Option<Interval>, // TODO Handle this next time FOR NOW WE ARE NOT USING THIS
+    pub interval: Option<Interval>,
+    pub count: Option<u32>,
     #[serde(rename = "sort_by")]
-    pub sort_field: Option<String>, // Do you know you can also pass this timestamp, (this gets mapped to start_time internally)
+    pub sort_field: Option<DepthSortField>, // Do you know you can also pass this timestamp, (this gets mapped to start_time internally)
     pub order: Option<String>,
     pub page: Option<u32>,
+    pub cursor: Option<String>,
     pub limit: Option<u32>,
+    pub filter: Option<String>,
+    pub fields: Option<String>,
 }
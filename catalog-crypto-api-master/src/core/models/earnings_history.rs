@@ -5,6 +5,8 @@ use sqlx::prelude::FromRow;
 use utoipa::ToSchema;
 
 use super::common::Interval;
+use super::filters::{Column, ColumnKind};
+use super::pagination::Pagination;
 
 mod float_serialization {
     use serde::{de::Deserializer, ser::Serializer, Deserialize};
@@ -85,7 +87,7 @@ mod u64_serialization {
     }
 }
 
-#[derive(Table, Debug, Serialize, Deserialize, FromRow, Clone, ToSchema)]
+#[derive(Table, Debug, Serialize, Deserialize, FromRow, Clone, PartialEq, ToSchema)]
 #[table_name("`earning_pool`")]
 pub struct Pool {
     #[serde(rename = "assetLiquidityFees", with = "u64_serialization")]
@@ -103,27 +105,38 @@ pub struct Pool {
     pub total_liquidity_fees_rune: u64,
 }
 
-#[derive(Table, Debug, Serialize, Deserialize, FromRow, Clone, ToSchema)]
+#[derive(Table, Debug, Serialize, Deserialize, FromRow, Clone, PartialEq, ToSchema)]
 #[table_name("`earning_intervals`")]
 #[serde(rename_all = "camelCase")]
-#[derive(sqlx::Type)]
 pub struct IntervalData {
     #[serde(rename = "avgNodeCount", with = "float_serialization")]
     pub avg_node_count: f64,
     #[serde(rename = "blockRewards", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub block_rewards: u64,
     #[serde(rename = "bondingEarnings", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub bonding_earnings: u64,
     #[serde(rename = "earnings", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub earnings: u64,
     #[serde(rename = "endTime", with = "timestamp_serialization")]
     pub end_time: DateTime<Utc>,
+    // Row id, breaks start time ties for the pagination cursors. Midgard doesn't send it
+    #[serde(skip)]
+    #[sqlx(default)]
+    pub id: i64,
     #[serde(rename = "liquidityEarnings", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub liquidity_earnings: u64,
     #[serde(rename = "liquidityFees", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub liquidity_fees: u64,
     #[serde(rename = "pools")]
     pub pools: Vec<Pool>,
+    #[serde(default)]
+    #[sqlx(default, try_from = "i32")]
+    pub revision: u32,
     #[serde(rename = "runePriceUSD", with = "float_serialization")]
     pub rune_price_usd: f64,
     #[serde(rename = "startTime", with = "timestamp_serialization")]
@@ -148,13 +161,24 @@ pub struct MetaStats {
     pub liquidity_fees: u64,
     #[serde(rename = "pools")]
     pub pools: Vec<Pool>,
+    #[serde(rename = "runePriceUSD", with = "float_serialization")]
+    pub rune_price_usd: f64,
+    #[serde(rename = "startTime", with = "timestamp_serialization")]
+    pub start_time: DateTime<Utc>,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct EarningsHistoryResponse {
     pub intervals: Vec<IntervalData>,
+    // Midgard always sends it, our handlers leave it out for an empty page
     #[serde(rename = "meta")]
-    pub meta_stats: MetaStats,
+    pub meta_stats: Option<MetaStats>,
+    // Pass back as `cursor` for the following or preceding page, null at either end
+    pub next_cursor: Option<String>,
+    pub prev_cursor: Option<String>,
+    // Ours only, left at the default when reading midgard's responses
+    #[serde(default)]
+    pub pagination: Pagination,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -165,15 +189,66 @@ pub struct EarningsHistoryParams {
     pub to: Option<DateTime<Utc>>,
 }
 
+#[derive(Debug, Deserialize, Clone, Copy, Default, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EarningsSortField {
+    #[default]
+    #[serde(alias = "timestamp")]
+    StartTime,
+    EndTime,
+    AvgNodeCount,
+    BlockRewards,
+    BondingEarnings,
+    Earnings,
+    LiquidityEarnings,
+    LiquidityFees,
+    RunePriceUsd,
+    Revision,
+}
+
+impl Column for EarningsSortField {
+    fn column(&self) -> &'static str {
+        match self {
+            EarningsSortField::StartTime => "start_time",
+            EarningsSortField::EndTime => "end_time",
+            EarningsSortField::AvgNodeCount => "avg_node_count",
+            EarningsSortField::BlockRewards => "block_rewards",
+            EarningsSortField::BondingEarnings => "bonding_earnings",
+            EarningsSortField::Earnings => "earnings",
+            EarningsSortField::LiquidityEarnings => "liquidity_earnings",
+            EarningsSortField::LiquidityFees => "liquidity_fees",
+            EarningsSortField::RunePriceUsd => "rune_price_usd",
+            EarningsSortField::Revision => "revision",
+        }
+    }
+
+    fn kind(&self) -> Option<ColumnKind> {
+        match self {
+            EarningsSortField::StartTime | EarningsSortField::EndTime => None,
+            EarningsSortField::AvgNodeCount | EarningsSortField::RunePriceUsd => {
+                Some(ColumnKind::Float)
+            }
+            _ => Some(ColumnKind::Int),
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct EarningsHistoryQueryParams {
     pub date_range: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub interval: Option<Interval>,
+    pub count: Option<u32>,
     pub page: Option<u32>,
+    pub cursor: Option<String>,
     pub limit: Option<u32>,
-    pub sort_by: Option<String>,
+    pub sort_by: Option<EarningsSortField>,
     pub order: Option<String>,
     pub earnings_gt: Option<u64>,
     pub block_rewards_gt: Option<u64>,
     pub node_count_gt: Option<f64>,
     pub pool: Option<String>,
+    pub filter: Option<String>,
+    pub fields: Option<String>,
 }
@@ -0,0 +1,93 @@
+use super::filters::{find_column, Column};
+use serde::{ser::Error, Serialize, Serializer};
+use serde_json::Value;
+
+// Reads `fields`, the comma separated columns to return. `extra` are the ones that aren't
+// sortable, like a depth's pool. None when every column is wanted
+pub fn parse_fields<C: Column>(
+    fields: &Option<String>,
+    extra: &[&'static str],
+) -> Result<Option<Vec<&'static str>>, String> {
+    let Some(fields) = fields else {
+        return Ok(None);
+    };
+
+    let mut columns = Vec::new();
+    for name in fields.split(',').map(str::trim) {
+        let column = extra
+            .iter()
+            .find(|column| **column == name)
+            .copied()
+            .or_else(|| find_column::<C>(name).map(|column| column.column()))
+            .ok_or_else(|| format!("Unknown field {}", name))?;
+        if !columns.contains(&column) {
+            columns.push(column);
+        }
+    }
+
+    Ok(Some(columns))
+}
+
+// A history response with only the asked for columns left in its intervals. The meta and the
+// paging stay whole
+pub struct Projected<T> {
+    pub response: T,
+    pub fields: Option<Vec<&'static str>>,
+}
+
+impl<T: Serialize> Serialize for Projected<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let Some(fields) = &self.fields else {
+            return self.response.serialize(serializer);
+        };
+
+        let mut response = serde_json::to_value(&self.response).map_err(S::Error::custom)?;
+        if let Some(Value::Array(intervals)) = response.get_mut("intervals") {
+            for interval in intervals.iter_mut().filter_map(Value::as_object_mut) {
+                interval.retain(|key, _| fields.iter().any(|field| same_name(key, field)));
+            }
+        }
+
+        response.serialize(serializer)
+    }
+}
+
+// Columns are snake case and the json keys midgard's camel case, `asset_price_usd` is
+// `assetPriceUSD`. Compared without underscores and case they're the same
+fn same_name(key: &str, column: &str) -> bool {
+    key.to_ascii_lowercase() == column.replace('_', "")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::models::depth_history::DepthSortField;
+    use serde_json::json;
+
+    #[test]
+    fn keeps_only_the_requested_columns() {
+        let fields = parse_fields::<DepthSortField>(
+            &Some("timestamp,asset_price_usd,pool,start_time".to_string()),
+            &["pool"],
+        )
+        .unwrap();
+        assert_eq!(fields, Some(vec!["start_time", "asset_price_usd", "pool"]));
+        assert!(parse_fields::<DepthSortField>(&Some("units,price".to_string()), &[]).is_err());
+        assert!(parse_fields::<DepthSortField>(&Some("pool".to_string()), &[]).is_err());
+
+        let projected = Projected {
+            response: json!({
+                "intervals": [{ "startTime": "1", "assetPriceUSD": "2.5", "units": "3", "pool": "BTC.BTC" }],
+                "meta": { "startTime": "1", "units": "3" }
+            }),
+            fields,
+        };
+        assert_eq!(
+            serde_json::to_value(&projected).unwrap(),
+            json!({
+                "intervals": [{ "startTime": "1", "assetPriceUSD": "2.5", "pool": "BTC.BTC" }],
+                "meta": { "startTime": "1", "units": "3" }
+            })
+        );
+    }
+}
@@ -0,0 +1,177 @@
+use serde::de::{value::StrDeserializer, DeserializeOwned};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnKind {
+    Int,
+    Float,
+}
+
+// A dataset's sort fields double as the columns `filter` accepts
+pub trait Column: Copy + std::fmt::Debug + DeserializeOwned {
+    fn column(&self) -> &'static str;
+
+    // How a filter value for the column is read, None for the timestamps `from` and `to` cover
+    fn kind(&self) -> Option<ColumnKind>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FilterOp {
+    Lt,
+    Lte,
+    Gt,
+    Gte,
+    Eq,
+    Between,
+}
+
+impl FilterOp {
+    fn parse(op: &str) -> Option<Self> {
+        match op {
+            "lt" => Some(FilterOp::Lt),
+            "lte" => Some(FilterOp::Lte),
+            "gt" => Some(FilterOp::Gt),
+            "gte" => Some(FilterOp::Gte),
+            "eq" => Some(FilterOp::Eq),
+            "between" => Some(FilterOp::Between),
+            _ => None,
+        }
+    }
+}
+
+// Amounts are stored as signed integers, so an integer value has to fit one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FilterValue {
+    Int(i64),
+    Float(f64),
+}
+
+impl FilterValue {
+    fn parse(value: &str, kind: ColumnKind) -> Option<Self> {
+        match kind {
+            ColumnKind::Int => value
+                .parse::<u64>()
+                .ok()
+                .and_then(|value| i64::try_from(value).ok())
+                .map(FilterValue::Int),
+            ColumnKind::Float => value
+                .parse::<f64>()
+                .ok()
+                .filter(|value| value.is_finite())
+                .map(FilterValue::Float),
+        }
+    }
+}
+
+// One `column:op:value` condition, `between` has an upper bound too and includes both
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Filter<C> {
+    pub column: C,
+    pub op: FilterOp,
+    pub value: FilterValue,
+    pub upper: Option<FilterValue>,
+}
+
+// The column a name from the query stands for, aliases included
+pub fn find_column<C: Column>(name: &str) -> Option<C> {
+    C::deserialize(StrDeserializer::<serde::de::value::Error>::new(name)).ok()
+}
+
+// Reads `filter`, a comma separated list like `asset_price_usd:gte:1500,units:between:10:20`
+pub fn parse_filters<C: Column>(filter: &Option<String>) -> Result<Vec<Filter<C>>, String> {
+    let Some(filter) = filter else {
+        return Ok(Vec::new());
+    };
+
+    filter.split(',').map(parse_filter).collect()
+}
+
+fn parse_filter<C: Column>(condition: &str) -> Result<Filter<C>, String> {
+    let parts: Vec<&str> = condition.trim().split(':').collect();
+    let (name, op, values) = match parts.as_slice() {
+        [name, op, values @ ..] if !values.is_empty() => (*name, *op, values),
+        _ => {
+            return Err(format!(
+                "Invalid filter {}, expected column:op:value",
+                condition
+            ))
+        }
+    };
+
+    let column = find_column::<C>(name).ok_or_else(|| format!("Unknown filter column {}", name))?;
+    let kind = column
+        .kind()
+        .ok_or_else(|| format!("{} can't be filtered on, use from and to", name))?;
+    let op = FilterOp::parse(op).ok_or_else(|| {
+        format!(
+            "Unknown filter operator {}, expected lt, lte, gt, gte, eq or between",
+            op
+        )
+    })?;
+
+    let parse = |value: &str| {
+        FilterValue::parse(value, kind)
+            .ok_or_else(|| format!("Invalid value {} for filter column {}", value, name))
+    };
+    let (value, upper) = match (op, values) {
+        (FilterOp::Between, [low, high]) => (parse(low)?, Some(parse(high)?)),
+        (FilterOp::Between, _) => {
+            return Err(format!(
+                "Invalid filter {}, expected column:between:low:high",
+                condition
+            ))
+        }
+        (_, [value]) => (parse(value)?, None),
+        _ => {
+            return Err(format!(
+                "Invalid filter {}, expected column:op:value",
+                condition
+            ))
+        }
+    };
+
+    Ok(Filter {
+        column,
+        op,
+        value,
+        upper,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::models::swap_history::SwapSortField;
+
+    fn parse(filter: &str) -> Result<Vec<Filter<SwapSortField>>, String> {
+        parse_filters(&Some(filter.to_string()))
+    }
+
+    #[test]
+    fn reads_conditions_on_whitelisted_columns() {
+        let filters = parse("rune_price_usd:gte:1.5,volume:between:10:20").unwrap();
+
+        assert_eq!(filters[0].column.column(), "rune_price_usd");
+        assert_eq!(filters[0].op, FilterOp::Gte);
+        assert_eq!(filters[0].value, FilterValue::Float(1.5));
+        assert_eq!(filters[1].column.column(), "total_volume");
+        assert_eq!(
+            (filters[1].value, filters[1].upper),
+            (FilterValue::Int(10), Some(FilterValue::Int(20)))
+        );
+
+        for invalid in [
+            "total_volume",
+            "total_volume:gt",
+            "total_volume:like:1",
+            "pool:eq:1",
+            "start_time:gt:1",
+            "total_volume:gt:1.5",
+            "total_volume:gt:-1",
+            "total_volume:between:1",
+            "total_volume:gt:1:2",
+            "rune_price_usd:lt:NaN",
+        ] {
+            assert!(parse(invalid).is_err(), "{invalid} was accepted");
+        }
+    }
+}
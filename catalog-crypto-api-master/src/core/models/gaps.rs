@@ -0,0 +1,57 @@
+use super::common::Dataset;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+mod timestamp_serialization {
+    use super::*;
+    use serde::Serializer;
+
+    pub fn serialize<S>(date: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&date.timestamp().to_string())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum GapKind {
+    // No interval covers the range, the next one starts after the previous one ended
+    Missing,
+    // Two intervals both cover the range
+    Overlap,
+}
+
+// A break in a stored time series, between the end of one interval and the start of the next
+#[derive(Debug, Serialize, Clone, PartialEq, ToSchema)]
+pub struct Gap {
+    // Only depth history is kept per pool
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pool: Option<String>,
+    pub kind: GapKind,
+    #[serde(rename = "startTime", with = "timestamp_serialization")]
+    pub start_time: DateTime<Utc>,
+    #[serde(rename = "endTime", with = "timestamp_serialization")]
+    pub end_time: DateTime<Utc>,
+}
+
+impl Gap {
+    // Hourly intervals that would fill a missing range, or that cover the same hours twice
+    pub fn hours(&self) -> i64 {
+        (self.end_time - self.start_time).num_hours()
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GapsResponse {
+    pub dataset: Dataset,
+    pub gaps: Vec<Gap>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct GapsQueryParams {
+    pub dataset: Dataset,
+    pub pool: Option<String>,
+}
@@ -0,0 +1,90 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+mod optional_timestamp_serialization {
+    use super::*;
+    use serde::Serializer;
+
+    pub fn serialize<S>(date: &Option<DateTime<Utc>>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match date {
+            Some(date) => serializer.serialize_str(&date.timestamp().to_string()),
+            None => serializer.serialize_none(),
+        }
+    }
+}
+
+// The commands waiting for a job and how its latest run went. A job nobody has touched yet has
+// the defaults
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct JobState {
+    pub paused: bool,
+    pub run_requested_at: Option<DateTime<Utc>>,
+    pub rewind_to: Option<DateTime<Utc>>,
+    pub last_started_at: Option<DateTime<Utc>>,
+    pub last_finished_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+}
+
+impl JobState {
+    pub fn is_running(&self) -> bool {
+        match (self.last_started_at, self.last_finished_at) {
+            (Some(started), Some(finished)) => started > finished,
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq, ToSchema)]
+pub struct JobInfo {
+    pub name: String,
+    // Cron expression the job runs on
+    pub schedule: String,
+    // Scheduled runs are skipped while paused, asking for a run still starts one
+    pub paused: bool,
+    pub running: bool,
+    // A run was asked for and hasn't started yet
+    #[serde(rename = "runRequestedAt", with = "optional_timestamp_serialization")]
+    pub run_requested_at: Option<DateTime<Utc>>,
+    // The next run fetches everything again from here
+    #[serde(rename = "rewindTo", with = "optional_timestamp_serialization")]
+    pub rewind_to: Option<DateTime<Utc>>,
+    #[serde(rename = "lastStartedAt", with = "optional_timestamp_serialization")]
+    pub last_started_at: Option<DateTime<Utc>>,
+    #[serde(rename = "lastFinishedAt", with = "optional_timestamp_serialization")]
+    pub last_finished_at: Option<DateTime<Utc>>,
+    // Error of the latest run, none when it succeeded
+    #[serde(rename = "lastError")]
+    pub last_error: Option<String>,
+}
+
+impl JobInfo {
+    pub fn new(name: &str, schedule: &str, state: JobState) -> Self {
+        Self {
+            name: name.to_string(),
+            schedule: schedule.to_string(),
+            running: state.is_running(),
+            paused: state.paused,
+            run_requested_at: state.run_requested_at,
+            rewind_to: state.rewind_to,
+            last_started_at: state.last_started_at,
+            last_finished_at: state.last_finished_at,
+            last_error: state.last_error,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct JobsResponse {
+    pub jobs: Vec<JobInfo>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CheckpointRequest {
+    // Unix seconds, the job's next run fetches everything from here up to now again
+    pub timestamp: i64,
+}
@@ -1,5 +1,12 @@
 pub mod common;
 pub mod depth_history;
 pub mod earnings_history;
+pub mod fields;
+pub mod filters;
+pub mod gaps;
+pub mod jobs;
+pub mod pagination;
+pub mod pools;
 pub mod runepool_units_history;
+pub mod status;
 pub mod swap_history;
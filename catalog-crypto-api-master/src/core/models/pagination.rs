@@ -0,0 +1,202 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    After,
+    Before,
+}
+
+// Where a page picks up: the row it continues from, keyed by start time and id. Rows the hourly
+// fetcher inserts meanwhile don't shift it like they shift an offset. Clients only see it encoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cursor {
+    pub direction: Direction,
+    pub start_time: DateTime<Utc>,
+    pub id: i64,
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        let direction = match self.direction {
+            Direction::After => "a",
+            Direction::Before => "b",
+        };
+        URL_SAFE_NO_PAD.encode(format!(
+            "{}:{}:{}",
+            direction,
+            self.start_time.timestamp(),
+            self.id
+        ))
+    }
+
+    pub fn decode(cursor: &str) -> Option<Self> {
+        let decoded = String::from_utf8(URL_SAFE_NO_PAD.decode(cursor).ok()?).ok()?;
+        let mut parts = decoded.split(':');
+        let direction = match parts.next()? {
+            "a" => Direction::After,
+            "b" => Direction::Before,
+            _ => return None,
+        };
+        let start_time = DateTime::from_timestamp(parts.next()?.parse().ok()?, 0)?;
+        let id = parts.next()?.parse().ok()?;
+        if parts.next().is_some() {
+            return None;
+        }
+
+        Some(Cursor {
+            direction,
+            start_time,
+            id,
+        })
+    }
+
+    pub fn key(&self) -> (DateTime<Utc>, i64) {
+        (self.start_time, self.id)
+    }
+
+    // Pages before the cursor are read backwards from it, so the order flips
+    pub fn descending(cursor: Option<&Cursor>, order: Option<&str>) -> bool {
+        let descending = order == Some("desc");
+        match cursor {
+            Some(cursor) if cursor.direction == Direction::Before => !descending,
+            _ => descending,
+        }
+    }
+}
+
+// A cursor only makes sense for the default start time ordering and replaces `page`
+pub fn parse_cursor(
+    cursor: &Option<String>,
+    page: Option<u32>,
+    sort_field: &str,
+) -> Result<Option<Cursor>, String> {
+    let Some(cursor) = cursor else {
+        return Ok(None);
+    };
+
+    if page.is_some() {
+        return Err("cursor and page can't be used together".to_string());
+    }
+    if sort_field != "start_time" {
+        return Err("cursor only works when sorting by start_time".to_string());
+    }
+
+    Cursor::decode(cursor)
+        .map(Some)
+        .ok_or_else(|| format!("Invalid cursor {}", cursor))
+}
+
+pub struct CursorPage<T> {
+    pub rows: Vec<T>,
+    pub next_cursor: Option<String>,
+    pub prev_cursor: Option<String>,
+    // There are intervals after this page, whatever the sorting
+    pub has_more: bool,
+}
+
+// Builds the page out of the rows read for it. The repositories are asked for one row past
+// `limit`, which tells whether there is more in the direction of reading, and return the rows in
+// that direction. Without start time ordering there's no cursor to give
+pub fn cursor_page<T>(
+    mut rows: Vec<T>,
+    limit: u32,
+    offset: u32,
+    cursor: Option<&Cursor>,
+    keyed: bool,
+    key: fn(&T) -> (DateTime<Utc>, i64),
+) -> CursorPage<T> {
+    let more = rows.len() > limit as usize;
+    rows.truncate(limit as usize);
+
+    let backwards = cursor.is_some_and(|cursor| cursor.direction == Direction::Before);
+    if backwards {
+        rows.reverse();
+    }
+
+    let (has_next, has_prev) = match cursor {
+        Some(_) if backwards => (true, more),
+        Some(_) => (more, true),
+        None => (more, offset > 0),
+    };
+
+    let at = |row: Option<&T>, direction: Direction, wanted: bool| {
+        row.filter(|_| keyed && wanted).map(|row| {
+            let (start_time, id) = key(row);
+            Cursor {
+                direction,
+                start_time,
+                id,
+            }
+            .encode()
+        })
+    };
+    let next_cursor = at(rows.last(), Direction::After, has_next);
+    let prev_cursor = at(rows.first(), Direction::Before, has_prev);
+
+    CursorPage {
+        rows,
+        next_cursor,
+        prev_cursor,
+        has_more: has_next,
+    }
+}
+
+// Where a page sits in everything the filters match
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct Pagination {
+    // Intervals matching the filters, counted after the rollup
+    pub total: u64,
+    // null for a page read from a cursor, it has no number
+    pub page: Option<u32>,
+    pub limit: u32,
+    pub has_more: bool,
+    pub total_pages: u64,
+}
+
+impl Pagination {
+    pub fn new(total: u64, page: Option<u32>, limit: u32, has_more: bool) -> Self {
+        let total_pages = if limit == 0 {
+            0
+        } else {
+            total.div_ceil(limit as u64)
+        };
+
+        Pagination {
+            total,
+            page,
+            limit,
+            has_more,
+            total_pages,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_only_the_cursors_it_encoded() {
+        let cursor = Cursor {
+            direction: Direction::Before,
+            start_time: DateTime::from_timestamp(1704067200, 0).unwrap(),
+            id: 42,
+        };
+
+        assert_eq!(Cursor::decode(&cursor.encode()), Some(cursor));
+        assert_eq!(Cursor::decode("not a cursor"), None);
+        assert_eq!(
+            Cursor::decode(&URL_SAFE_NO_PAD.encode("a:1704067200")),
+            None
+        );
+        assert_eq!(
+            Cursor::decode(&URL_SAFE_NO_PAD.encode("x:1704067200:1")),
+            None
+        );
+        assert!(parse_cursor(&Some(cursor.encode()), Some(1), "start_time").is_err());
+        assert!(parse_cursor(&Some(cursor.encode()), None, "total_volume").is_err());
+    }
+}
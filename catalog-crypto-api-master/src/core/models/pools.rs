@@ -0,0 +1,64 @@
+use chrono::{DateTime, TimeZone, Utc};
+use prkorm::Table;
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+use utoipa::ToSchema;
+
+mod timestamp_serialization {
+    use super::*;
+    use serde::{Deserializer, Serializer};
+
+    pub fn serialize<S>(date: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&date.timestamp().to_string())
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let timestamp_str = String::deserialize(deserializer)?;
+        let timestamp = timestamp_str
+            .parse::<i64>()
+            .map_err(serde::de::Error::custom)?;
+        Ok(Utc.timestamp_opt(timestamp, 0).unwrap())
+    }
+}
+
+// Only the fields we keep in the registry, midgard sends a lot more (depths, apy, volume...)
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct PoolDetail {
+    pub asset: String,
+    pub status: String,
+    #[serde(rename = "nativeDecimal")]
+    pub native_decimal: String,
+}
+
+impl PoolDetail {
+    // Midgard reports `-1` when it doesn't know the decimals of the asset
+    pub fn decimals(&self) -> Option<i32> {
+        self.native_decimal
+            .parse::<i32>()
+            .ok()
+            .filter(|decimals| *decimals >= 0)
+    }
+}
+
+#[derive(Table, Debug, Serialize, Deserialize, FromRow, Clone, ToSchema)]
+#[table_name("`pools`")]
+pub struct PoolRecord {
+    pub asset: String,
+    pub status: String,
+    pub decimals: Option<i32>,
+    #[serde(rename = "firstSeen", with = "timestamp_serialization")]
+    pub first_seen: DateTime<Utc>,
+    #[serde(rename = "lastSynced", with = "timestamp_serialization")]
+    pub last_synced: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PoolsQueryParams {
+    pub status: Option<String>,
+}
@@ -5,6 +5,8 @@ use sqlx::prelude::FromRow;
 use utoipa::ToSchema;
 
 use super::common::Interval;
+use super::filters::{Column, ColumnKind};
+use super::pagination::Pagination;
 
 mod timestamp_serialization {
     use super::*;
@@ -52,16 +54,26 @@ mod u64_serialization {
     }
 }
 
-#[derive(Table, Debug, Serialize, Deserialize, FromRow, Clone, ToSchema)]
+#[derive(Table, Debug, Default, Serialize, Deserialize, FromRow, Clone, PartialEq, ToSchema)]
 #[table_name("`runepool_unit_intervals`")]
+#[sqlx(default)]
 pub struct RunepoolUnitsInterval {
     #[serde(rename = "count", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub count: u64,
     #[serde(rename = "endTime", with = "timestamp_serialization")]
     pub end_time: DateTime<Utc>,
+    // Row id, breaks start time ties for the pagination cursors. Midgard doesn't send it
+    #[serde(skip)]
+    #[sqlx(default)]
+    pub id: i64,
+    #[serde(default)]
+    #[sqlx(default, try_from = "i32")]
+    pub revision: u32,
     #[serde(rename = "startTime", with = "timestamp_serialization")]
     pub start_time: DateTime<Utc>,
     #[serde(rename = "units", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub units: u64,
 }
 
@@ -84,8 +96,15 @@ pub struct MetaStats {
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct RunepoolUnitsHistoryResponse {
     pub intervals: Vec<RunepoolUnitsInterval>,
+    // Midgard always sends it, our handlers leave it out for an empty page
     #[serde(rename = "meta")]
-    pub meta_stats: MetaStats,
+    pub meta_stats: Option<MetaStats>,
+    // Pass back as `cursor` for the following or preceding page, null at either end
+    pub next_cursor: Option<String>,
+    pub prev_cursor: Option<String>,
+    // Ours only, left at the default when reading midgard's responses
+    #[serde(default)]
+    pub pagination: Pagination,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -96,12 +115,50 @@ pub struct RunepoolUnitsHistoryParams {
     pub to: Option<DateTime<Utc>>,
 }
 
+#[derive(Debug, Deserialize, Clone, Copy, Default, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RunepoolUnitsSortField {
+    #[default]
+    #[serde(alias = "timestamp")]
+    StartTime,
+    EndTime,
+    Count,
+    Units,
+    Revision,
+}
+
+impl Column for RunepoolUnitsSortField {
+    fn column(&self) -> &'static str {
+        match self {
+            RunepoolUnitsSortField::StartTime => "start_time",
+            RunepoolUnitsSortField::EndTime => "end_time",
+            RunepoolUnitsSortField::Count => "count",
+            RunepoolUnitsSortField::Units => "units",
+            RunepoolUnitsSortField::Revision => "revision",
+        }
+    }
+
+    fn kind(&self) -> Option<ColumnKind> {
+        match self {
+            RunepoolUnitsSortField::StartTime | RunepoolUnitsSortField::EndTime => None,
+            _ => Some(ColumnKind::Int),
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct RunepoolUnitsHistoryQueryParams {
     pub date_range: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub interval: Option<Interval>,
+    pub count: Option<u32>,
     pub page: Option<u32>,
+    pub cursor: Option<String>,
     pub limit: Option<u32>,
-    pub sort_by: Option<String>,
+    pub sort_by: Option<RunepoolUnitsSortField>,
     pub order: Option<String>,
     pub units_gt: Option<u64>,
+    pub filter: Option<String>,
+    pub fields: Option<String>,
 }
@@ -0,0 +1,92 @@
+use super::common::Dataset;
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+mod optional_timestamp_serialization {
+    use super::*;
+    use serde::Serializer;
+
+    pub fn serialize<S>(date: &Option<DateTime<Utc>>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match date {
+            Some(date) => serializer.serialize_str(&date.timestamp().to_string()),
+            None => serializer.serialize_none(),
+        }
+    }
+}
+
+// What the hourly fetcher recorded about its fetches of one dataset. A success resets the
+// failure count but keeps the last error around
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IngestionState {
+    pub last_success_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub last_error_at: Option<DateTime<Utc>>,
+    pub consecutive_failures: u32,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq, ToSchema)]
+pub struct DatasetStatus {
+    pub dataset: Dataset,
+    // End of the newest stored interval
+    #[serde(rename = "latestEndTime", with = "optional_timestamp_serialization")]
+    pub latest_end_time: Option<DateTime<Utc>>,
+    #[serde(rename = "rowCount")]
+    pub row_count: i64,
+    #[serde(rename = "lastSuccessAt", with = "optional_timestamp_serialization")]
+    pub last_success_at: Option<DateTime<Utc>>,
+    #[serde(rename = "lastError")]
+    pub last_error: Option<String>,
+    #[serde(rename = "lastErrorAt", with = "optional_timestamp_serialization")]
+    pub last_error_at: Option<DateTime<Utc>>,
+    #[serde(rename = "consecutiveFailures")]
+    pub consecutive_failures: u32,
+    // Seconds from the newest stored interval to now, none while the dataset is empty
+    #[serde(rename = "lagSeconds")]
+    pub lag_seconds: Option<i64>,
+    // An empty dataset counts as stale
+    pub stale: bool,
+}
+
+impl DatasetStatus {
+    pub fn new(
+        dataset: Dataset,
+        latest_end_time: Option<DateTime<Utc>>,
+        row_count: i64,
+        state: IngestionState,
+    ) -> Self {
+        Self {
+            dataset,
+            latest_end_time,
+            row_count,
+            last_success_at: state.last_success_at,
+            last_error: state.last_error,
+            last_error_at: state.last_error_at,
+            consecutive_failures: state.consecutive_failures,
+            lag_seconds: None,
+            stale: true,
+        }
+    }
+
+    // The newest interval is still in progress and ends in the future, that's no lag at all
+    pub fn check_freshness(mut self, now: DateTime<Utc>, stale_after: Duration) -> Self {
+        let lag = self
+            .latest_end_time
+            .map(|end_time| (now - end_time).max(Duration::zero()));
+        self.lag_seconds = lag.map(|lag| lag.num_seconds());
+        self.stale = lag.is_none_or(|lag| lag > stale_after);
+        self
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct StatusResponse {
+    // False as soon as any dataset is stale, what alerting should page on
+    pub healthy: bool,
+    #[serde(rename = "staleAfterSeconds")]
+    pub stale_after_seconds: i64,
+    pub datasets: Vec<DatasetStatus>,
+}
@@ -5,6 +5,8 @@ use sqlx::prelude::FromRow;
 use utoipa::ToSchema;
 
 use super::common::Interval;
+use super::filters::{Column, ColumnKind};
+use super::pagination::Pagination;
 
 mod float_serialization {
     use serde::{de::Deserializer, ser::Serializer, Deserialize};
@@ -75,9 +77,10 @@ mod u64_serialization {
     }
 }
 
-#[derive(Table, Debug, Serialize, Deserialize, FromRow, Clone, ToSchema)]
+#[derive(Table, Debug, Default, Serialize, Deserialize, FromRow, Clone, PartialEq, ToSchema)]
 #[table_name("`swap_intervals`")]
 #[serde(rename_all = "camelCase")]
+#[sqlx(default)]
 pub struct SwapInterval {
     #[serde(rename = "averageSlip", with = "float_serialization")]
     pub average_slip: f64,
@@ -86,13 +89,24 @@ pub struct SwapInterval {
     #[serde(rename = "fromTradeAverageSlip", with = "float_serialization")]
     pub from_trade_average_slip: f64,
     #[serde(rename = "fromTradeCount", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub from_trade_count: u64,
     #[serde(rename = "fromTradeFees", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub from_trade_fees: u64,
     #[serde(rename = "fromTradeVolume", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub from_trade_volume: u64,
     #[serde(rename = "fromTradeVolumeUSD", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub from_trade_volume_usd: u64,
+    // Row id, breaks start time ties for the pagination cursors. Midgard doesn't send it
+    #[serde(skip)]
+    #[sqlx(default)]
+    pub id: i64,
+    #[serde(default)]
+    #[sqlx(default, try_from = "i32")]
+    pub revision: u32,
     #[serde(rename = "runePriceUSD", with = "float_serialization")]
     pub rune_price_usd: f64,
     #[serde(rename = "startTime", with = "timestamp_serialization")]
@@ -100,64 +114,88 @@ pub struct SwapInterval {
     #[serde(rename = "synthMintAverageSlip", with = "float_serialization")]
     pub synth_mint_average_slip: f64,
     #[serde(rename = "synthMintCount", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub synth_mint_count: u64,
     #[serde(rename = "synthMintFees", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub synth_mint_fees: u64,
     #[serde(rename = "synthMintVolume", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub synth_mint_volume: u64,
     #[serde(rename = "synthMintVolumeUSD", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub synth_mint_volume_usd: u64,
     #[serde(rename = "synthRedeemAverageSlip", with = "float_serialization")]
     pub synth_redeem_average_slip: f64,
     #[serde(rename = "synthRedeemCount", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub synth_redeem_count: u64,
     #[serde(rename = "synthRedeemFees", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub synth_redeem_fees: u64,
     #[serde(rename = "synthRedeemVolume", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub synth_redeem_volume: u64,
     #[serde(rename = "synthRedeemVolumeUSD", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub synth_redeem_volume_usd: u64,
     #[serde(rename = "toAssetAverageSlip", with = "float_serialization")]
     pub to_asset_average_slip: f64,
     #[serde(rename = "toAssetCount", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub to_asset_count: u64,
     #[serde(rename = "toAssetFees", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub to_asset_fees: u64,
     #[serde(rename = "toAssetVolume", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub to_asset_volume: u64,
     #[serde(rename = "toAssetVolumeUSD", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub to_asset_volume_usd: u64,
     #[serde(rename = "toRuneAverageSlip", with = "float_serialization")]
     pub to_rune_average_slip: f64,
     #[serde(rename = "toRuneCount", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub to_rune_count: u64,
     #[serde(rename = "toRuneFees", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub to_rune_fees: u64,
     #[serde(rename = "toRuneVolume", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub to_rune_volume: u64,
     #[serde(rename = "toRuneVolumeUSD", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub to_rune_volume_usd: u64,
     #[serde(rename = "toTradeAverageSlip", with = "float_serialization")]
     pub to_trade_average_slip: f64,
     #[serde(rename = "toTradeCount", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub to_trade_count: u64,
     #[serde(rename = "toTradeFees", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub to_trade_fees: u64,
     #[serde(rename = "toTradeVolume", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub to_trade_volume: u64,
     #[serde(rename = "toTradeVolumeUSD", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub to_trade_volume_usd: u64,
     #[serde(rename = "totalCount", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub total_count: u64,
     #[serde(rename = "totalFees", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub total_fees: u64,
     #[serde(rename = "totalVolume", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub total_volume: u64,
     #[serde(rename = "totalVolumeUSD", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub total_volume_usd: u64,
 }
 
-#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[derive(Debug, Serialize, Deserialize, FromRow, ToSchema)]
 pub struct SwapMeta {
     #[serde(rename = "averageSlip", with = "float_serialization")]
     pub average_slip: f64,
@@ -166,12 +204,16 @@ pub struct SwapMeta {
     #[serde(rename = "fromTradeAverageSlip", with = "float_serialization")]
     pub from_trade_average_slip: f64,
     #[serde(rename = "fromTradeCount", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub from_trade_count: u64,
     #[serde(rename = "fromTradeFees", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub from_trade_fees: u64,
     #[serde(rename = "fromTradeVolume", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub from_trade_volume: u64,
     #[serde(rename = "fromTradeVolumeUSD", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub from_trade_volume_usd: u64,
     #[serde(rename = "runePriceUSD", with = "float_serialization")]
     pub rune_price_usd: f64,
@@ -180,68 +222,145 @@ pub struct SwapMeta {
     #[serde(rename = "synthMintAverageSlip", with = "float_serialization")]
     pub synth_mint_average_slip: f64,
     #[serde(rename = "synthMintCount", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub synth_mint_count: u64,
     #[serde(rename = "synthMintFees", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub synth_mint_fees: u64,
     #[serde(rename = "synthMintVolume", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub synth_mint_volume: u64,
     #[serde(rename = "synthMintVolumeUSD", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub synth_mint_volume_usd: u64,
     #[serde(rename = "synthRedeemAverageSlip", with = "float_serialization")]
     pub synth_redeem_average_slip: f64,
     #[serde(rename = "synthRedeemCount", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub synth_redeem_count: u64,
     #[serde(rename = "synthRedeemFees", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub synth_redeem_fees: u64,
     #[serde(rename = "synthRedeemVolume", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub synth_redeem_volume: u64,
     #[serde(rename = "synthRedeemVolumeUSD", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub synth_redeem_volume_usd: u64,
     #[serde(rename = "toAssetAverageSlip", with = "float_serialization")]
     pub to_asset_average_slip: f64,
     #[serde(rename = "toAssetCount", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub to_asset_count: u64,
     #[serde(rename = "toAssetFees", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub to_asset_fees: u64,
     #[serde(rename = "toAssetVolume", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub to_asset_volume: u64,
     #[serde(rename = "toAssetVolumeUSD", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub to_asset_volume_usd: u64,
     #[serde(rename = "toRuneAverageSlip", with = "float_serialization")]
     pub to_rune_average_slip: f64,
     #[serde(rename = "toRuneCount", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub to_rune_count: u64,
     #[serde(rename = "toRuneFees", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub to_rune_fees: u64,
     #[serde(rename = "toRuneVolume", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub to_rune_volume: u64,
     #[serde(rename = "toRuneVolumeUSD", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub to_rune_volume_usd: u64,
     #[serde(rename = "toTradeAverageSlip", with = "float_serialization")]
     pub to_trade_average_slip: f64,
     #[serde(rename = "toTradeCount", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub to_trade_count: u64,
     #[serde(rename = "toTradeFees", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub to_trade_fees: u64,
     #[serde(rename = "toTradeVolume", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub to_trade_volume: u64,
     #[serde(rename = "toTradeVolumeUSD", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub to_trade_volume_usd: u64,
     #[serde(rename = "totalCount", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub total_count: u64,
     #[serde(rename = "totalFees", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub total_fees: u64,
     #[serde(rename = "totalVolume", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub total_volume: u64,
     #[serde(rename = "totalVolumeUSD", with = "u64_serialization")]
+    #[sqlx(try_from = "i64")]
     pub total_volume_usd: u64,
 }
 
+// A swap rollup over the whole filtered range, without the revision
+impl From<SwapInterval> for SwapMeta {
+    fn from(interval: SwapInterval) -> Self {
+        Self {
+            average_slip: interval.average_slip,
+            end_time: interval.end_time,
+            from_trade_average_slip: interval.from_trade_average_slip,
+            from_trade_count: interval.from_trade_count,
+            from_trade_fees: interval.from_trade_fees,
+            from_trade_volume: interval.from_trade_volume,
+            from_trade_volume_usd: interval.from_trade_volume_usd,
+            rune_price_usd: interval.rune_price_usd,
+            start_time: interval.start_time,
+            synth_mint_average_slip: interval.synth_mint_average_slip,
+            synth_mint_count: interval.synth_mint_count,
+            synth_mint_fees: interval.synth_mint_fees,
+            synth_mint_volume: interval.synth_mint_volume,
+            synth_mint_volume_usd: interval.synth_mint_volume_usd,
+            synth_redeem_average_slip: interval.synth_redeem_average_slip,
+            synth_redeem_count: interval.synth_redeem_count,
+            synth_redeem_fees: interval.synth_redeem_fees,
+            synth_redeem_volume: interval.synth_redeem_volume,
+            synth_redeem_volume_usd: interval.synth_redeem_volume_usd,
+            to_asset_average_slip: interval.to_asset_average_slip,
+            to_asset_count: interval.to_asset_count,
+            to_asset_fees: interval.to_asset_fees,
+            to_asset_volume: interval.to_asset_volume,
+            to_asset_volume_usd: interval.to_asset_volume_usd,
+            to_rune_average_slip: interval.to_rune_average_slip,
+            to_rune_count: interval.to_rune_count,
+            to_rune_fees: interval.to_rune_fees,
+            to_rune_volume: interval.to_rune_volume,
+            to_rune_volume_usd: interval.to_rune_volume_usd,
+            to_trade_average_slip: interval.to_trade_average_slip,
+            to_trade_count: interval.to_trade_count,
+            to_trade_fees: interval.to_trade_fees,
+            to_trade_volume: interval.to_trade_volume,
+            to_trade_volume_usd: interval.to_trade_volume_usd,
+            total_count: interval.total_count,
+            total_fees: interval.total_fees,
+            total_volume: interval.total_volume,
+            total_volume_usd: interval.total_volume_usd,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct SwapHistoryResponse {
     pub intervals: Vec<SwapInterval>,
+    // Midgard always sends it, our handlers leave it out for an empty page
     #[serde(rename = "meta")]
-    pub meta_stats: SwapMeta,
+    pub meta_stats: Option<SwapMeta>,
+    // Pass back as `cursor` for the following or preceding page, null at either end
+    pub next_cursor: Option<String>,
+    pub prev_cursor: Option<String>,
+    // Ours only, left at the default when reading midgard's responses
+    #[serde(default)]
+    pub pagination: Pagination,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -252,13 +371,124 @@ pub struct SwapHistoryParams {
     pub to: Option<DateTime<Utc>>,
 }
 
+#[derive(Debug, Deserialize, Clone, Copy, Default, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SwapSortField {
+    #[default]
+    #[serde(alias = "timestamp")]
+    StartTime,
+    EndTime,
+    AverageSlip,
+    FromTradeAverageSlip,
+    FromTradeCount,
+    FromTradeFees,
+    FromTradeVolume,
+    FromTradeVolumeUsd,
+    RunePriceUsd,
+    SynthMintAverageSlip,
+    SynthMintCount,
+    SynthMintFees,
+    SynthMintVolume,
+    SynthMintVolumeUsd,
+    SynthRedeemAverageSlip,
+    SynthRedeemCount,
+    SynthRedeemFees,
+    SynthRedeemVolume,
+    SynthRedeemVolumeUsd,
+    ToAssetAverageSlip,
+    ToAssetCount,
+    ToAssetFees,
+    ToAssetVolume,
+    ToAssetVolumeUsd,
+    ToRuneAverageSlip,
+    ToRuneCount,
+    ToRuneFees,
+    ToRuneVolume,
+    ToRuneVolumeUsd,
+    ToTradeAverageSlip,
+    ToTradeCount,
+    ToTradeFees,
+    ToTradeVolume,
+    ToTradeVolumeUsd,
+    #[serde(alias = "count")]
+    TotalCount,
+    #[serde(alias = "fees")]
+    TotalFees,
+    #[serde(alias = "volume")]
+    TotalVolume,
+    TotalVolumeUsd,
+    Revision,
+}
+
+impl Column for SwapSortField {
+    fn column(&self) -> &'static str {
+        match self {
+            SwapSortField::StartTime => "start_time",
+            SwapSortField::EndTime => "end_time",
+            SwapSortField::AverageSlip => "average_slip",
+            SwapSortField::FromTradeAverageSlip => "from_trade_average_slip",
+            SwapSortField::FromTradeCount => "from_trade_count",
+            SwapSortField::FromTradeFees => "from_trade_fees",
+            SwapSortField::FromTradeVolume => "from_trade_volume",
+            SwapSortField::FromTradeVolumeUsd => "from_trade_volume_usd",
+            SwapSortField::RunePriceUsd => "rune_price_usd",
+            SwapSortField::SynthMintAverageSlip => "synth_mint_average_slip",
+            SwapSortField::SynthMintCount => "synth_mint_count",
+            SwapSortField::SynthMintFees => "synth_mint_fees",
+            SwapSortField::SynthMintVolume => "synth_mint_volume",
+            SwapSortField::SynthMintVolumeUsd => "synth_mint_volume_usd",
+            SwapSortField::SynthRedeemAverageSlip => "synth_redeem_average_slip",
+            SwapSortField::SynthRedeemCount => "synth_redeem_count",
+            SwapSortField::SynthRedeemFees => "synth_redeem_fees",
+            SwapSortField::SynthRedeemVolume => "synth_redeem_volume",
+            SwapSortField::SynthRedeemVolumeUsd => "synth_redeem_volume_usd",
+            SwapSortField::ToAssetAverageSlip => "to_asset_average_slip",
+            SwapSortField::ToAssetCount => "to_asset_count",
+            SwapSortField::ToAssetFees => "to_asset_fees",
+            SwapSortField::ToAssetVolume => "to_asset_volume",
+            SwapSortField::ToAssetVolumeUsd => "to_asset_volume_usd",
+            SwapSortField::ToRuneAverageSlip => "to_rune_average_slip",
+            SwapSortField::ToRuneCount => "to_rune_count",
+            SwapSortField::ToRuneFees => "to_rune_fees",
+            SwapSortField::ToRuneVolume => "to_rune_volume",
+            SwapSortField::ToRuneVolumeUsd => "to_rune_volume_usd",
+            SwapSortField::ToTradeAverageSlip => "to_trade_average_slip",
+            SwapSortField::ToTradeCount => "to_trade_count",
+            SwapSortField::ToTradeFees => "to_trade_fees",
+            SwapSortField::ToTradeVolume => "to_trade_volume",
+            SwapSortField::ToTradeVolumeUsd => "to_trade_volume_usd",
+            SwapSortField::TotalCount => "total_count",
+            SwapSortField::TotalFees => "total_fees",
+            SwapSortField::TotalVolume => "total_volume",
+            SwapSortField::TotalVolumeUsd => "total_volume_usd",
+            SwapSortField::Revision => "revision",
+        }
+    }
+
+    fn kind(&self) -> Option<ColumnKind> {
+        match self {
+            SwapSortField::StartTime | SwapSortField::EndTime => None,
+            SwapSortField::RunePriceUsd => Some(ColumnKind::Float),
+            _ if self.column().ends_with("_slip") => Some(ColumnKind::Float),
+            _ => Some(ColumnKind::Int),
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct SwapHistoryQueryParams {
     pub date_range: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub interval: Option<Interval>,
+    pub count: Option<u32>,
     pub page: Option<u32>,
+    pub cursor: Option<String>,
     pub limit: Option<u32>,
-    pub sort_by: Option<String>,
+    pub sort_by: Option<SwapSortField>,
     pub order: Option<String>,
     pub volume_gt: Option<u64>,
     pub fees_gt: Option<u64>,
+    pub filter: Option<String>,
+    pub fields: Option<String>,
 }
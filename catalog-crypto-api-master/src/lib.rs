@@ -0,0 +1,6 @@
+pub mod api;
+pub mod config;
+pub mod core;
+pub mod services;
+
+pub mod swagger;
@@ -1,45 +1,97 @@
-use api::routes::depth::get_depth_history;
-use api::routes::earnings::get_earnings_history;
-use api::routes::runepool::get_runepool_units_history;
-use api::routes::swap::get_swap_history;
-use api::server::fetch::{
+use axum::{
+    middleware,
+    routing::{get, post},
+    Router,
+};
+use catalog_crypto_api::api::auth::require_admin_token;
+use catalog_crypto_api::api::routes::admin::get_gaps;
+use catalog_crypto_api::api::routes::depth::get_depth_history;
+use catalog_crypto_api::api::routes::earnings::get_earnings_history;
+use catalog_crypto_api::api::routes::jobs::{
+    list_jobs, pause_job, reset_checkpoint, resume_job, run_job,
+};
+use catalog_crypto_api::api::routes::pools::{get_pool, get_pools};
+use catalog_crypto_api::api::routes::runepool::get_runepool_units_history;
+use catalog_crypto_api::api::routes::status::get_status;
+use catalog_crypto_api::api::routes::swap::get_swap_history;
+use catalog_crypto_api::api::server::fetch::{
     fetch_and_store_depth_history, fetch_and_store_earnings_history,
     fetch_and_store_runepool_units_history, fetch_and_store_swap_history,
 };
-use axum::{routing::get, Router};
-use chrono::Utc;
-use config::connect;
+use catalog_crypto_api::api::state::AppState;
+use catalog_crypto_api::config::connect::{self, Database};
+use catalog_crypto_api::core::models::common::Dataset;
+use catalog_crypto_api::services::jobs::{backfill::Backfill, leader::LeaderElection};
+use catalog_crypto_api::services::repository::checkpoints::BackfillRange;
+use catalog_crypto_api::services::{
+    client::{
+        get_admin_token, get_backfill_on_start, get_database_url, get_midgard_api_url,
+        MidgardClient,
+    },
+    spawn::{schedule_jobs, spawn_cron_jobs},
+};
+use catalog_crypto_api::swagger::SwaggerApiDoc;
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::{Args, Parser, Subcommand};
 use dotenv::dotenv;
 use http::Method;
-use services::{
-    client::get_midgard_api_url, jobs::cron::hourly_fetcher::HourlyFetcher, spawn::spawn_cron_jobs,
-};
 use std::env;
 use std::net::SocketAddr;
-use swagger::SwaggerApiDoc;
 use tokio::net::TcpListener;
 use tower_http::cors::{Any, CorsLayer};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
-mod api;
-mod config;
-mod core;
-mod services;
+#[derive(Parser)]
+#[command(version, about = "Thorchain history api fed from midgard")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
 
-mod swagger;
+#[derive(Subcommand)]
+enum Command {
+    /// Runs the api along with the ingestion jobs, what happens without a command
+    Serve(ServeArgs),
+    /// Loads one dataset between two dates from midgard and exits
+    Backfill(BackfillArgs),
+}
+
+#[derive(Args, Default)]
+struct ServeArgs {
+    /// Only serve the api, ingestion is left to other processes
+    #[arg(long, conflicts_with = "ingest_only")]
+    no_ingest: bool,
+    /// Only run the ingestion jobs, without the api
+    #[arg(long)]
+    ingest_only: bool,
+}
+
+#[derive(Args)]
+struct BackfillArgs {
+    /// depth, earnings, swap or runepool
+    #[arg(long, value_parser = parse_dataset)]
+    dataset: Dataset,
+    /// Start of the range, a date (2023-01-01) or an rfc3339 timestamp
+    #[arg(long, value_parser = parse_time)]
+    from: DateTime<Utc>,
+    /// End of the range (exclusive), defaults to now
+    #[arg(long, value_parser = parse_time)]
+    to: Option<DateTime<Utc>>,
+    /// Depth history only, can be repeated. Every tracked pool when left out
+    #[arg(long = "pool")]
+    pools: Vec<String>,
+}
 
-/* ************************************************************ */
-/* ************************************************************ */
-/* !NOTE: PLEASE FETCH THINGS ONE BY ONE BECAUSE OF RATE LIMITS */
-/* ************************************************************ */
-/* ************************************************************ */
 #[tokio::main(flavor = "multi_thread", worker_threads = 10)]
 async fn main() {
     dotenv().ok();
+    let cli = Cli::parse();
 
-    let database_url = std::env::var("DATABASE_URL").expect("Database url issue");
+    setup_tracing();
+
+    let database_url = get_database_url();
 
     tracing::info!(
         "Env variables are \n{}\n{}",
@@ -47,28 +99,89 @@ async fn main() {
         database_url
     );
 
-    let pool = connect::connect_database(&database_url)
+    let db = connect::connect_database(&database_url)
         .await
         .expect("Failed to connect to database");
 
-    setup_tracing();
-
     tracing::info!("Connected to database...");
+
+    match cli
+        .command
+        .unwrap_or_else(|| Command::Serve(ServeArgs::default()))
+    {
+        Command::Serve(args) => serve(db, args).await,
+        Command::Backfill(args) => backfill(db, args).await,
+    }
+}
+
+async fn serve(db: Database, args: ServeArgs) {
     println!("Current Utc TimeStamp: {:?}", Utc::now().timestamp());
 
-    // !NOTE: Uncomment this if you want to fetch initial data and read the comment above the main
-    // spawn_cron_jobs(pool.clone());
-    // fetch_initial_data(pool.clone()).await;
+    if args.no_ingest {
+        start_server(db).await;
+        return;
+    }
+
+    if let Err(e) = schedule_jobs(db.clone()) {
+        tracing::error!("Failed to schedule the ingestion jobs: {}", e);
+        std::process::exit(1);
+    }
+
+    // Every replica can run with ingestion on, only the one holding the lease ingests
+    let election = LeaderElection::new(db.clone(), "ingestion");
+    tokio::select! {
+        _ = election.run(|| ingest(db.clone())) => {}
+        _ = start_server(db.clone()), if !args.ingest_only => {}
+        _ = tokio::signal::ctrl_c() => tracing::info!("Shutting down..."),
+    }
+
+    if let Err(e) = election.release().await {
+        tracing::error!("Failed to release the ingestion lease: {}", e);
+    }
+}
+
+// What the replica holding the ingestion lease runs, all of it stops when the lease is lost
+async fn ingest(db: Database) {
+    // !NOTE: Set BACKFILL_ON_START=true if you want to fetch initial data
+    let _backfills = if get_backfill_on_start() {
+        let backfills = spawn_cron_jobs(db.clone());
+        fetch_initial_data(db.clone()).await;
+        Some(backfills)
+    } else {
+        None
+    };
+
+    schedule_jobs(db)
+        .expect("schedules are checked on startup")
+        .run()
+        .await;
+}
+
+async fn backfill(db: Database, args: BackfillArgs) {
+    let range = BackfillRange {
+        from: args.from,
+        to: args.to.unwrap_or_else(Utc::now),
+    };
+
+    let backfill = Backfill::new(db, args.dataset, range).with_pools(args.pools);
+    if let Err(e) = backfill.run().await {
+        tracing::error!("Backfill failed: {}", e);
+        std::process::exit(1);
+    }
+}
 
-    let hourly_pool = pool.clone();
-    tokio::spawn(async move {
-        let mut hourly_fetcher = HourlyFetcher::new(hourly_pool);
-        if let Err(e) = hourly_fetcher.start().await {
-            tracing::error!("Hourly fetcher failed: {}", e);
-        }
-    });
+fn parse_dataset(value: &str) -> Result<Dataset, String> {
+    Dataset::try_from(value.to_string())
+}
+
+fn parse_time(value: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(date.and_hms_opt(0, 0, 0).unwrap().and_utc());
+    }
 
-    start_server(pool).await;
+    DateTime::parse_from_rfc3339(value)
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(|_| format!("{} is neither a date nor an rfc3339 timestamp", value))
 }
 
 fn setup_tracing() {
@@ -81,16 +194,29 @@ fn setup_tracing() {
         .init();
 }
 
-async fn fetch_initial_data(pool: sqlx::MySqlPool) {
+async fn fetch_initial_data(db: Database) {
     tracing::info!("Starting initial data fetch...");
 
-    fetch_and_store_depth_history(&pool).await;
-    fetch_and_store_earnings_history(&pool).await;
-    fetch_and_store_swap_history(&pool).await;
-    fetch_and_store_runepool_units_history(&pool).await;
+    let midgard = MidgardClient::from_env();
+    fetch_and_store_depth_history(&db, &midgard).await;
+    fetch_and_store_earnings_history(&db, &midgard).await;
+    fetch_and_store_swap_history(&db, &midgard).await;
+    fetch_and_store_runepool_units_history(&db, &midgard).await;
 }
 
-async fn start_server(pool: sqlx::MySqlPool) {
+async fn start_server(db: Database) {
+    let admin = Router::new()
+        .route("/admin/gaps", get(get_gaps))
+        .route("/admin/jobs", get(list_jobs))
+        .route("/admin/jobs/:name/pause", post(pause_job))
+        .route("/admin/jobs/:name/resume", post(resume_job))
+        .route("/admin/jobs/:name/run", post(run_job))
+        .route("/admin/jobs/:name/checkpoint", post(reset_checkpoint))
+        .route_layer(middleware::from_fn_with_state(
+            get_admin_token(),
+            require_admin_token,
+        ));
+
     let app = Router::new()
         .layer(CorsLayer::new().allow_origin(Any).allow_methods([
             Method::GET,
@@ -102,7 +228,11 @@ async fn start_server(pool: sqlx::MySqlPool) {
         .route("/earning_history", get(get_earnings_history))
         .route("/swap_history", get(get_swap_history))
         .route("/runepool_units_history", get(get_runepool_units_history))
-        .with_state(pool)
+        .route("/pools", get(get_pools))
+        .route("/pools/:asset", get(get_pool))
+        .route("/status", get(get_status))
+        .merge(admin)
+        .with_state(AppState::new(db))
         .merge(SwaggerUi::new("/").url("/api-docs/openapi.json", SwaggerApiDoc::openapi()));
 
     let addr = SocketAddr::from(([0, 0, 0, 0], 3000));
@@ -0,0 +1,345 @@
+use super::{get_midgard_api_url, get_midgard_rate_limit, rate_limit::TokenBucket};
+use crate::core::models::{
+    common::Interval,
+    depth_history::{DepthHistoryParams, DepthHistoryResponse},
+    earnings_history::{EarningsHistoryParams, EarningsHistoryResponse},
+    pools::PoolDetail,
+    runepool_units_history::{RunepoolUnitsHistoryParams, RunepoolUnitsHistoryResponse},
+    swap_history::{SwapHistoryParams, SwapHistoryResponse},
+};
+use chrono::{DateTime, Utc};
+use reqwest::{header::RETRY_AFTER, StatusCode};
+use serde::de::DeserializeOwned;
+use std::future::Future;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tokio::time;
+use tracing::warn;
+
+// How many requests can go out back to back before the rate kicks in
+const BURST: f64 = 5.0;
+
+#[derive(Debug, thiserror::Error)]
+pub enum MidgardError {
+    #[error("midgard rate limited the request to {url}")]
+    RateLimited {
+        url: String,
+        retry_after: Option<Duration>,
+    },
+    #[error("request to {url} failed: {source}")]
+    Request {
+        url: String,
+        #[source]
+        source: reqwest::Error,
+    },
+    #[error("midgard answered {status} for {url}: {body}")]
+    Status {
+        url: String,
+        status: StatusCode,
+        body: String,
+    },
+    #[error("failed to parse the response from {url}: {source}, response text (first 500 chars): {body}")]
+    Decode {
+        url: String,
+        #[source]
+        source: serde_json::Error,
+        body: String,
+    },
+}
+
+impl MidgardError {
+    // Rate limits, dropped connections and midgard's own failures tend to pass, a 404 or a body
+    // we can't read won't get any better by asking again
+    fn is_transient(&self) -> bool {
+        match self {
+            MidgardError::RateLimited { .. } | MidgardError::Request { .. } => true,
+            MidgardError::Status { status, .. } => status.is_server_error(),
+            MidgardError::Decode { .. } => false,
+        }
+    }
+}
+
+// Exponential backoff, each delay is somewhere between half and all of `base * 2^attempt`
+#[derive(Debug, Clone, Copy)]
+struct Backoff {
+    base: Duration,
+    max: Duration,
+    max_retries: u32,
+}
+
+impl Backoff {
+    fn delay(&self, attempt: u32) -> Duration {
+        let ceiling = self
+            .base
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max);
+        ceiling / 2 + ceiling.mul_f64(fastrand::f64() / 2.0)
+    }
+}
+
+// Typed access to the midgard endpoints the jobs read. Clones are cheap and every client in the
+// process shares one connection pool and one rate limiter, however many jobs hold one
+#[derive(Clone)]
+pub struct MidgardClient {
+    http: reqwest::Client,
+    limiter: Arc<TokenBucket>,
+    base_url: String,
+    backoff: Backoff,
+}
+
+impl MidgardClient {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        static SHARED: OnceLock<(reqwest::Client, Arc<TokenBucket>)> = OnceLock::new();
+        let (http, limiter) = SHARED.get_or_init(|| {
+            (
+                reqwest::Client::new(),
+                Arc::new(TokenBucket::new(get_midgard_rate_limit(), BURST)),
+            )
+        });
+
+        Self {
+            http: http.clone(),
+            limiter: limiter.clone(),
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            backoff: Backoff {
+                base: Duration::from_secs(1),
+                max: Duration::from_secs(60),
+                max_retries: 6,
+            },
+        }
+    }
+
+    pub fn from_env() -> Self {
+        Self::new(get_midgard_api_url())
+    }
+
+    pub async fn pools(&self) -> Result<Vec<PoolDetail>, MidgardError> {
+        self.get("/pools", &[]).await
+    }
+
+    pub async fn depth_history(
+        &self,
+        pool_name: &str,
+        params: &DepthHistoryParams,
+    ) -> Result<DepthHistoryResponse, MidgardError> {
+        let query = history_query(&params.interval, params.count, params.from, params.to);
+        let mut depth_history: DepthHistoryResponse = self
+            .get(&format!("/history/depths/{}", pool_name), &query)
+            .await?;
+
+        // Midgard doesn't repeat the pool in the intervals
+        for interval in depth_history.intervals.iter_mut() {
+            interval.pool = pool_name.to_string();
+        }
+        Ok(depth_history)
+    }
+
+    pub async fn earnings_history(
+        &self,
+        params: &EarningsHistoryParams,
+    ) -> Result<EarningsHistoryResponse, MidgardError> {
+        let query = history_query(&params.interval, params.count, params.from, params.to);
+        self.get("/history/earnings", &query).await
+    }
+
+    pub async fn swap_history(
+        &self,
+        params: &SwapHistoryParams,
+    ) -> Result<SwapHistoryResponse, MidgardError> {
+        let query = history_query(&params.interval, params.count, params.from, params.to);
+        self.get("/history/swaps", &query).await
+    }
+
+    pub async fn runepool_units_history(
+        &self,
+        params: &RunepoolUnitsHistoryParams,
+    ) -> Result<RunepoolUnitsHistoryResponse, MidgardError> {
+        let query = history_query(&params.interval, params.count, params.from, params.to);
+        self.get("/history/runepool", &query).await
+    }
+
+    // For the backfills, which would rather ask again than stop at a malformed body since those
+    // are usually cut off on the way. `get` already retries whatever else tends to pass, so a body
+    // is only asked for once more here and any other error goes straight back to the caller, whose
+    // next scheduled run starts again from the checkpoint
+    pub async fn until_ok<T, F, Fut>(&self, what: &str, mut request: F) -> Result<T, MidgardError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, MidgardError>>,
+    {
+        match request().await {
+            Err(e @ MidgardError::Decode { .. }) => {
+                let delay = self.backoff.delay(0);
+                warn!("Failed to fetch {}: {}, retrying in {:?}", what, e, delay);
+                time::sleep(delay).await;
+                request().await
+            }
+            result => result,
+        }
+    }
+
+    async fn get<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, String)],
+    ) -> Result<T, MidgardError> {
+        let url = format!("{}{}", self.base_url, path);
+
+        let mut attempt = 0;
+        loop {
+            self.limiter.acquire().await;
+
+            let error = match self.send(&url, query).await {
+                Ok(body) => {
+                    return serde_json::from_str(&body).map_err(|source| MidgardError::Decode {
+                        url,
+                        source,
+                        body: body.chars().take(500).collect(),
+                    })
+                }
+                Err(e) => e,
+            };
+
+            if !error.is_transient() || attempt >= self.backoff.max_retries {
+                return Err(error);
+            }
+
+            let delay = match &error {
+                MidgardError::RateLimited {
+                    retry_after: Some(retry_after),
+                    ..
+                } => (*retry_after).min(self.backoff.max),
+                _ => self.backoff.delay(attempt),
+            };
+            // Midgard limits by client, so everyone else waits it out too
+            if let MidgardError::RateLimited { .. } = error {
+                self.limiter.pause_for(delay);
+            }
+
+            warn!("{}, retrying in {:?}", error, delay);
+            time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    async fn send(&self, url: &str, query: &[(&str, String)]) -> Result<String, MidgardError> {
+        let request_error = |source| MidgardError::Request {
+            url: url.to_string(),
+            source,
+        };
+
+        let response = self
+            .http
+            .get(url)
+            .query(query)
+            .send()
+            .await
+            .map_err(request_error)?;
+        let status = response.status();
+
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            let retry_after = response
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.trim().parse().ok())
+                .map(Duration::from_secs);
+            return Err(MidgardError::RateLimited {
+                url: url.to_string(),
+                retry_after,
+            });
+        }
+
+        let body = response.text().await.map_err(request_error)?;
+        if !status.is_success() {
+            return Err(MidgardError::Status {
+                url: url.to_string(),
+                status,
+                body: body.chars().take(500).collect(),
+            });
+        }
+
+        Ok(body)
+    }
+}
+
+fn history_query(
+    interval: &Option<Interval>,
+    count: Option<u32>,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> Vec<(&'static str, String)> {
+    let mut query = Vec::new();
+
+    if let Some(interval) = interval {
+        query.push(("interval", interval.to_string()));
+    }
+    if let Some(count) = count {
+        query.push(("count", count.to_string()));
+    }
+    if let Some(from) = from {
+        query.push(("from", from.timestamp().to_string()));
+    }
+    if let Some(to) = to {
+        query.push(("to", to.timestamp().to_string()));
+    }
+
+    query
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_its_cap() {
+        let backoff = Backoff {
+            base: Duration::from_secs(1),
+            max: Duration::from_secs(60),
+            max_retries: 6,
+        };
+
+        for (attempt, ceiling) in [(0, 1), (1, 2), (3, 8), (10, 60)] {
+            let delay = backoff.delay(attempt);
+            let ceiling = Duration::from_secs(ceiling);
+            assert!(delay >= ceiling / 2 && delay <= ceiling, "{delay:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn until_ok_retries_only_a_malformed_body_once() {
+        let mut client = MidgardClient::new("http://midgard.invalid");
+        client.backoff = Backoff {
+            base: Duration::from_millis(1),
+            max: Duration::from_millis(1),
+            max_retries: 3,
+        };
+        let url = "http://midgard.invalid".to_string();
+        let attempts = std::sync::atomic::AtomicU32::new(0);
+
+        let result: Result<(), _> = client
+            .until_ok("nothing", || async {
+                attempts.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                Err(MidgardError::Decode {
+                    url: url.clone(),
+                    source: serde_json::from_str::<u8>("{").unwrap_err(),
+                    body: "{".to_string(),
+                })
+            })
+            .await;
+        assert!(matches!(result, Err(MidgardError::Decode { .. })));
+        assert_eq!(attempts.swap(0, std::sync::atomic::Ordering::SeqCst), 2);
+
+        let result: Result<(), _> = client
+            .until_ok("nothing", || async {
+                attempts.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                Err(MidgardError::RateLimited {
+                    url: url.clone(),
+                    retry_after: None,
+                })
+            })
+            .await;
+        assert!(matches!(result, Err(MidgardError::RateLimited { .. })));
+        assert_eq!(attempts.into_inner(), 1);
+    }
+}
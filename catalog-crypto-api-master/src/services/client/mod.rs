@@ -0,0 +1,86 @@
+use std::env;
+
+mod midgard;
+mod rate_limit;
+
+pub use midgard::{MidgardClient, MidgardError};
+
+// Falls back to a sqlite file next to the binary so the api runs without any database server
+pub fn get_database_url() -> String {
+    env::var("DATABASE_URL").unwrap_or_else(|_| "sqlite://catalog.db".to_string())
+}
+
+pub fn get_midgard_api_url() -> String {
+    env::var("MIDGARD_API_URL").unwrap_or_else(|_| "http://rick_roll.com".to_string())
+}
+
+// Comma separated list of pools to track depth history for, e.g. `BTC.BTC,ETH.ETH`
+// When it's not set every available pool from the pool registry is tracked
+pub fn get_depth_pools() -> Option<Vec<String>> {
+    env::var("MIDGARD_DEPTH_POOLS").ok().map(|pools| {
+        pools
+            .split(',')
+            .map(|pool| pool.trim().to_string())
+            .filter(|pool| !pool.is_empty())
+            .collect()
+    })
+}
+
+// Backfills the whole history on startup, keep it off unless you need it because of midgard's
+// rate limits
+pub fn get_backfill_on_start() -> bool {
+    env::var("BACKFILL_ON_START")
+        .map(|value| value == "true" || value == "1")
+        .unwrap_or(false)
+}
+
+// How many of the latest hourly intervals the hourly fetcher asks midgard for again. The newest
+// one is still in progress when it's first stored, re-fetching lets it settle to its final values
+pub fn get_refetch_intervals() -> u32 {
+    env::var("MIDGARD_REFETCH_INTERVALS")
+        .ok()
+        .and_then(|count| count.parse().ok())
+        .filter(|count| *count > 0)
+        .unwrap_or(3)
+}
+
+// Requests per second all jobs together may send to midgard, bursts of a few are let through
+pub fn get_midgard_rate_limit() -> f64 {
+    env::var("MIDGARD_RATE_LIMIT")
+        .ok()
+        .and_then(|rate| rate.parse().ok())
+        .filter(|rate: &f64| *rate > 0.0)
+        .unwrap_or(2.0)
+}
+
+// Cron expression for one of the scheduled jobs, e.g. SCHEDULE_SWAP="0 * * * *". Five fields, or
+// six with the seconds in front
+pub fn get_schedule(job: &str, default: &str) -> String {
+    env::var(format!("SCHEDULE_{}", job.to_uppercase())).unwrap_or_else(|_| default.to_string())
+}
+
+// How old the newest stored interval of a dataset may get before GET /status reports it stale
+pub fn get_stale_after_minutes() -> i64 {
+    env::var("STALE_AFTER_MINUTES")
+        .ok()
+        .and_then(|minutes| minutes.parse().ok())
+        .filter(|minutes| *minutes > 0)
+        .unwrap_or(120)
+}
+
+// How long the ingestion lease lasts without a renewal, which is how long it takes another
+// replica to take over from one that died
+pub fn get_lease_ttl_seconds() -> i64 {
+    env::var("LEASE_TTL_SECONDS")
+        .ok()
+        .and_then(|seconds| seconds.parse().ok())
+        .filter(|seconds| *seconds >= 3)
+        .unwrap_or(30)
+}
+
+// Bearer token the /admin routes ask for, they are turned off while it isn't set
+pub fn get_admin_token() -> Option<String> {
+    env::var("ADMIN_TOKEN")
+        .ok()
+        .filter(|token| !token.trim().is_empty())
+}
@@ -0,0 +1,70 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::time;
+
+// Token bucket every midgard request goes through. Tokens refill at `rate` per second up to
+// `capacity`, so a few requests can go out back to back but the average stays under the rate
+pub(crate) struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    state: Mutex<BucketState>,
+}
+
+struct BucketState {
+    tokens: f64,
+    refilled_at: Instant,
+    // Set when midgard told us to back off, nobody sends anything until then
+    paused_until: Option<Instant>,
+}
+
+impl TokenBucket {
+    pub(crate) fn new(rate: f64, capacity: f64) -> Self {
+        Self {
+            rate,
+            capacity,
+            state: Mutex::new(BucketState {
+                tokens: capacity,
+                refilled_at: Instant::now(),
+                paused_until: None,
+            }),
+        }
+    }
+
+    pub(crate) async fn acquire(&self) {
+        loop {
+            let wait = {
+                let mut state = self.state.lock().unwrap();
+                let now = Instant::now();
+
+                match state.paused_until {
+                    Some(until) if until > now => until - now,
+                    _ => {
+                        state.paused_until = None;
+                        let elapsed = now.duration_since(state.refilled_at).as_secs_f64();
+                        state.tokens = (state.tokens + elapsed * self.rate).min(self.capacity);
+                        state.refilled_at = now;
+
+                        if state.tokens >= 1.0 {
+                            state.tokens -= 1.0;
+                            return;
+                        }
+                        Duration::from_secs_f64((1.0 - state.tokens) / self.rate)
+                    }
+                }
+            };
+
+            time::sleep(wait).await;
+        }
+    }
+
+    pub(crate) fn pause_for(&self, duration: Duration) {
+        let until = Instant::now() + duration;
+        let mut state = self.state.lock().unwrap();
+        if state
+            .paused_until
+            .is_none_or(|paused_until| paused_until < until)
+        {
+            state.paused_until = Some(until);
+        }
+    }
+}
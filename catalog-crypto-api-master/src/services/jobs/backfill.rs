@@ -0,0 +1,205 @@
+use crate::config::connect::Database;
+use crate::core::models::{
+    common::{Dataset, Interval},
+    depth_history::DepthHistoryParams,
+    earnings_history::EarningsHistoryParams,
+    runepool_units_history::RunepoolUnitsHistoryParams,
+    swap_history::SwapHistoryParams,
+};
+use crate::services::client::MidgardClient;
+use crate::services::repository::{
+    checkpoints::{get_backfill_progress, BackfillRange},
+    depth, earnings,
+    pools::get_tracked_pools,
+    runepool, swap,
+};
+use chrono::{DateTime, Utc};
+use tracing::info;
+
+// The most midgard hands out in one page
+const PAGE_SIZE: u32 = 400;
+
+// The next page of hourly intervals starting at `from`. Midgard doesn't take `count` together
+// with both `from` and `to`, the end of the range is applied to what comes back instead
+macro_rules! page_params {
+    ($params:ident, $from:expr) => {
+        $params {
+            interval: Some(Interval::Hour),
+            count: Some(PAGE_SIZE),
+            from: Some($from),
+            to: None,
+        }
+    };
+}
+
+// Stores what midgard returned up to the end of the range, returns how many intervals that was
+// and where the last one ended
+macro_rules! store_page {
+    ($backfill:ident, $response:expr, $repository:ident) => {{
+        let mut history = $response.await?;
+        history
+            .intervals
+            .retain(|interval| interval.start_time < $backfill.range.to);
+        if $backfill.cron_checkpoint {
+            $repository::store_page(&$backfill.db, &history.intervals).await?;
+        } else {
+            $repository::store_backfill_page(&$backfill.db, &history.intervals, $backfill.range)
+                .await?;
+        }
+        history
+            .intervals
+            .last()
+            .map(|last| (history.intervals.len(), last.end_time))
+    }};
+}
+
+// Loads one dataset between two dates and stops, for the `backfill` command. Progress is saved
+// with every page, running it again with the same range continues where it left off
+pub struct Backfill {
+    db: Database,
+    midgard: MidgardClient,
+    dataset: Dataset,
+    range: BackfillRange,
+    pools: Vec<String>,
+    cron_checkpoint: bool,
+}
+
+impl Backfill {
+    pub fn new(db: Database, dataset: Dataset, range: BackfillRange) -> Self {
+        Self {
+            db,
+            midgard: MidgardClient::from_env(),
+            dataset,
+            range,
+            pools: Vec::new(),
+            cron_checkpoint: false,
+        }
+    }
+
+    pub fn with_midgard_url(mut self, midgard_url: impl Into<String>) -> Self {
+        self.midgard = MidgardClient::new(midgard_url);
+        self
+    }
+
+    // Depth history only, without any pools every tracked pool is loaded
+    pub fn with_pools(mut self, pools: Vec<String>) -> Self {
+        self.pools = pools;
+        self
+    }
+
+    // For the hourly catch-up, pages move the crons' checkpoint instead of keeping progress for
+    // the range. The caller picks where to start from, nothing is resumed here
+    pub fn on_cron_checkpoint(mut self) -> Self {
+        self.cron_checkpoint = true;
+        self
+    }
+
+    pub async fn run(&self) -> Result<(), anyhow::Error> {
+        if self.range.from >= self.range.to {
+            anyhow::bail!(
+                "Nothing to backfill, {} is not before {}",
+                self.range.from,
+                self.range.to
+            );
+        }
+
+        if self.dataset != Dataset::Depth {
+            if !self.pools.is_empty() {
+                anyhow::bail!("Only depth history is fetched per pool");
+            }
+            return self.run_series(None).await;
+        }
+
+        let pools = if self.pools.is_empty() {
+            get_tracked_pools(&self.db).await?
+        } else {
+            self.pools.clone()
+        };
+        for pool_name in &pools {
+            self.run_series(Some(pool_name)).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn run_series(&self, pool_name: Option<&str>) -> Result<(), anyhow::Error> {
+        let label = match pool_name {
+            Some(pool_name) => format!("{} history for {}", self.dataset, pool_name),
+            None => format!("{} history", self.dataset),
+        };
+
+        let progress = if self.cron_checkpoint {
+            None
+        } else {
+            get_backfill_progress(&self.db, self.dataset, pool_name, &self.range).await?
+        };
+        let mut cursor = match progress {
+            Some(progress) => {
+                info!("Resuming {} from {}", label, progress);
+                progress
+            }
+            None => {
+                info!(
+                    "Backfilling {} from {} to {}",
+                    label, self.range.from, self.range.to
+                );
+                self.range.from
+            }
+        };
+
+        while cursor < self.range.to {
+            // An empty page means midgard has nothing after the cursor yet
+            let Some((stored, last_end_time)) =
+                self.fetch_and_store_page(pool_name, cursor).await?
+            else {
+                break;
+            };
+
+            cursor = last_end_time;
+            info!(
+                "{}: stored {} intervals up to {} ({:.1}%)",
+                label,
+                stored,
+                cursor,
+                self.percent_done(cursor)
+            );
+        }
+
+        info!("Finished backfilling {}", label);
+        Ok(())
+    }
+
+    async fn fetch_and_store_page(
+        &self,
+        pool_name: Option<&str>,
+        from: DateTime<Utc>,
+    ) -> Result<Option<(usize, DateTime<Utc>)>, anyhow::Error> {
+        let page = match self.dataset {
+            Dataset::Depth => {
+                let pool_name = pool_name.expect("depth history is fetched per pool");
+                let params = page_params!(DepthHistoryParams, from);
+                store_page!(self, self.midgard.depth_history(pool_name, &params), depth)
+            }
+            Dataset::Earnings => {
+                let params = page_params!(EarningsHistoryParams, from);
+                store_page!(self, self.midgard.earnings_history(&params), earnings)
+            }
+            Dataset::Swap => {
+                let params = page_params!(SwapHistoryParams, from);
+                store_page!(self, self.midgard.swap_history(&params), swap)
+            }
+            Dataset::Runepool => {
+                let params = page_params!(RunepoolUnitsHistoryParams, from);
+                store_page!(self, self.midgard.runepool_units_history(&params), runepool)
+            }
+        };
+
+        Ok(page)
+    }
+
+    fn percent_done(&self, cursor: DateTime<Utc>) -> f64 {
+        let total = (self.range.to - self.range.from).num_seconds() as f64;
+        let done = (cursor.min(self.range.to) - self.range.from).num_seconds() as f64;
+        done / total * 100.0
+    }
+}
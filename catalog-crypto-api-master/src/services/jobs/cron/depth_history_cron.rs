@@ -1,168 +1,195 @@
+use crate::config::connect::Database;
 use crate::{
     core::models::{
-        common::Interval,
-        depth_history::{DepthHistoryParams, DepthHistoryResponse},
+        common::{Dataset, Interval, HISTORY_START_TIMESTAMP},
+        depth_history::DepthHistoryParams,
+    },
+    services::{
+        client::{get_refetch_intervals, MidgardClient},
+        jobs::scheduler::wait_while_paused,
+        repository::{
+            checkpoints::get_checkpoint,
+            depth::{store_intervals, store_page},
+            pools::get_tracked_pools,
+        },
     },
-    services::{client::get_midgard_api_url, repository::depth::store_intervals},
 };
 use chrono::{DateTime, Duration, Utc};
-use sqlx::MySqlPool;
+use std::collections::HashMap;
 use tokio::time;
 use tracing::{error, info};
 
 pub struct DepthHistoryCron {
-    pool: MySqlPool,
+    db: Database,
+    midgard: MidgardClient,
     interval: Interval,
     count: u32,
-    last_fetch_time: Option<DateTime<Utc>>,
+    pools: Vec<String>,
+    // Every midgard pool is walked independently so each one keeps its own checkpoint
+    last_fetch_times: HashMap<String, DateTime<Utc>>,
 }
 
 impl DepthHistoryCron {
-    pub fn new(pool: MySqlPool) -> Self {
+    pub fn new(db: Database) -> Self {
         Self {
-            pool,
+            db,
+            midgard: MidgardClient::from_env(),
             interval: Interval::Hour,
             count: 400,
-            last_fetch_time: Some(DateTime::from_timestamp(1648771200, 0).unwrap()),
+            pools: Vec::new(),
+            last_fetch_times: HashMap::new(),
         }
     }
 
-    pub async fn start(&mut self) -> Result<(), anyhow::Error> {
-        loop {
-            if let Err(e) = self.fetch_and_store().await {
-                error!("Failed to fetch and store depth history: {}", e);
-                time::sleep(Duration::seconds(3).to_std().unwrap()).await;
+    // Points the cron at another midgard, e.g. a local stand-in
+    pub fn with_midgard_url(mut self, midgard_url: impl Into<String>) -> Self {
+        self.midgard = MidgardClient::new(midgard_url);
+        self
+    }
+
+    // Pools can be added to the registry at any time, new ones resume from their checkpoint
+    // or start from the beginning of the history
+    pub async fn refresh_pools(&mut self) -> Result<(), anyhow::Error> {
+        self.pools = get_tracked_pools(&self.db).await?;
+
+        for pool_name in &self.pools {
+            if self.last_fetch_times.contains_key(pool_name) {
                 continue;
             }
 
-            time::sleep(Duration::seconds(3).to_std().unwrap()).await;
+            let checkpoint = get_checkpoint(&self.db, Dataset::Depth, Some(pool_name)).await?;
+            if let Some(checkpoint) = checkpoint {
+                info!(
+                    "Resuming depth history for {} from checkpoint {}",
+                    pool_name, checkpoint
+                );
+            }
+
+            self.last_fetch_times.insert(
+                pool_name.clone(),
+                checkpoint.unwrap_or(DateTime::from_timestamp(HISTORY_START_TIMESTAMP, 0).unwrap()),
+            );
         }
-    }
 
-    async fn fetch_and_store(&mut self) -> Result<(), anyhow::Error> {
-        let client = reqwest::Client::new();
+        Ok(())
+    }
 
+    pub async fn start(&mut self) -> Result<(), anyhow::Error> {
         loop {
-            let params = DepthHistoryParams {
-                interval: Some(self.interval.clone()),
-                count: Some(self.count),
-                from: self.last_fetch_time,
-                to: None,
-            };
-
-            let base_url = get_midgard_api_url();
-            let mut url = reqwest::Url::parse(&format!("{}/history/depths/ETH.ETH", base_url))?;
-
-            if let Some(interval) = &params.interval {
-                url.query_pairs_mut()
-                    .append_pair("interval", &interval.to_string());
+            wait_while_paused(&self.db, &Dataset::Depth.to_string()).await;
+            if let Err(e) = self.refresh_pools().await {
+                error!("Failed to refresh depth history pools: {}", e);
             }
 
-            if let Some(count) = params.count {
-                url.query_pairs_mut()
-                    .append_pair("count", &count.to_string());
+            match self.fetch_and_store().await {
+                Ok(true) => {
+                    info!("Depth history is caught up, the scheduler keeps it current");
+                    return Ok(());
+                }
+                Ok(false) => {}
+                Err(e) => {
+                    error!("Failed to fetch and store depth history: {}", e);
+                    time::sleep(Duration::seconds(3).to_std().unwrap()).await;
+                }
             }
+        }
+    }
 
-            if let Some(from) = params.from {
-                url.query_pairs_mut()
-                    .append_pair("from", &from.timestamp().to_string());
-            }
+    // Returns whether every pool is caught up, a pool that failed is not
+    pub async fn fetch_and_store(&mut self) -> Result<bool, anyhow::Error> {
+        let mut caught_up = true;
 
-            match client.get(url.clone()).send().await {
-                Ok(response) => {
-                    let response_text = response.text().await?;
-
-                    if response_text.contains("slow down") {
-                        tracing::warn!("Rate limited, waiting for 5 seconds before retry...");
-                        time::sleep(Duration::seconds(5).to_std().unwrap()).await;
-                        continue;
-                    }
-
-                    match serde_json::from_str::<DepthHistoryResponse>(&response_text) {
-                        Ok(depth_history) => {
-                            store_intervals(&self.pool, &depth_history.intervals).await?;
-
-                            info!(
-                                "Successfully stored {} intervals",
-                                depth_history.intervals.len()
-                            );
-
-                            if let Some(last_interval) = depth_history.intervals.last() {
-                                self.last_fetch_time = Some(last_interval.end_time);
-                                info!(
-                                    "Successfully updated depth history. URL: {} Last fetch time: {}",
-                                    url, last_interval.end_time
-                                );
-                            }
-                            break Ok(());
-                        }
-                        Err(e) => {
-                            error!(
-                                "Failed to parse response: {}, response text (first 500 chars): {}",
-                                e,
-                                response_text.chars().take(500).collect::<String>()
-                            );
-                            time::sleep(Duration::seconds(5).to_std().unwrap()).await;
-                            continue;
-                        }
-                    }
-                }
+        for pool_name in self.pools.clone() {
+            match self.fetch_and_store_pool(&pool_name).await {
+                Ok(pool_caught_up) => caught_up &= pool_caught_up,
                 Err(e) => {
-                    error!("Request failed: {}", e);
-                    time::sleep(Duration::seconds(5).to_std().unwrap()).await;
-                    continue;
+                    error!(
+                        "Failed to fetch and store depth history for {}: {}",
+                        pool_name, e
+                    );
+                    caught_up = false;
                 }
             }
         }
+
+        Ok(caught_up)
+    }
+
+    async fn fetch_and_store_pool(&mut self, pool_name: &str) -> Result<bool, anyhow::Error> {
+        let params = DepthHistoryParams {
+            interval: Some(self.interval.clone()),
+            count: Some(self.count),
+            from: self.last_fetch_times.get(pool_name).copied(),
+            to: None,
+        };
+
+        let depth_history = self
+            .midgard
+            .until_ok(&format!("depth history for {}", pool_name), || {
+                self.midgard.depth_history(pool_name, &params)
+            })
+            .await?;
+        let summary = store_page(&self.db, &depth_history.intervals).await?;
+
+        info!(
+            "Successfully stored {} intervals for {} ({})",
+            depth_history.intervals.len(),
+            pool_name,
+            summary
+        );
+
+        if let Some(last_interval) = depth_history.intervals.last() {
+            self.last_fetch_times
+                .insert(pool_name.to_string(), last_interval.end_time);
+            info!(
+                "Successfully updated depth history for {}. Last fetch time: {}",
+                pool_name, last_interval.end_time
+            );
+        }
+
+        Ok(depth_history.intervals.len() < self.count as usize)
     }
-    // Box Pin to avoid indefinite recursion
+
     pub async fn fetch_latest_hour(&mut self) -> Result<(), anyhow::Error> {
-        let now = Utc::now();
-        let one_hour_ago = now - Duration::hours(1);
-
-        // let params = DepthHistoryParams {
-        //     interval: Some(Interval::Hour),
-        //     count: Some(1),
-        //     from: Some(one_hour_ago),
-        //     to: Some(now),
-        // };
-
-        let client = reqwest::Client::new();
-        let base_url = get_midgard_api_url();
-        let mut url = reqwest::Url::parse(&format!("{}/history/depths/ETH.ETH", base_url))?;
-
-        url.query_pairs_mut()
-            .append_pair("interval", "hour")
-            .append_pair("count", "1")
-            .append_pair("from", &one_hour_ago.timestamp().to_string())
-            .append_pair("to", &now.timestamp().to_string());
-
-        match client.get(url.clone()).send().await {
-            Ok(response) => {
-                let response_text = response.text().await?;
-
-                if response_text.contains("slow down") {
-                    tracing::warn!("Rate limited, waiting for 5 seconds before retry...");
-                    time::sleep(Duration::seconds(5).to_std().unwrap()).await;
-                    return Box::pin(self.fetch_latest_hour()).await;
-                }
+        self.refresh_pools().await?;
 
-                match serde_json::from_str::<DepthHistoryResponse>(&response_text) {
-                    Ok(depth_history) => {
-                        store_intervals(&self.pool, &depth_history.intervals).await?;
-                        info!("Successfully stored latest hour depth data");
-                        Ok(())
-                    }
-                    Err(e) => {
-                        error!("Failed to parse response: {}", e);
-                        Err(anyhow::anyhow!("Failed to parse response"))
-                    }
-                }
-            }
-            Err(e) => {
-                error!("Request failed: {}", e);
-                Err(anyhow::anyhow!("Request failed"))
+        let mut failed_pools = Vec::new();
+
+        for pool_name in self.pools.clone() {
+            if let Err(e) = self.fetch_latest_hour_for_pool(&pool_name).await {
+                error!(
+                    "Failed to fetch latest depth history for {}: {}",
+                    pool_name, e
+                );
+                failed_pools.push(pool_name);
             }
         }
+
+        if !failed_pools.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Failed to fetch latest hour for pools: {}",
+                failed_pools.join(", ")
+            ));
+        }
+
+        Ok(())
+    }
+
+    async fn fetch_latest_hour_for_pool(&mut self, pool_name: &str) -> Result<(), anyhow::Error> {
+        let params = DepthHistoryParams {
+            interval: Some(Interval::Hour),
+            count: Some(get_refetch_intervals()),
+            from: None,
+            to: Some(Utc::now()),
+        };
+
+        let depth_history = self.midgard.depth_history(pool_name, &params).await?;
+        let summary = store_intervals(&self.db, &depth_history.intervals).await?;
+        info!(
+            "Successfully stored latest hour depth data for {} ({})",
+            pool_name, summary
+        );
+
+        Ok(())
     }
 }
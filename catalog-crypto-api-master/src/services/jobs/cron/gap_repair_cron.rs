@@ -0,0 +1,78 @@
+use crate::config::connect::Database;
+use crate::core::models::{common::Dataset, gaps::GapKind};
+use crate::services::{
+    client::get_midgard_api_url,
+    jobs::backfill::Backfill,
+    repository::{checkpoints::BackfillRange, gaps::find_gaps},
+};
+use tracing::{error, info, warn};
+
+// A missed hourly cycle leaves a hole the hourly fetcher never looks at again. This goes over the
+// stored series (every six hours by default) and re-fetches whatever is missing. Overlaps are only reported,
+// fetching again can't take an interval away
+pub struct GapRepairCron {
+    db: Database,
+    midgard_url: String,
+}
+
+impl GapRepairCron {
+    pub fn new(db: Database) -> Self {
+        Self {
+            db,
+            midgard_url: get_midgard_api_url(),
+        }
+    }
+
+    pub fn with_midgard_url(mut self, midgard_url: impl Into<String>) -> Self {
+        self.midgard_url = midgard_url.into();
+        self
+    }
+
+    // Every missing range is fetched like a `backfill` run would, so a range that's cut short
+    // picks up where it stopped on the next pass
+    pub async fn repair(&self) -> Result<(), anyhow::Error> {
+        for dataset in [
+            Dataset::Depth,
+            Dataset::Earnings,
+            Dataset::Swap,
+            Dataset::Runepool,
+        ] {
+            for gap in find_gaps(&self.db, dataset, None).await? {
+                let series = match &gap.pool {
+                    Some(pool_name) => format!("{} history for {}", dataset, pool_name),
+                    None => format!("{} history", dataset),
+                };
+
+                if gap.kind == GapKind::Overlap {
+                    warn!(
+                        "{} has {} overlapping hours from {}",
+                        series,
+                        gap.hours(),
+                        gap.start_time
+                    );
+                    continue;
+                }
+
+                info!(
+                    "Re-fetching {} missing hours of {} from {}",
+                    gap.hours(),
+                    series,
+                    gap.start_time
+                );
+                let range = BackfillRange {
+                    from: gap.start_time,
+                    to: gap.end_time,
+                };
+                let backfill = Backfill::new(self.db.clone(), dataset, range)
+                    .with_midgard_url(&self.midgard_url)
+                    .with_pools(gap.pool.into_iter().collect());
+
+                if let Err(e) = backfill.run().await {
+                    error!("Failed to re-fetch {}: {}", series, e);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
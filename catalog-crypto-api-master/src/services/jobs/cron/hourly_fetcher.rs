@@ -1,6 +1,14 @@
+use crate::config::connect::Database;
+use crate::core::models::common::Dataset;
+use crate::services::client::{get_midgard_api_url, get_refetch_intervals};
+use crate::services::jobs::backfill::Backfill;
+use crate::services::repository::{
+    checkpoints::{get_checkpoint, BackfillRange},
+    jobs::{clear_rewind, get_job_state},
+    pools::get_tracked_pools,
+    status::{get_latest_end_time, record_failure, record_success},
+};
 use chrono::{DateTime, Duration, Utc};
-use sqlx::MySqlPool;
-use tokio::time;
 use tracing::{error, info};
 
 use crate::services::jobs::cron::{
@@ -8,66 +16,177 @@ use crate::services::jobs::cron::{
     runepool_units_history_cron::RunepoolUnitsHistoryCron, swap_history_cron::SwapHistoryCron,
 };
 
+// Keeps every dataset current, the scheduler runs `fetch_dataset` once per dataset on its own
+// schedule
 pub struct HourlyFetcher {
-    pool: MySqlPool,
-    last_run: DateTime<Utc>,
+    db: Database,
+    midgard_url: String,
 }
 
 impl HourlyFetcher {
-    pub fn new(pool: MySqlPool) -> Self {
+    pub fn new(db: Database) -> Self {
         Self {
-            pool,
-            last_run: Utc::now(),
+            db,
+            midgard_url: get_midgard_api_url(),
         }
     }
 
-    pub async fn start(&mut self) -> Result<(), anyhow::Error> {
-        info!("Starting hourly fetcher...");
+    pub fn with_midgard_url(mut self, midgard_url: impl Into<String>) -> Self {
+        self.midgard_url = midgard_url.into();
+        self
+    }
 
-        loop {
-            let now = Utc::now();
-            let duration_since_last = now - self.last_run;
+    // One pass over every dataset, a failing one is logged and doesn't stop the others. The
+    // shared midgard client paces the requests so the datasets run back to back
+    pub async fn fetch_cycle(&self) {
+        info!("Starting hourly data fetch cycle...");
 
-            if duration_since_last >= Duration::hours(1) {
-                info!("Starting hourly data fetch cycle...");
-                self.last_run = now;
+        for dataset in [
+            Dataset::Depth,
+            Dataset::Earnings,
+            Dataset::Swap,
+            Dataset::Runepool,
+        ] {
+            if let Err(e) = self.fetch_dataset(dataset).await {
+                error!("Failed to fetch {} history: {}", dataset, e);
+            }
+        }
 
-                // Fetch depth history
-                let depth_pool = self.pool.clone();
-                let mut depth_cron = DepthHistoryCron::new(depth_pool);
-                if let Err(e) = depth_cron.fetch_latest_hour().await {
-                    error!("Failed to fetch depth history: {}", e);
-                }
-                time::sleep(Duration::seconds(3).to_std().unwrap()).await;
+        info!("Completed hourly data fetch cycle");
+    }
 
-                // Fetch earnings history
-                let earnings_pool = self.pool.clone();
-                let mut earnings_cron = EarningsHistoryCron::new(earnings_pool);
-                if let Err(e) = earnings_cron.fetch_latest_hour().await {
-                    error!("Failed to fetch earnings history: {}", e);
-                }
-                time::sleep(Duration::seconds(3).to_std().unwrap()).await;
+    pub async fn fetch_dataset(&self, dataset: Dataset) -> Result<(), anyhow::Error> {
+        let result = match self.catch_up(dataset).await {
+            Ok(()) => self.fetch_latest_hour(dataset).await,
+            Err(e) => Err(e),
+        };
+        self.record(dataset, &result).await;
 
-                // Fetch swap history
-                let swap_pool = self.pool.clone();
-                let mut swap_cron = SwapHistoryCron::new(swap_pool);
-                if let Err(e) = swap_cron.fetch_latest_hour().await {
-                    error!("Failed to fetch swap history: {}", e);
-                }
-                time::sleep(Duration::seconds(3).to_std().unwrap()).await;
+        result
+    }
+
+    // After downtime the newest stored interval is further back than the re-fetched ones reach,
+    // the hours in between are backfilled first. Depth history is checked per tracked pool, a
+    // pool that lags behind the others is caught up on its own. An empty series is left to the
+    // full backfill. A checkpoint reset from the admin routes goes back further, everything from
+    // there is fetched again
+    async fn catch_up(&self, dataset: Dataset) -> Result<(), anyhow::Error> {
+        let job = dataset.to_string();
+        let rewind_to = get_job_state(&self.db, &job).await?.rewind_to;
+        let series = match dataset {
+            Dataset::Depth => get_tracked_pools(&self.db)
+                .await?
+                .into_iter()
+                .map(Some)
+                .collect(),
+            _ => vec![None],
+        };
+
+        for pool_name in &series {
+            self.catch_up_series(dataset, pool_name.as_deref(), rewind_to)
+                .await?;
+        }
+
+        if let Some(rewind_to) = rewind_to {
+            clear_rewind(&self.db, &job, rewind_to).await?;
+        }
+
+        Ok(())
+    }
+
+    // The pages move the cron checkpoint, so a rewind that was interrupted continues from where
+    // it got to. Without one the stored intervals show where to continue
+    async fn catch_up_series(
+        &self,
+        dataset: Dataset,
+        pool_name: Option<&str>,
+        rewind_to: Option<DateTime<Utc>>,
+    ) -> Result<(), anyhow::Error> {
+        let label = match pool_name {
+            Some(pool_name) => format!("{} history for {}", dataset, pool_name),
+            None => format!("{} history", dataset),
+        };
 
-                // Fetch runepool units history
-                let runepool_pool = self.pool.clone();
-                let mut runepool_cron = RunepoolUnitsHistoryCron::new(runepool_pool);
-                if let Err(e) = runepool_cron.fetch_latest_hour().await {
-                    error!("Failed to fetch runepool units history: {}", e);
+        let from = match rewind_to {
+            Some(rewind_to) => {
+                let checkpoint = get_checkpoint(&self.db, dataset, pool_name).await?;
+                let from = checkpoint.map_or(rewind_to, |checkpoint| checkpoint.max(rewind_to));
+                info!("Fetching {} again from {}", label, from);
+                from
+            }
+            None => {
+                let Some(latest_end_time) =
+                    get_latest_end_time(&self.db, dataset, pool_name).await?
+                else {
+                    return Ok(());
+                };
+                if latest_end_time >= Utc::now() - Duration::hours(get_refetch_intervals() as i64) {
+                    return Ok(());
                 }
 
-                info!("Completed hourly data fetch cycle");
+                info!(
+                    "{} stops at {}, catching up before the hourly fetch",
+                    label, latest_end_time
+                );
+                latest_end_time
             }
+        };
+
+        let range = BackfillRange {
+            from,
+            to: Utc::now(),
+        };
+        if range.from >= range.to {
+            return Ok(());
+        }
+
+        Backfill::new(self.db.clone(), dataset, range)
+            .with_midgard_url(&self.midgard_url)
+            .with_pools(pool_name.map(str::to_string).into_iter().collect())
+            .on_cron_checkpoint()
+            .run()
+            .await
+    }
+
+    async fn fetch_latest_hour(&self, dataset: Dataset) -> Result<(), anyhow::Error> {
+        let db = self.db.clone();
+        match dataset {
+            Dataset::Depth => {
+                DepthHistoryCron::new(db)
+                    .with_midgard_url(&self.midgard_url)
+                    .fetch_latest_hour()
+                    .await
+            }
+            Dataset::Earnings => {
+                EarningsHistoryCron::new(db)
+                    .with_midgard_url(&self.midgard_url)
+                    .fetch_latest_hour()
+                    .await
+            }
+            Dataset::Swap => {
+                SwapHistoryCron::new(db)
+                    .with_midgard_url(&self.midgard_url)
+                    .fetch_latest_hour()
+                    .await
+            }
+            Dataset::Runepool => {
+                RunepoolUnitsHistoryCron::new(db)
+                    .with_midgard_url(&self.midgard_url)
+                    .fetch_latest_hour()
+                    .await
+            }
+        }
+    }
+
+    // Keeps the outcome for GET /status, errors would otherwise only reach the logs
+    async fn record(&self, dataset: Dataset, result: &Result<(), anyhow::Error>) {
+        let recorded = match result {
+            Ok(()) => record_success(&self.db, dataset, Utc::now()).await,
+            Err(e) => record_failure(&self.db, dataset, &e.to_string(), Utc::now()).await,
+        };
 
-            // Sleep for a minute before checking again
-            time::sleep(Duration::minutes(1).to_std().unwrap()).await;
+        if let Err(e) = recorded {
+            error!("Failed to record the {} history fetch: {}", dataset, e);
         }
     }
 }
@@ -1,5 +1,7 @@
 pub mod depth_history_cron;
 pub mod earnings_history_cron;
+pub mod gap_repair_cron;
 pub mod hourly_fetcher;
+pub mod pools_cron;
 pub mod runepool_units_history_cron;
 pub mod swap_history_cron;
@@ -0,0 +1,30 @@
+use crate::config::connect::Database;
+use crate::services::{client::MidgardClient, repository::pools::store_pools};
+use tracing::info;
+
+pub struct PoolsCron {
+    db: Database,
+    midgard: MidgardClient,
+}
+
+impl PoolsCron {
+    pub fn new(db: Database) -> Self {
+        Self {
+            db,
+            midgard: MidgardClient::from_env(),
+        }
+    }
+
+    pub fn with_midgard_url(mut self, midgard_url: impl Into<String>) -> Self {
+        self.midgard = MidgardClient::new(midgard_url);
+        self
+    }
+
+    pub async fn sync(&mut self) -> Result<(), anyhow::Error> {
+        let pools = self.midgard.pools().await?;
+        store_pools(&self.db, &pools).await?;
+        info!("Successfully synced {} pools", pools.len());
+
+        Ok(())
+    }
+}
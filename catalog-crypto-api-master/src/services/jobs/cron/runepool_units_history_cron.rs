@@ -1,168 +1,130 @@
+use crate::config::connect::Database;
 use crate::{
     core::models::{
-        common::Interval,
-        runepool_units_history::{RunepoolUnitsHistoryParams, RunepoolUnitsHistoryResponse},
+        common::{Dataset, Interval, HISTORY_START_TIMESTAMP},
+        runepool_units_history::RunepoolUnitsHistoryParams,
+    },
+    services::{
+        client::{get_refetch_intervals, MidgardClient},
+        jobs::scheduler::wait_while_paused,
+        repository::{
+            checkpoints::get_checkpoint,
+            runepool::{store_intervals, store_page},
+        },
     },
-    services::{client::get_midgard_api_url, repository::runepool::store_intervals},
 };
 use chrono::{DateTime, Duration, Utc};
-use sqlx::MySqlPool;
 use tokio::time;
 use tracing::{error, info};
 
 pub struct RunepoolUnitsHistoryCron {
-    pool: MySqlPool,
+    db: Database,
+    midgard: MidgardClient,
     interval: Interval,
     count: u32,
     last_fetch_time: Option<DateTime<Utc>>,
 }
 
 impl RunepoolUnitsHistoryCron {
-    pub fn new(pool: MySqlPool) -> Self {
+    pub fn new(db: Database) -> Self {
         Self {
-            pool,
+            db,
+            midgard: MidgardClient::from_env(),
             interval: Interval::Hour,
             count: 400,
-            last_fetch_time: Some(DateTime::from_timestamp(1648771200, 0).unwrap()),
+            last_fetch_time: Some(DateTime::from_timestamp(HISTORY_START_TIMESTAMP, 0).unwrap()),
         }
     }
 
-    pub async fn start(&mut self) -> Result<(), anyhow::Error> {
-        loop {
-            if let Err(e) = self.fetch_and_store().await {
-                error!("Failed to fetch and store runepool units history: {}", e);
-                time::sleep(Duration::seconds(3).to_std().unwrap()).await;
-                continue;
-            }
-
-            time::sleep(Duration::seconds(3).to_std().unwrap()).await;
-        }
+    pub fn with_midgard_url(mut self, midgard_url: impl Into<String>) -> Self {
+        self.midgard = MidgardClient::new(midgard_url);
+        self
     }
-    async fn fetch_and_store(&mut self) -> Result<(), anyhow::Error> {
-        let client = reqwest::Client::new();
-
-        loop {
-            let params = RunepoolUnitsHistoryParams {
-                interval: Some(self.interval.clone()),
-                count: Some(self.count),
-                from: self.last_fetch_time,
-                to: None,
-            };
 
-            let base_url = get_midgard_api_url();
-            let mut url = reqwest::Url::parse(&format!("{}/history/runepool", base_url))?;
-
-            if let Some(interval) = &params.interval {
-                url.query_pairs_mut()
-                    .append_pair("interval", &interval.to_string());
-            }
-
-            if let Some(count) = params.count {
-                url.query_pairs_mut()
-                    .append_pair("count", &count.to_string());
-            }
-
-            if let Some(from) = params.from {
-                url.query_pairs_mut()
-                    .append_pair("from", &from.timestamp().to_string());
-            }
-
-            match client.get(url.clone()).send().await {
-                Ok(response) => {
-                    let response_text = response.text().await?;
-
-                    if response_text.contains("slow down") {
-                        tracing::warn!("Rate limited, waiting for 5 seconds before retry...");
-                        time::sleep(Duration::seconds(5).to_std().unwrap()).await;
-                        continue;
-                    }
+    // Resume from where the last run stopped instead of walking the whole history again
+    pub async fn load_checkpoint(&mut self) -> Result<(), sqlx::Error> {
+        if let Some(checkpoint) = get_checkpoint(&self.db, Dataset::Runepool, None).await? {
+            info!(
+                "Resuming runepool units history from checkpoint {}",
+                checkpoint
+            );
+            self.last_fetch_time = Some(checkpoint);
+        }
 
-                    match serde_json::from_str::<RunepoolUnitsHistoryResponse>(&response_text) {
-                        Ok(runepool_history) => {
-                            store_intervals(&self.pool, &runepool_history.intervals).await?;
+        Ok(())
+    }
 
-                            info!(
-                                "Successfully stored {} intervals",
-                                runepool_history.intervals.len()
-                            );
+    pub async fn start(&mut self) -> Result<(), anyhow::Error> {
+        while let Err(e) = self.load_checkpoint().await {
+            error!("Failed to load runepool units history checkpoint: {}", e);
+            time::sleep(Duration::seconds(3).to_std().unwrap()).await;
+        }
 
-                            if let Some(last_interval) = runepool_history.intervals.last() {
-                                self.last_fetch_time = Some(last_interval.end_time);
-                                info!(
-                                    "Successfully updated runepool units history. URL: {} Last fetch time: {}",
-                                    url, last_interval.end_time
-                                );
-                            }
-                            break Ok(());
-                        }
-                        Err(e) => {
-                            error!(
-                                "Failed to parse response: {}, response text (first 500 chars): {}",
-                                e,
-                                response_text.chars().take(500).collect::<String>()
-                            );
-                            time::sleep(Duration::seconds(5).to_std().unwrap()).await;
-                            continue;
-                        }
-                    }
+        loop {
+            wait_while_paused(&self.db, &Dataset::Runepool.to_string()).await;
+            match self.fetch_and_store().await {
+                Ok(true) => {
+                    info!("Runepool units history is caught up, the scheduler keeps it current");
+                    return Ok(());
                 }
+                Ok(false) => {}
                 Err(e) => {
-                    error!("Request failed: {}", e);
-                    time::sleep(Duration::seconds(5).to_std().unwrap()).await;
-                    continue;
+                    error!("Failed to fetch and store runepool units history: {}", e);
+                    time::sleep(Duration::seconds(3).to_std().unwrap()).await;
                 }
             }
         }
     }
 
-    // Box Pin to avoid indefinite recursion
-    pub async fn fetch_latest_hour(&mut self) -> Result<(), anyhow::Error> {
-        let now = Utc::now();
-        let one_hour_ago = now - Duration::hours(1);
-
-        // let params = RunepoolUnitsHistoryParams {
-        //     interval: Some(Interval::Hour),
-        //     count: Some(1),
-        //     from: Some(one_hour_ago),
-        //     to: Some(now),
-        // };
-
-        let client = reqwest::Client::new();
-        let base_url = get_midgard_api_url();
-        let mut url = reqwest::Url::parse(&format!("{}/history/runepool", base_url))?;
-
-        url.query_pairs_mut()
-            .append_pair("interval", "hour")
-            .append_pair("count", "1")
-            .append_pair("from", &one_hour_ago.timestamp().to_string())
-            .append_pair("to", &now.timestamp().to_string());
-
-        match client.get(url.clone()).send().await {
-            Ok(response) => {
-                let response_text = response.text().await?;
+    // Returns whether midgard ran out of intervals, a page short of `count` is the last one
+    pub async fn fetch_and_store(&mut self) -> Result<bool, anyhow::Error> {
+        let params = RunepoolUnitsHistoryParams {
+            interval: Some(self.interval.clone()),
+            count: Some(self.count),
+            from: self.last_fetch_time,
+            to: None,
+        };
+
+        let runepool_history = self
+            .midgard
+            .until_ok("runepool units history", || {
+                self.midgard.runepool_units_history(&params)
+            })
+            .await?;
+        let summary = store_page(&self.db, &runepool_history.intervals).await?;
+
+        info!(
+            "Successfully stored {} intervals ({})",
+            runepool_history.intervals.len(),
+            summary
+        );
+
+        if let Some(last_interval) = runepool_history.intervals.last() {
+            self.last_fetch_time = Some(last_interval.end_time);
+            info!(
+                "Successfully updated runepool units history. Last fetch time: {}",
+                last_interval.end_time
+            );
+        }
 
-                if response_text.contains("slow down") {
-                    tracing::warn!("Rate limited, waiting for 5 seconds before retry...");
-                    time::sleep(Duration::seconds(5).to_std().unwrap()).await;
-                    return Box::pin(self.fetch_latest_hour()).await;
-                }
+        Ok(runepool_history.intervals.len() < self.count as usize)
+    }
 
-                match serde_json::from_str::<RunepoolUnitsHistoryResponse>(&response_text) {
-                    Ok(runepool_history) => {
-                        store_intervals(&self.pool, &runepool_history.intervals).await?;
-                        info!("Successfully stored latest hour runepool units data");
-                        Ok(())
-                    }
-                    Err(e) => {
-                        error!("Failed to parse response: {}", e);
-                        Err(anyhow::anyhow!("Failed to parse response"))
-                    }
-                }
-            }
-            Err(e) => {
-                error!("Request failed: {}", e);
-                Err(anyhow::anyhow!("Request failed"))
-            }
-        }
+    pub async fn fetch_latest_hour(&mut self) -> Result<(), anyhow::Error> {
+        let params = RunepoolUnitsHistoryParams {
+            interval: Some(Interval::Hour),
+            count: Some(get_refetch_intervals()),
+            from: None,
+            to: Some(Utc::now()),
+        };
+
+        let runepool_history = self.midgard.runepool_units_history(&params).await?;
+        let summary = store_intervals(&self.db, &runepool_history.intervals).await?;
+        info!(
+            "Successfully stored latest hour runepool units data ({})",
+            summary
+        );
+
+        Ok(())
     }
 }
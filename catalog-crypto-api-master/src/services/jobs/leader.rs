@@ -0,0 +1,92 @@
+use crate::config::connect::Database;
+use crate::services::client::get_lease_ttl_seconds;
+use crate::services::repository::leases::{release_lease, try_acquire_lease};
+use chrono::{DateTime, Duration, Utc};
+use std::future::Future;
+use tokio::task::JoinHandle;
+use tokio::time;
+use tracing::{error, info, warn};
+
+// Runs a task on one replica at a time. Every replica keeps asking for the same lease, the one
+// holding it renews it a few times per lease length and runs the task. When the holder stops
+// renewing (crashed, lost the database) the lease runs out and the next replica to ask takes over
+pub struct LeaderElection {
+    db: Database,
+    lease: String,
+    holder: String,
+    ttl: Duration,
+}
+
+impl LeaderElection {
+    pub fn new(db: Database, lease: impl Into<String>) -> Self {
+        Self {
+            db,
+            lease: lease.into(),
+            holder: instance_id(),
+            ttl: Duration::seconds(get_lease_ttl_seconds()),
+        }
+    }
+
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    // Runs until the future is dropped. `lead` is started every time this replica gets the lease
+    // and aborted as soon as it can't be sure it still has it
+    pub async fn run<F, Fut>(&self, lead: F)
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let mut leading: Option<JoinHandle<()>> = None;
+        let mut held_until: Option<DateTime<Utc>> = None;
+        let renew_every = self.ttl / 3;
+
+        loop {
+            let now = Utc::now();
+            match try_acquire_lease(&self.db, &self.lease, &self.holder, now + self.ttl, now).await
+            {
+                Ok(true) => {
+                    held_until = Some(now + self.ttl);
+                    if leading.as_ref().is_none_or(|task| task.is_finished()) {
+                        info!("{} holds the {} lease", self.holder, self.lease);
+                        leading = Some(tokio::spawn(lead()));
+                    }
+                }
+                Ok(false) => {
+                    held_until = None;
+                    if let Some(task) = leading.take() {
+                        warn!("{} lost the {} lease", self.holder, self.lease);
+                        task.abort();
+                    }
+                }
+                // The lease may still be ours, stop once the next renewal would come too late to
+                // keep it
+                Err(e) => {
+                    error!("Failed to renew the {} lease: {}", self.lease, e);
+                    if held_until.is_some_and(|until| until - renew_every <= Utc::now()) {
+                        if let Some(task) = leading.take() {
+                            warn!("{} can't renew the {} lease", self.holder, self.lease);
+                            task.abort();
+                        }
+                        held_until = None;
+                    }
+                }
+            }
+
+            time::sleep(renew_every.to_std().unwrap()).await;
+        }
+    }
+
+    // For a clean shutdown, the next replica doesn't have to wait for the lease to run out
+    pub async fn release(&self) -> Result<(), sqlx::Error> {
+        release_lease(&self.db, &self.lease, &self.holder, Utc::now()).await
+    }
+}
+
+// Tells the replicas apart in the lease table and the logs
+fn instance_id() -> String {
+    let host = std::env::var("HOSTNAME").unwrap_or_else(|_| "localhost".to_string());
+    format!("{}-{}-{:08x}", host, std::process::id(), fastrand::u32(..))
+}
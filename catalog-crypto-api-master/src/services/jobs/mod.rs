@@ -1 +1,4 @@
+pub mod backfill;
 pub mod cron;
+pub mod leader;
+pub mod scheduler;
@@ -0,0 +1,337 @@
+use crate::config::connect::Database;
+use crate::core::models::jobs::JobState;
+use crate::services::repository::jobs::{self, clear_run_request, get_job_state};
+use chrono::{DateTime, Utc};
+use cron::Schedule;
+use std::future::Future;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio::task::JoinSet;
+use tokio::time;
+use tracing::{error, info, warn};
+
+type JobFuture = Pin<Box<dyn Future<Output = Result<(), anyhow::Error>> + Send>>;
+
+// Takes the usual five fields (minute hour day month weekday) as well as the cron crate's own
+// format with seconds in front and an optional year at the end
+pub fn parse_schedule(expression: &str) -> Result<Schedule, anyhow::Error> {
+    let expression = expression.trim();
+    let with_seconds = match expression.split_whitespace().count() {
+        5 => format!("0 {}", expression),
+        _ => expression.to_string(),
+    };
+
+    Schedule::from_str(&with_seconds)
+        .map_err(|e| anyhow::anyhow!("Invalid schedule '{}': {}", expression, e))
+}
+
+// Where a job finds the commands from the admin routes and keeps how its runs went. Commands
+// are only seen on the next look, `poll` apart
+#[derive(Clone)]
+struct Controls {
+    db: Database,
+    poll: std::time::Duration,
+}
+
+pub struct Job {
+    name: String,
+    schedule: Schedule,
+    catch_up: bool,
+    run: Arc<dyn Fn() -> JobFuture + Send + Sync>,
+    // Held for as long as a run is going, a tick that finds it taken is skipped
+    running: Arc<Mutex<()>>,
+    // The runs in the background, dropping the job aborts them
+    runs: JoinSet<()>,
+    controls: Option<Controls>,
+}
+
+impl Job {
+    pub fn new<F, Fut>(name: impl Into<String>, schedule: Schedule, run: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), anyhow::Error>> + Send + 'static,
+    {
+        Self {
+            name: name.into(),
+            schedule,
+            catch_up: false,
+            run: Arc::new(move || Box::pin(run())),
+            running: Arc::new(Mutex::new(())),
+            runs: JoinSet::new(),
+            controls: None,
+        }
+    }
+
+    // Runs the job once as soon as the scheduler starts instead of waiting for the first tick
+    pub fn with_catch_up(mut self) -> Self {
+        self.catch_up = true;
+        self
+    }
+
+    // Starts a run in the background unless the previous one is still going. Returns whether a
+    // run was started
+    pub fn trigger(&mut self) -> bool {
+        let started = self.start_run();
+        if !started {
+            warn!("Job {} is still running, skipping this run", self.name);
+        }
+
+        started
+    }
+
+    fn start_run(&mut self) -> bool {
+        // Forget the runs that are done so the set doesn't grow
+        while self.runs.try_join_next().is_some() {}
+
+        let Ok(guard) = self.running.clone().try_lock_owned() else {
+            return false;
+        };
+
+        let name = self.name.clone();
+        let controls = self.controls.clone();
+        let run = (self.run)();
+        self.runs.spawn(async move {
+            let _guard = guard;
+            record_started(&controls, &name).await;
+            let result = run.await;
+            if let Err(e) = &result {
+                error!("Job {} failed: {}", name, e);
+            }
+            record_finished(&controls, &name, &result).await;
+        });
+
+        true
+    }
+
+    async fn run_now(&self) {
+        let _guard = self.running.lock().await;
+        if self.state().await.paused {
+            info!("Job {} is paused, skipping its catch-up", self.name);
+            return;
+        }
+
+        info!("Catching up on job {}", self.name);
+        record_started(&self.controls, &self.name).await;
+        let result = (self.run)().await;
+        if let Err(e) = &result {
+            error!("Job {} failed to catch up: {}", self.name, e);
+        }
+        record_finished(&self.controls, &self.name, &result).await;
+    }
+
+    // Commands for the job, the defaults when it has no controls or they can't be read
+    async fn state(&self) -> JobState {
+        let Some(controls) = &self.controls else {
+            return JobState::default();
+        };
+
+        get_job_state(&controls.db, &self.name)
+            .await
+            .unwrap_or_else(|e| {
+                error!("Failed to read the commands for job {}: {}", self.name, e);
+                JobState::default()
+            })
+    }
+
+    // Sleeps until the next time the schedule matches, measured against the wall clock every
+    // time so runs stay on their minute instead of drifting. With controls it wakes up every
+    // `poll` in between to look for commands. A requested run starts even while the job is
+    // paused, when the previous run is still going the request waits for a later look
+    async fn tick_forever(mut self) {
+        let Some(mut next) = self.schedule.upcoming(Utc).next() else {
+            warn!("Job {} has no upcoming runs left", self.name);
+            return;
+        };
+
+        loop {
+            let mut wait = (next - Utc::now()).to_std().unwrap_or_default();
+            if let Some(controls) = &self.controls {
+                wait = wait.min(controls.poll);
+            }
+            time::sleep(wait).await;
+
+            let state = self.state().await;
+            let due = Utc::now() >= next;
+            let scheduled = due && !state.paused;
+            if due && state.paused && state.run_requested_at.is_none() {
+                info!("Job {} is paused, skipping this run", self.name);
+            }
+
+            if scheduled || state.run_requested_at.is_some() {
+                if self.start_run() {
+                    if let Some(requested_at) = state.run_requested_at {
+                        info!("Started the requested run of job {}", self.name);
+                        self.clear_run_request(requested_at).await;
+                    }
+                } else if scheduled {
+                    warn!("Job {} is still running, skipping this run", self.name);
+                }
+            }
+
+            if due {
+                let Some(upcoming) = self.schedule.upcoming(Utc).next() else {
+                    warn!("Job {} has no upcoming runs left", self.name);
+                    return;
+                };
+                next = upcoming;
+            }
+        }
+    }
+
+    async fn clear_run_request(&self, requested_at: DateTime<Utc>) {
+        let Some(controls) = &self.controls else {
+            return;
+        };
+
+        if let Err(e) = clear_run_request(&controls.db, &self.name, requested_at).await {
+            error!(
+                "Failed to clear the run request of job {}: {}",
+                self.name, e
+            );
+        }
+    }
+}
+
+// Kept for the admin routes, a failure to record only reaches the logs
+async fn record_started(controls: &Option<Controls>, name: &str) {
+    let Some(controls) = controls else {
+        return;
+    };
+
+    if let Err(e) = jobs::record_started(&controls.db, name, Utc::now()).await {
+        error!("Failed to record the start of job {}: {}", name, e);
+    }
+}
+
+async fn record_finished(
+    controls: &Option<Controls>,
+    name: &str,
+    result: &Result<(), anyhow::Error>,
+) {
+    let Some(controls) = controls else {
+        return;
+    };
+
+    let error = result.as_ref().err().map(|e| e.to_string());
+    if let Err(e) = jobs::record_finished(&controls.db, name, Utc::now(), error.as_deref()).await {
+        error!("Failed to record the end of job {}: {}", name, e);
+    }
+}
+
+// For the backfills on start, which run under a job's name without going through its schedule.
+// Holds them between pages for as long as the job is paused
+pub async fn wait_while_paused(db: &Database, name: &str) {
+    let mut logged = false;
+    loop {
+        match get_job_state(db, name).await {
+            Ok(state) if state.paused => {
+                if !logged {
+                    info!("Job {} is paused, holding its backfill", name);
+                    logged = true;
+                }
+            }
+            Ok(_) => return,
+            Err(e) => {
+                error!("Failed to read the commands for job {}: {}", name, e);
+                return;
+            }
+        }
+        time::sleep(std::time::Duration::from_secs(3)).await;
+    }
+}
+
+#[derive(Default)]
+pub struct Scheduler {
+    jobs: Vec<Job>,
+    controls: Option<Controls>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_job(mut self, job: Job) -> Self {
+        self.jobs.push(job);
+        self
+    }
+
+    // Lets the jobs be paused, resumed, run and rewound through the scheduled_jobs table, which
+    // they look at every `poll`
+    pub fn with_controls(mut self, db: Database, poll: std::time::Duration) -> Self {
+        self.controls = Some(Controls { db, poll });
+        self
+    }
+
+    // Catch-up runs go one after the other in the order the jobs were added, so a job can rely
+    // on the ones before it (depth history needs the pool registry). Each job starts ticking
+    // once its own catch-up is done. Dropping the future stops every schedule and aborts the runs
+    // that are still going, so a replica that lost the ingestion lease stops writing right away
+    pub async fn run(self) {
+        let mut schedules = JoinSet::new();
+        for mut job in self.jobs {
+            job.controls = self.controls.clone();
+            if job.catch_up {
+                job.run_now().await;
+            }
+
+            info!("Scheduled job {} at '{}'", job.name, job.schedule.source());
+            schedules.spawn(job.tick_forever());
+        }
+
+        while schedules.join_next().await.is_some() {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Timelike};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::sync::Notify;
+
+    #[test]
+    fn parses_five_field_expressions_on_the_minute() {
+        let schedule = parse_schedule("5 * * * *").unwrap();
+        let after = Utc.with_ymd_and_hms(2024, 1, 1, 10, 30, 15).unwrap();
+        let next = schedule.after(&after).next().unwrap();
+
+        assert_eq!((next.hour(), next.minute(), next.second()), (11, 5, 0));
+        assert!(parse_schedule("every hour").is_err());
+    }
+
+    #[tokio::test]
+    async fn skips_a_run_while_the_previous_one_is_going() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let release = Arc::new(Notify::new());
+
+        let mut job = {
+            let (runs, release) = (runs.clone(), release.clone());
+            Job::new(
+                "test job",
+                parse_schedule("0 * * * *").unwrap(),
+                move || {
+                    let (runs, release) = (runs.clone(), release.clone());
+                    async move {
+                        runs.fetch_add(1, Ordering::SeqCst);
+                        release.notified().await;
+                        Ok(())
+                    }
+                },
+            )
+        };
+
+        assert!(job.trigger());
+        assert!(!job.trigger());
+
+        // Taking the lock waits for the run to finish
+        release.notify_one();
+        drop(job.running.lock().await);
+        assert!(job.trigger());
+        release.notify_one();
+        drop(job.running.lock().await);
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,129 @@
+use crate::config::connect::{with_pool, Backend, Database};
+use crate::core::models::common::Dataset;
+use chrono::{DateTime, Utc};
+use sqlx::{Encode, QueryBuilder, Type};
+
+// Where a backfill stopped, pool is empty for the datasets that are not per pool. Runs of the
+// `backfill` command carry the range they were asked for and are tracked apart from the crons
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    pub dataset: Dataset,
+    pub pool: String,
+    pub last_end_time: DateTime<Utc>,
+    pub range: Option<BackfillRange>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackfillRange {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+}
+
+pub async fn get_checkpoint(
+    db: &Database,
+    dataset: Dataset,
+    pool_name: Option<&str>,
+) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+    with_pool!(db, pool => {
+        let mut query = QueryBuilder::new(
+            "SELECT last_end_time FROM ingestion_checkpoints WHERE dataset = ",
+        );
+        query
+            .push_bind(dataset.to_string())
+            .push(" AND pool = ")
+            .push_bind(pool_name.unwrap_or_default().to_string());
+
+        query
+            .build_query_scalar::<DateTime<Utc>>()
+            .fetch_optional(pool)
+            .await
+    })
+}
+
+pub async fn get_backfill_progress(
+    db: &Database,
+    dataset: Dataset,
+    pool_name: Option<&str>,
+    range: &BackfillRange,
+) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+    with_pool!(db, pool => {
+        let mut query =
+            QueryBuilder::new("SELECT last_end_time FROM backfill_progress WHERE dataset = ");
+        query
+            .push_bind(dataset.to_string())
+            .push(" AND pool = ")
+            .push_bind(pool_name.unwrap_or_default().to_string())
+            .push(" AND range_start = ")
+            .push_bind(range.from)
+            .push(" AND range_end = ")
+            .push_bind(range.to);
+
+        query
+            .build_query_scalar::<DateTime<Utc>>()
+            .fetch_optional(pool)
+            .await
+    })
+}
+
+// Only builds the statement, the repositories run it in the same transaction as the intervals
+pub(crate) fn save_checkpoint_query<'a, DB>(
+    backend: Backend,
+    checkpoint: &Checkpoint,
+) -> QueryBuilder<'a, DB>
+where
+    DB: sqlx::Database,
+    String: Encode<'a, DB> + Type<DB>,
+    DateTime<Utc>: Encode<'a, DB> + Type<DB>,
+{
+    if let Some(range) = &checkpoint.range {
+        return save_backfill_progress_query(backend, checkpoint, range);
+    }
+
+    let mut query =
+        QueryBuilder::new("INSERT INTO ingestion_checkpoints (dataset, pool, last_end_time) ");
+    query.push_values([checkpoint], |mut row, checkpoint| {
+        row.push_bind(checkpoint.dataset.to_string())
+            .push_bind(checkpoint.pool.clone())
+            .push_bind(checkpoint.last_end_time);
+    });
+    query.push(match backend {
+        Backend::MySql => " ON DUPLICATE KEY UPDATE last_end_time = VALUES(last_end_time)",
+        Backend::Postgres | Backend::Sqlite => {
+            " ON CONFLICT (dataset, pool) DO UPDATE \
+             SET last_end_time = EXCLUDED.last_end_time, updated_at = CURRENT_TIMESTAMP"
+        }
+    });
+
+    query
+}
+
+fn save_backfill_progress_query<'a, DB>(
+    backend: Backend,
+    checkpoint: &Checkpoint,
+    range: &BackfillRange,
+) -> QueryBuilder<'a, DB>
+where
+    DB: sqlx::Database,
+    String: Encode<'a, DB> + Type<DB>,
+    DateTime<Utc>: Encode<'a, DB> + Type<DB>,
+{
+    let mut query = QueryBuilder::new(
+        "INSERT INTO backfill_progress (dataset, pool, range_start, range_end, last_end_time) ",
+    );
+    query.push_values([checkpoint], |mut row, checkpoint| {
+        row.push_bind(checkpoint.dataset.to_string())
+            .push_bind(checkpoint.pool.clone())
+            .push_bind(range.from)
+            .push_bind(range.to)
+            .push_bind(checkpoint.last_end_time);
+    });
+    query.push(match backend {
+        Backend::MySql => " ON DUPLICATE KEY UPDATE last_end_time = VALUES(last_end_time)",
+        Backend::Postgres | Backend::Sqlite => {
+            " ON CONFLICT (dataset, pool, range_start, range_end) DO UPDATE \
+             SET last_end_time = EXCLUDED.last_end_time, updated_at = CURRENT_TIMESTAMP"
+        }
+    });
+
+    query
+}
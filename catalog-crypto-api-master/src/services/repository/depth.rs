@@ -1,52 +1,309 @@
-use crate::core::models::depth_history::DepthInterval;
-use sqlx::MySqlPool;
+use super::{
+    checkpoints::{save_checkpoint_query, BackfillRange, Checkpoint},
+    sql, StoreSummary, BATCH_SIZE,
+};
+use crate::config::connect::{with_pool, Backend, Database};
+use crate::core::models::{
+    common::Dataset,
+    depth_history::{DepthHistoryQueryParams, DepthInterval, MetaStats},
+    pagination::Cursor,
+};
+use chrono::{DateTime, Utc};
+use sqlx::{Encode, QueryBuilder, Type};
+use tracing::debug;
+
+const KEY_COLUMNS: [&str; 3] = ["pool", "start_time", "end_time"];
+const VALUE_COLUMNS: [&str; 10] = [
+    "asset_depth",
+    "asset_price",
+    "asset_price_usd",
+    "liquidity_units",
+    "luvi",
+    "members_count",
+    "rune_depth",
+    "synth_supply",
+    "synth_units",
+    "units",
+];
 
 pub async fn store_intervals(
-    pool: &MySqlPool,
+    db: &Database,
+    intervals: &[DepthInterval],
+) -> Result<StoreSummary, sqlx::Error> {
+    store(db, intervals, None).await
+}
+
+// Stores a backfill page and moves the pool's checkpoint to its end in the same transaction
+pub async fn store_page(
+    db: &Database,
+    intervals: &[DepthInterval],
+) -> Result<StoreSummary, sqlx::Error> {
+    store(db, intervals, page_checkpoint(intervals, None)).await
+}
+
+// Same for a page of the `backfill` command, which keeps its progress per requested range
+pub async fn store_backfill_page(
+    db: &Database,
+    intervals: &[DepthInterval],
+    range: BackfillRange,
+) -> Result<StoreSummary, sqlx::Error> {
+    store(db, intervals, page_checkpoint(intervals, Some(range))).await
+}
+
+fn page_checkpoint(
+    intervals: &[DepthInterval],
+    range: Option<BackfillRange>,
+) -> Option<Checkpoint> {
+    intervals.last().map(|last| Checkpoint {
+        dataset: Dataset::Depth,
+        pool: last.pool.clone(),
+        last_end_time: last.end_time,
+        range,
+    })
+}
+
+async fn store(
+    db: &Database,
     intervals: &[DepthInterval],
-) -> Result<(), sqlx::Error> {
-    for interval in intervals {
-        // Check if record exists
-        let exists = sqlx::query!(
-            r#"
-            SELECT COUNT(*) as count 
-            FROM `depth_intervals` 
-            WHERE start_time = ? AND end_time = ?
-            "#,
-            interval.start_time.naive_utc(),
-            interval.end_time.naive_utc()
-        )
-        .fetch_one(pool)
-        .await?
-        .count
-            > 0;
-
-        if !exists {
-            sqlx::query!(
-                r#"
-                INSERT INTO `depth_intervals` (
-                    start_time, end_time, asset_depth, asset_price,
-                    asset_price_usd, liquidity_units, luvi, members_count,
-                    rune_depth, synth_supply, synth_units, units
-                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
-                "#,
-                interval.start_time.naive_utc(),
-                interval.end_time.naive_utc(),
-                interval.asset_depth as i64,
-                interval.asset_price,
-                interval.asset_price_usd,
-                interval.liquidity_units as i64,
-                interval.luvi,
-                interval.members_count as i32,
-                interval.rune_depth as i64,
-                interval.synth_supply as i64,
-                interval.synth_units as i64,
-                interval.units as i64
-            )
-            .execute(pool)
-            .await?;
+    checkpoint: Option<Checkpoint>,
+) -> Result<StoreSummary, sqlx::Error> {
+    let backend = db.backend();
+    let mut summary = StoreSummary::default();
+
+    with_pool!(db, pool => {
+        let mut tx = pool.begin().await?;
+
+        for batch in intervals.chunks(BATCH_SIZE) {
+            // Mysql reports unchanged duplicates like inserts, so existing rows are counted up front
+            let mut existing = QueryBuilder::new(
+                "SELECT COUNT(*) FROM depth_intervals WHERE (pool, start_time, end_time) IN ",
+            );
+            sql::push_keys(&mut existing, backend, batch, |mut row, interval| {
+                row.push_bind(interval.pool.clone())
+                    .push_bind(interval.start_time)
+                    .push_bind(interval.end_time);
+            });
+            let existing: i64 = existing.build_query_scalar().fetch_one(&mut *tx).await?;
+
+            let mut query = QueryBuilder::new(format!(
+                "INSERT INTO depth_intervals ({}, {}) ",
+                KEY_COLUMNS.join(", "),
+                VALUE_COLUMNS.join(", ")
+            ));
+            query.push_values(batch, |mut row, interval| {
+                row.push_bind(interval.pool.clone())
+                    .push_bind(interval.start_time)
+                    .push_bind(interval.end_time)
+                    .push_bind(interval.asset_depth as i64)
+                    .push_bind(interval.asset_price)
+                    .push_bind(interval.asset_price_usd)
+                    .push_bind(interval.liquidity_units as i64)
+                    .push_bind(interval.luvi)
+                    .push_bind(interval.members_count as i32)
+                    .push_bind(interval.rune_depth as i64)
+                    .push_bind(interval.synth_supply as i64)
+                    .push_bind(interval.synth_units as i64)
+                    .push_bind(interval.units as i64);
+            });
+            query.push(sql::upsert(backend, "depth_intervals", &KEY_COLUMNS, &VALUE_COLUMNS));
+            let affected = query.build().execute(&mut *tx).await?.rows_affected();
+
+            summary += StoreSummary::from_upsert(
+                backend,
+                batch.len() as u64,
+                existing as u64,
+                affected,
+            );
         }
+
+        if let Some(checkpoint) = &checkpoint {
+            save_checkpoint_query(backend, checkpoint)
+                .build()
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+    });
+
+    Ok(summary)
+}
+
+// Every interval the params select, rolled up when asked, before sorting and paging
+fn push_intervals<'a, DB>(
+    query: &mut QueryBuilder<'a, DB>,
+    backend: Backend,
+    params: &DepthHistoryQueryParams,
+    columns: &str,
+) where
+    DB: sqlx::Database,
+    DateTime<Utc>: Encode<'a, DB> + Type<DB>,
+    i64: Encode<'a, DB> + Type<DB>,
+    f64: Encode<'a, DB> + Type<DB>,
+    String: Encode<'a, DB> + Type<DB>,
+{
+    match &params.interval {
+        // Depths are snapshots, so a bucket takes every value from its last hour
+        Some(interval) => {
+            let bucket = sql::bucket(backend, interval, "start_time");
+            debug!("Rolling up by {}", bucket);
+            query.push(format!(
+                "SELECT {columns} FROM (
+                    SELECT d.id, d.pool, b.start_time, b.end_time, d.asset_depth, d.asset_price,
+                        d.asset_price_usd, d.liquidity_units, d.luvi, d.members_count,
+                        d.rune_depth, d.synth_supply, d.synth_units, d.units, d.revision
+                    FROM (
+                        SELECT pool, {bucket} AS start_time, MAX(end_time) AS end_time,
+                            MAX(start_time) AS last_start_time
+                        FROM depth_intervals WHERE 1=1"
+            ));
+            push_filters(query, params);
+            query.push(format!(
+                " GROUP BY pool, {bucket}
+                    ) AS b
+                    JOIN depth_intervals AS d
+                        ON d.pool = b.pool AND d.start_time = b.last_start_time
+                ) AS depth_intervals WHERE 1=1"
+            ));
+        }
+        None => {
+            query.push(format!("SELECT {columns} FROM depth_intervals WHERE 1=1"));
+            push_filters(query, params);
+        }
+    }
+}
+
+pub async fn query_intervals(
+    db: &Database,
+    params: &DepthHistoryQueryParams,
+    limit: u32,
+    offset: u32,
+    cursor: Option<&Cursor>,
+) -> Result<Vec<DepthInterval>, sqlx::Error> {
+    let backend = db.backend();
+    let columns = sql::select_columns(params.projection().unwrap_or_default().as_deref());
+
+    with_pool!(db, pool => {
+        let mut query = QueryBuilder::new("");
+        push_intervals(&mut query, backend, params, &columns);
+        sql::push_page(
+            &mut query,
+            backend,
+            params.get_sort_field(),
+            params.order.as_deref(),
+            cursor,
+            limit,
+            offset,
+        );
+
+        debug!("Executing SQL query: {}", query.sql());
+
+        query.build_query_as::<DepthInterval>().fetch_all(pool).await
+    })
+}
+
+// Same filters and rollup as the pages, so the total lines up with them
+pub async fn count_intervals(
+    db: &Database,
+    params: &DepthHistoryQueryParams,
+) -> Result<u64, sqlx::Error> {
+    let backend = db.backend();
+
+    let total: i64 = with_pool!(db, pool => {
+        let mut query = QueryBuilder::new("SELECT COUNT(*) FROM (");
+        push_intervals(&mut query, backend, params, "*");
+        query.push(") AS matching");
+
+        query.build_query_scalar().fetch_one(pool).await?
+    });
+
+    Ok(total as u64)
+}
+
+// Meta compares the first and last hour of the whole filtered range, so it doesn't move with
+// `page`/`limit`. Like midgard it only means something for a single pool, the handler doesn't ask
+// for it without one
+pub async fn fetch_meta(
+    db: &Database,
+    params: &DepthHistoryQueryParams,
+) -> Result<MetaStats, sqlx::Error> {
+    let first = fetch_edge(db, params, "ASC").await?;
+    let last = fetch_edge(db, params, "DESC").await?;
+
+    Ok(meta_from_edges(&first, &last))
+}
+
+pub(crate) fn meta_from_edges(first: &DepthInterval, last: &DepthInterval) -> MetaStats {
+    let luvi_increase = if first.luvi > 0.0 {
+        last.luvi / first.luvi
+    } else {
+        0.0
+    };
+
+    // Value left after the price moved, relative to just holding: 2 * sqrt(r) / (1 + r)
+    let price_shift_loss = if first.asset_price > 0.0 {
+        let ratio = last.asset_price / first.asset_price;
+        2.0 * ratio.sqrt() / (1.0 + ratio)
+    } else {
+        0.0
+    };
+
+    MetaStats {
+        start_time: first.start_time,
+        end_time: last.end_time,
+        start_asset_depth: first.asset_depth,
+        end_asset_depth: last.asset_depth,
+        start_rune_depth: first.rune_depth,
+        end_rune_depth: last.rune_depth,
+        start_lp_units: first.liquidity_units,
+        end_lp_units: last.liquidity_units,
+        start_member_count: first.members_count,
+        end_member_count: last.members_count,
+        start_synth_units: first.synth_units,
+        end_synth_units: last.synth_units,
+        luvi_increase,
+        price_shift_loss,
+    }
+}
+
+async fn fetch_edge(
+    db: &Database,
+    params: &DepthHistoryQueryParams,
+    order: &str,
+) -> Result<DepthInterval, sqlx::Error> {
+    with_pool!(db, pool => {
+        let mut query = QueryBuilder::new("SELECT * FROM depth_intervals WHERE 1=1");
+        push_filters(&mut query, params);
+        query.push(format!(" ORDER BY start_time {order} LIMIT 1"));
+
+        query.build_query_as::<DepthInterval>().fetch_one(pool).await
+    })
+}
+
+fn push_filters<'a, DB>(query: &mut QueryBuilder<'a, DB>, params: &DepthHistoryQueryParams)
+where
+    DB: sqlx::Database,
+    DateTime<Utc>: Encode<'a, DB> + Type<DB>,
+    String: Encode<'a, DB> + Type<DB>,
+    i64: Encode<'a, DB> + Type<DB>,
+    f64: Encode<'a, DB> + Type<DB>,
+{
+    // Handle date range
+    sql::push_time_range(query, params.time_range().unwrap_or_default());
+
+    // Handle pool filter
+    if let Some(pool_name) = &params.pool {
+        debug!("Pool filter: {}", pool_name);
+        query.push(" AND pool = ");
+        query.push_bind(pool_name.clone());
+    }
+
+    // Handle liquidity filter
+    if let Some(min_liquidity) = params.liquidity_gt {
+        debug!("Liquidity filter: > {}", min_liquidity);
+        query.push(" AND liquidity_units > ");
+        query.push_bind(min_liquidity as i64);
     }
 
-    Ok(())
+    sql::push_column_filters(query, &params.filters().unwrap_or_default());
 }
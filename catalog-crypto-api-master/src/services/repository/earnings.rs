@@ -1,54 +1,492 @@
-use crate::core::models::earnings_history::IntervalData;
-use serde_json;
-use sqlx::MySqlPool;
+use super::{
+    checkpoints::{save_checkpoint_query, BackfillRange, Checkpoint},
+    sql, StoreSummary, BATCH_SIZE,
+};
+use crate::config::connect::{with_pool, Backend, Database};
+use crate::core::models::{
+    common::Dataset,
+    earnings_history::{EarningsHistoryQueryParams, IntervalData, MetaStats, Pool},
+    pagination::Cursor,
+};
+use chrono::{DateTime, Utc};
+use serde_json::Value as JsonValue;
+use sqlx::prelude::FromRow;
+use sqlx::types::Json;
+use sqlx::{Encode, QueryBuilder, Type};
+use tracing::debug;
+
+const KEY_COLUMNS: [&str; 2] = ["start_time", "end_time"];
+const VALUE_COLUMNS: [&str; 8] = [
+    "avg_node_count",
+    "block_rewards",
+    "bonding_earnings",
+    "earnings",
+    "liquidity_earnings",
+    "liquidity_fees",
+    "rune_price_usd",
+    "pools",
+];
+
+// !Just cuz in the models, we have intervalData which contains Vec<Pool> and rust don't know how to deserialize it
+// !So we need to create a new struct to deserialize it (Only solution i found)
+#[derive(Debug, Default, FromRow, Clone)]
+// Columns a `fields` query leaves out are left at their defaults, pools included
+#[sqlx(default)]
+struct EarningIntervalDB {
+    pub avg_node_count: f64,
+    #[sqlx(try_from = "i64")]
+    pub block_rewards: u64,
+    #[sqlx(try_from = "i64")]
+    pub bonding_earnings: u64,
+    #[sqlx(try_from = "i64")]
+    pub earnings: u64,
+    pub end_time: DateTime<Utc>,
+    #[sqlx(try_from = "i64")]
+    pub liquidity_earnings: u64,
+    #[sqlx(try_from = "i64")]
+    pub liquidity_fees: u64,
+    pub rune_price_usd: f64,
+    pub start_time: DateTime<Utc>,
+    pub pools: JsonValue,
+    #[sqlx(default, try_from = "i32")]
+    pub revision: u32,
+    #[sqlx(default)]
+    pub id: i64,
+}
+
+impl TryFrom<EarningIntervalDB> for IntervalData {
+    type Error = sqlx::Error;
+
+    fn try_from(db: EarningIntervalDB) -> Result<Self, Self::Error> {
+        let pools: Vec<Pool> = if db.pools.is_null() {
+            Vec::new()
+        } else {
+            serde_json::from_value(db.pools).map_err(|e| sqlx::Error::Decode(Box::new(e)))?
+        };
+
+        Ok(IntervalData {
+            start_time: db.start_time,
+            end_time: db.end_time,
+            avg_node_count: db.avg_node_count,
+            block_rewards: db.block_rewards,
+            bonding_earnings: db.bonding_earnings,
+            earnings: db.earnings,
+            liquidity_earnings: db.liquidity_earnings,
+            liquidity_fees: db.liquidity_fees,
+            rune_price_usd: db.rune_price_usd,
+            pools,
+            revision: db.revision,
+            id: db.id,
+        })
+    }
+}
 
 pub async fn store_intervals(
-    pool: &MySqlPool,
+    db: &Database,
     intervals: &[IntervalData],
-) -> Result<(), sqlx::Error> {
-    for interval in intervals {
-        // Check if record exists
-        let exists = sqlx::query!(
-            r#"
-            SELECT COUNT(*) as count
-            FROM `earning_intervals`
-            WHERE start_time = ? AND end_time = ?
-            "#,
-            interval.start_time.naive_utc(),
-            interval.end_time.naive_utc()
-        )
-        .fetch_one(pool)
-        .await?
-        .count
-            > 0;
-
-        if !exists {
-            let pools_json = serde_json::to_string(&interval.pools)
-                .map_err(|e| sqlx::Error::Protocol(e.to_string()))?;
-
-            sqlx::query!(
-                r#"
-                INSERT INTO `earning_intervals` (
-                    start_time, end_time, avg_node_count, block_rewards,
-                    bonding_earnings, earnings, liquidity_earnings,
-                    liquidity_fees, rune_price_usd, pools
-                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
-                "#,
-                interval.start_time.naive_utc(),
-                interval.end_time.naive_utc(),
-                interval.avg_node_count,
-                interval.block_rewards as i64,
-                interval.bonding_earnings as i64,
-                interval.earnings as i64,
-                interval.liquidity_earnings as i64,
-                interval.liquidity_fees as i64,
-                interval.rune_price_usd,
-                pools_json,
-            )
-            .execute(pool)
-            .await?;
+) -> Result<StoreSummary, sqlx::Error> {
+    store(db, intervals, None).await
+}
+
+pub async fn store_page(
+    db: &Database,
+    intervals: &[IntervalData],
+) -> Result<StoreSummary, sqlx::Error> {
+    store(db, intervals, page_checkpoint(intervals, None)).await
+}
+
+// Same for a page of the `backfill` command, which keeps its progress per requested range
+pub async fn store_backfill_page(
+    db: &Database,
+    intervals: &[IntervalData],
+    range: BackfillRange,
+) -> Result<StoreSummary, sqlx::Error> {
+    store(db, intervals, page_checkpoint(intervals, Some(range))).await
+}
+
+fn page_checkpoint(intervals: &[IntervalData], range: Option<BackfillRange>) -> Option<Checkpoint> {
+    intervals.last().map(|last| Checkpoint {
+        dataset: Dataset::Earnings,
+        pool: String::new(),
+        last_end_time: last.end_time,
+        range,
+    })
+}
+
+async fn store(
+    db: &Database,
+    intervals: &[IntervalData],
+    checkpoint: Option<Checkpoint>,
+) -> Result<StoreSummary, sqlx::Error> {
+    let backend = db.backend();
+    let mut summary = StoreSummary::default();
+
+    with_pool!(db, pool => {
+        let mut tx = pool.begin().await?;
+
+        for batch in intervals.chunks(BATCH_SIZE) {
+            let mut existing = QueryBuilder::new(
+                "SELECT COUNT(*) FROM earning_intervals WHERE (start_time, end_time) IN ",
+            );
+            sql::push_keys(&mut existing, backend, batch, |mut row, interval| {
+                row.push_bind(interval.start_time)
+                    .push_bind(interval.end_time);
+            });
+            let existing: i64 = existing.build_query_scalar().fetch_one(&mut *tx).await?;
+
+            let mut query = QueryBuilder::new(format!(
+                "INSERT INTO earning_intervals ({}, {}) ",
+                KEY_COLUMNS.join(", "),
+                VALUE_COLUMNS.join(", ")
+            ));
+            query.push_values(batch, |mut row, interval| {
+                row.push_bind(interval.start_time)
+                    .push_bind(interval.end_time)
+                    .push_bind(interval.avg_node_count)
+                    .push_bind(interval.block_rewards as i64)
+                    .push_bind(interval.bonding_earnings as i64)
+                    .push_bind(interval.earnings as i64)
+                    .push_bind(interval.liquidity_earnings as i64)
+                    .push_bind(interval.liquidity_fees as i64)
+                    .push_bind(interval.rune_price_usd)
+                    .push_bind(Json(interval.pools.clone()));
+            });
+            query.push(sql::upsert(backend, "earning_intervals", &KEY_COLUMNS, &VALUE_COLUMNS));
+            let affected = query.build().execute(&mut *tx).await?.rows_affected();
+
+            summary += StoreSummary::from_upsert(
+                backend,
+                batch.len() as u64,
+                existing as u64,
+                affected,
+            );
+        }
+
+        if let Some(checkpoint) = &checkpoint {
+            save_checkpoint_query(backend, checkpoint)
+                .build()
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+    });
+
+    Ok(summary)
+}
+
+// Every interval the params select, rolled up when asked, before sorting and paging
+fn push_intervals<'a, DB>(
+    query: &mut QueryBuilder<'a, DB>,
+    backend: Backend,
+    params: &EarningsHistoryQueryParams,
+    columns: &str,
+) where
+    DB: sqlx::Database,
+    DateTime<Utc>: Encode<'a, DB> + Type<DB>,
+    String: Encode<'a, DB> + Type<DB>,
+    i64: Encode<'a, DB> + Type<DB>,
+    f64: Encode<'a, DB> + Type<DB>,
+{
+    match &params.interval {
+        Some(interval) => {
+            let bucket = sql::bucket(backend, interval, "start_time");
+            debug!("Rolling up by {}", bucket);
+            query.push(format!("SELECT {columns} FROM ("));
+            push_rollup(query, backend, Some(&bucket), params);
+            query.push(") AS earning_intervals WHERE 1=1");
+        }
+        None => {
+            query.push(format!("SELECT {columns} FROM earning_intervals WHERE 1=1"));
+            push_filters(query, backend, params);
+        }
+    }
+}
+
+pub async fn query_intervals(
+    db: &Database,
+    params: &EarningsHistoryQueryParams,
+    limit: u32,
+    offset: u32,
+    cursor: Option<&Cursor>,
+) -> Result<Vec<IntervalData>, sqlx::Error> {
+    let backend = db.backend();
+    let columns = sql::select_columns(params.projection().unwrap_or_default().as_deref());
+
+    let rows = with_pool!(db, pool => {
+        let mut query = QueryBuilder::new("");
+        push_intervals(&mut query, backend, params, &columns);
+        sql::push_page(
+            &mut query,
+            backend,
+            params.get_sort_field(),
+            params.order.as_deref(),
+            cursor,
+            limit,
+            offset,
+        );
+
+        debug!("Executing query: {}", query.sql());
+
+        query
+            .build_query_as::<EarningIntervalDB>()
+            .fetch_all(pool)
+            .await?
+    });
+
+    rows.into_iter().map(IntervalData::try_from).collect()
+}
+
+pub async fn count_intervals(
+    db: &Database,
+    params: &EarningsHistoryQueryParams,
+) -> Result<u64, sqlx::Error> {
+    let backend = db.backend();
+
+    let total: i64 = with_pool!(db, pool => {
+        let mut query = QueryBuilder::new("SELECT COUNT(*) FROM (");
+        push_intervals(&mut query, backend, params, "*");
+        query.push(") AS matching");
+
+        query.build_query_scalar().fetch_one(pool).await?
+    });
+
+    Ok(total as u64)
+}
+
+// Meta covers the whole filtered range, so it doesn't move with `page`/`limit`
+pub async fn fetch_meta(
+    db: &Database,
+    params: &EarningsHistoryQueryParams,
+) -> Result<MetaStats, sqlx::Error> {
+    let backend = db.backend();
+
+    let totals = with_pool!(db, pool => {
+        let mut query = QueryBuilder::new("");
+        push_rollup(&mut query, backend, None, params);
+
+        query
+            .build_query_as::<EarningIntervalDB>()
+            .fetch_one(pool)
+            .await?
+    });
+
+    Ok(MetaStats {
+        avg_node_count: totals.avg_node_count,
+        block_rewards: totals.block_rewards,
+        bonding_earnings: totals.bonding_earnings,
+        earnings: totals.earnings,
+        end_time: totals.end_time,
+        liquidity_earnings: totals.liquidity_earnings,
+        liquidity_fees: totals.liquidity_fees,
+        pools: serde_json::from_value(totals.pools).unwrap_or_default(),
+        rune_price_usd: totals.rune_price_usd,
+        start_time: totals.start_time,
+    })
+}
+
+// Totals are summed, node count and rune price are averaged over time and the per-pool
+// earnings are unpacked from the json column and summed per pool. Without a bucket the whole
+// filtered range collapses into a single row, which is what the meta is built from
+fn push_rollup<'a, DB>(
+    query: &mut QueryBuilder<'a, DB>,
+    backend: Backend,
+    bucket: Option<&str>,
+    params: &EarningsHistoryQueryParams,
+) where
+    DB: sqlx::Database,
+    DateTime<Utc>: Encode<'a, DB> + Type<DB>,
+    String: Encode<'a, DB> + Type<DB>,
+    i64: Encode<'a, DB> + Type<DB>,
+    f64: Encode<'a, DB> + Type<DB>,
+{
+    let (start_time, group_by) = match bucket {
+        Some(bucket) => (bucket.to_string(), format!(" GROUP BY {bucket}")),
+        None => ("MIN(start_time)".to_string(), String::new()),
+    };
+    let (pool_bucket, pool_group_by, join_on) = match bucket {
+        Some(bucket) => (
+            bucket.to_string(),
+            format!(" GROUP BY {bucket}, pool_asset"),
+            "p.bucket_start = t.start_time",
+        ),
+        None => (
+            "NULL".to_string(),
+            " GROUP BY pool_asset".to_string(),
+            "TRUE",
+        ),
+    };
+    let sums = [
+        "block_rewards",
+        "bonding_earnings",
+        "earnings",
+        "liquidity_earnings",
+        "liquidity_fees",
+    ]
+    .iter()
+    .map(|column| format!("{} AS {column}", sql::sum_int(backend, column)))
+    .collect::<Vec<_>>()
+    .join(", ");
+    let avg_node_count = sql::time_weighted_avg(backend, "avg_node_count");
+    let rune_price_usd = sql::time_weighted_avg(backend, "rune_price_usd");
+    let empty_pools = match backend {
+        Backend::MySql => "JSON_ARRAY()",
+        Backend::Postgres => "'[]'::jsonb",
+        Backend::Sqlite => "'[]'",
+    };
+
+    query.push(format!(
+        "SELECT t.*, COALESCE(p.pools, {empty_pools}) AS pools
+        FROM (
+            SELECT {start_time} AS start_time, MAX(end_time) AS end_time,
+                {avg_node_count} AS avg_node_count, {sums},
+                {rune_price_usd} AS rune_price_usd,
+                MAX(revision) AS revision, MAX(id) AS id
+            FROM earning_intervals WHERE 1=1"
+    ));
+    push_filters(query, backend, params);
+    query.push(format!("{group_by}\n        ) AS t\n        LEFT JOIN ("));
+
+    match backend {
+        Backend::MySql => query.push(format!(
+            "SELECT bucket_start, JSON_ARRAYAGG(JSON_OBJECT(
+                'assetLiquidityFees', CAST(asset_liquidity_fees AS CHAR),
+                'earnings', CAST(pool_earnings AS CHAR),
+                'pool', pool_asset,
+                'rewards', CAST(rewards AS CHAR),
+                'runeLiquidityFees', CAST(rune_liquidity_fees AS CHAR),
+                'saverEarning', CAST(saver_earning AS CHAR),
+                'totalLiquidityFeesRune', CAST(total_liquidity_fees_rune AS CHAR)
+            )) AS pools
+            FROM (
+                SELECT {pool_bucket} AS bucket_start, jt.pool_asset,
+                    SUM(jt.pool_asset_liquidity_fees) AS asset_liquidity_fees,
+                    SUM(jt.pool_earnings) AS pool_earnings,
+                    SUM(jt.pool_rewards) AS rewards,
+                    SUM(jt.pool_rune_liquidity_fees) AS rune_liquidity_fees,
+                    SUM(jt.pool_saver_earning) AS saver_earning,
+                    SUM(jt.pool_total_liquidity_fees_rune) AS total_liquidity_fees_rune
+                FROM earning_intervals,
+                JSON_TABLE(pools, '$[*]' COLUMNS (
+                    pool_asset VARCHAR(64) PATH '$.pool',
+                    pool_asset_liquidity_fees DECIMAL(30, 0) PATH '$.assetLiquidityFees',
+                    pool_earnings DECIMAL(30, 0) PATH '$.earnings',
+                    pool_rewards DECIMAL(30, 0) PATH '$.rewards',
+                    pool_rune_liquidity_fees DECIMAL(30, 0) PATH '$.runeLiquidityFees',
+                    pool_saver_earning DECIMAL(30, 0) PATH '$.saverEarning',
+                    pool_total_liquidity_fees_rune DECIMAL(30, 0) PATH '$.totalLiquidityFeesRune'
+                )) AS jt
+                WHERE 1=1"
+        )),
+        // The amounts are kept as strings inside the json, numeric sums them without overflowing
+        Backend::Postgres => query.push(format!(
+            "SELECT bucket_start, jsonb_agg(jsonb_build_object(
+                'assetLiquidityFees', asset_liquidity_fees::text,
+                'earnings', pool_earnings::text,
+                'pool', pool_asset,
+                'rewards', rewards::text,
+                'runeLiquidityFees', rune_liquidity_fees::text,
+                'saverEarning', saver_earning::text,
+                'totalLiquidityFeesRune', total_liquidity_fees_rune::text
+            )) AS pools
+            FROM (
+                SELECT {pool_bucket} AS bucket_start, e.value->>'pool' AS pool_asset,
+                    SUM((e.value->>'assetLiquidityFees')::numeric) AS asset_liquidity_fees,
+                    SUM((e.value->>'earnings')::numeric) AS pool_earnings,
+                    SUM((e.value->>'rewards')::numeric) AS rewards,
+                    SUM((e.value->>'runeLiquidityFees')::numeric) AS rune_liquidity_fees,
+                    SUM((e.value->>'saverEarning')::numeric) AS saver_earning,
+                    SUM((e.value->>'totalLiquidityFeesRune')::numeric) AS total_liquidity_fees_rune
+                FROM earning_intervals, jsonb_array_elements(pools) AS e
+                WHERE 1=1"
+        )),
+        Backend::Sqlite => query.push(format!(
+            "SELECT bucket_start, json_group_array(json_object(
+                'assetLiquidityFees', CAST(asset_liquidity_fees AS TEXT),
+                'earnings', CAST(pool_earnings AS TEXT),
+                'pool', pool_asset,
+                'rewards', CAST(rewards AS TEXT),
+                'runeLiquidityFees', CAST(rune_liquidity_fees AS TEXT),
+                'saverEarning', CAST(saver_earning AS TEXT),
+                'totalLiquidityFeesRune', CAST(total_liquidity_fees_rune AS TEXT)
+            )) AS pools
+            FROM (
+                SELECT {pool_bucket} AS bucket_start,
+                    json_extract(e.value, '$.pool') AS pool_asset,
+                    SUM(CAST(json_extract(e.value, '$.assetLiquidityFees') AS INTEGER)) AS asset_liquidity_fees,
+                    SUM(CAST(json_extract(e.value, '$.earnings') AS INTEGER)) AS pool_earnings,
+                    SUM(CAST(json_extract(e.value, '$.rewards') AS INTEGER)) AS rewards,
+                    SUM(CAST(json_extract(e.value, '$.runeLiquidityFees') AS INTEGER)) AS rune_liquidity_fees,
+                    SUM(CAST(json_extract(e.value, '$.saverEarning') AS INTEGER)) AS saver_earning,
+                    SUM(CAST(json_extract(e.value, '$.totalLiquidityFeesRune') AS INTEGER)) AS total_liquidity_fees_rune
+                FROM earning_intervals, json_each(earning_intervals.pools) AS e
+                WHERE 1=1"
+        )),
+    };
+    push_filters(query, backend, params);
+    query.push(format!(
+        "{pool_group_by}
+            ) AS per_pool
+            GROUP BY bucket_start
+        ) AS p ON {join_on}"
+    ));
+}
+
+fn push_filters<'a, DB>(
+    query: &mut QueryBuilder<'a, DB>,
+    backend: Backend,
+    params: &EarningsHistoryQueryParams,
+) where
+    DB: sqlx::Database,
+    DateTime<Utc>: Encode<'a, DB> + Type<DB>,
+    String: Encode<'a, DB> + Type<DB>,
+    i64: Encode<'a, DB> + Type<DB>,
+    f64: Encode<'a, DB> + Type<DB>,
+{
+    sql::push_time_range(query, params.time_range().unwrap_or_default());
+
+    if let Some(min_earnings) = params.earnings_gt {
+        debug!("Earnings filter: > {}", min_earnings);
+        query
+            .push(" AND earnings > ")
+            .push_bind(min_earnings as i64);
+    }
+
+    if let Some(min_rewards) = params.block_rewards_gt {
+        debug!("Block rewards filter: > {}", min_rewards);
+        query
+            .push(" AND block_rewards > ")
+            .push_bind(min_rewards as i64);
+    }
+
+    if let Some(min_nodes) = params.node_count_gt {
+        debug!("Node count filter: > {}", min_nodes);
+        query.push(" AND avg_node_count > ").push_bind(min_nodes);
+    }
+
+    if let Some(pool_name) = &params.pool {
+        debug!("Pool filter: {}", pool_name);
+        match backend {
+            Backend::MySql => {
+                query.push(" AND JSON_CONTAINS(pools, JSON_ARRAY(JSON_OBJECT('pool', ");
+                query.push_bind(pool_name.clone());
+                query.push(")))");
+            }
+            Backend::Postgres => {
+                query.push(" AND pools @> jsonb_build_array(jsonb_build_object('pool', ");
+                query.push_bind(pool_name.clone());
+                query.push("::text))");
+            }
+            Backend::Sqlite => {
+                query.push(
+                    " AND EXISTS (SELECT 1 FROM json_each(earning_intervals.pools) AS f \
+                     WHERE json_extract(f.value, '$.pool') = ",
+                );
+                query.push_bind(pool_name.clone());
+                query.push(")");
+            }
         }
     }
 
-    Ok(())
+    sql::push_column_filters(query, &params.filters().unwrap_or_default());
 }
@@ -0,0 +1,60 @@
+use super::dataset_table;
+use crate::config::connect::{with_pool, Database};
+use crate::core::models::{
+    common::Dataset,
+    gaps::{Gap, GapKind},
+};
+use chrono::{DateTime, Utc};
+use sqlx::QueryBuilder;
+
+// Compares every interval with the one before it in the same series (per pool for depth
+// history). Anything but the previous end being the next start is a hole or an overlap
+pub async fn find_gaps(
+    db: &Database,
+    dataset: Dataset,
+    pool_name: Option<&str>,
+) -> Result<Vec<Gap>, sqlx::Error> {
+    let per_pool = dataset == Dataset::Depth;
+
+    let rows: Vec<(String, DateTime<Utc>, DateTime<Utc>)> = with_pool!(db, pool => {
+        let mut query = QueryBuilder::new(format!(
+            "SELECT pool, previous_end, start_time FROM (\
+             SELECT {pool}, start_time, \
+             LAG(end_time) OVER ({partition}ORDER BY start_time) AS previous_end \
+             FROM {table}",
+            pool = if per_pool { "pool" } else { "'' AS pool" },
+            partition = if per_pool { "PARTITION BY pool " } else { "" },
+            table = dataset_table(dataset),
+        ));
+        if let Some(pool_name) = pool_name.filter(|_| per_pool) {
+            query.push(" WHERE pool = ").push_bind(pool_name.to_string());
+        }
+        query.push(
+            ") AS series WHERE previous_end <> start_time ORDER BY pool, start_time",
+        );
+
+        query.build_query_as().fetch_all(pool).await
+    })?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(pool, previous_end, start_time)| {
+            let pool = Some(pool).filter(|_| per_pool);
+            if previous_end < start_time {
+                Gap {
+                    pool,
+                    kind: GapKind::Missing,
+                    start_time: previous_end,
+                    end_time: start_time,
+                }
+            } else {
+                Gap {
+                    pool,
+                    kind: GapKind::Overlap,
+                    start_time,
+                    end_time: previous_end,
+                }
+            }
+        })
+        .collect())
+}
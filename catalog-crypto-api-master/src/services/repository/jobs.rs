@@ -0,0 +1,195 @@
+use crate::config::connect::{with_pool, Backend, Database};
+use crate::core::models::jobs::JobState;
+use chrono::{DateTime, Utc};
+use sqlx::{Encode, QueryBuilder, Type};
+
+// paused, run_requested_at, rewind_to, last_started_at, last_finished_at, last_error
+type JobRow = (
+    bool,
+    Option<DateTime<Utc>>,
+    Option<DateTime<Utc>>,
+    Option<DateTime<Utc>>,
+    Option<DateTime<Utc>>,
+    Option<String>,
+);
+
+pub async fn get_job_state(db: &Database, name: &str) -> Result<JobState, sqlx::Error> {
+    let row: Option<JobRow> = with_pool!(db, pool => {
+        let mut query = QueryBuilder::new(
+            "SELECT paused, run_requested_at, rewind_to, last_started_at, last_finished_at, \
+             last_error FROM scheduled_jobs WHERE name = ",
+        );
+        query.push_bind(name.to_string());
+
+        query.build_query_as().fetch_optional(pool).await
+    })?;
+
+    Ok(row
+        .map(
+            |(
+                paused,
+                run_requested_at,
+                rewind_to,
+                last_started_at,
+                last_finished_at,
+                last_error,
+            )| {
+                JobState {
+                    paused,
+                    run_requested_at,
+                    rewind_to,
+                    last_started_at,
+                    last_finished_at,
+                    last_error,
+                }
+            },
+        )
+        .unwrap_or_default())
+}
+
+// Sets one column of the job's row, creating the row the first time
+fn upsert_query<'a, DB, T>(
+    backend: Backend,
+    name: &str,
+    column: &str,
+    value: T,
+) -> QueryBuilder<'a, DB>
+where
+    DB: sqlx::Database,
+    String: Encode<'a, DB> + Type<DB>,
+    T: Encode<'a, DB> + Type<DB> + 'a,
+{
+    let mut query = QueryBuilder::new(format!("INSERT INTO scheduled_jobs (name, {column}) "));
+    query.push_values([(name.to_string(), value)], |mut row, (name, value)| {
+        row.push_bind(name).push_bind(value);
+    });
+    query.push(match backend {
+        Backend::MySql => format!(" ON DUPLICATE KEY UPDATE {column} = VALUES({column})"),
+        Backend::Postgres | Backend::Sqlite => {
+            format!(" ON CONFLICT (name) DO UPDATE SET {column} = EXCLUDED.{column}")
+        }
+    });
+
+    query
+}
+
+pub async fn set_paused(db: &Database, name: &str, paused: bool) -> Result<(), sqlx::Error> {
+    with_pool!(db, pool => {
+        upsert_query(db.backend(), name, "paused", paused)
+            .build()
+            .execute(pool)
+            .await?;
+    });
+
+    Ok(())
+}
+
+pub async fn request_run(db: &Database, name: &str, at: DateTime<Utc>) -> Result<(), sqlx::Error> {
+    with_pool!(db, pool => {
+        upsert_query(db.backend(), name, "run_requested_at", at)
+            .build()
+            .execute(pool)
+            .await?;
+    });
+
+    Ok(())
+}
+
+// The dataset's cron checkpoints go back with it. The catch-up moves them forward as it stores,
+// so a run that gets interrupted is resumed from there instead of starting over
+pub async fn set_rewind(db: &Database, name: &str, to: DateTime<Utc>) -> Result<(), sqlx::Error> {
+    with_pool!(db, pool => {
+        let mut tx = pool.begin().await?;
+
+        upsert_query(db.backend(), name, "rewind_to", to)
+            .build()
+            .execute(&mut *tx)
+            .await?;
+
+        let mut checkpoints = QueryBuilder::new("UPDATE ingestion_checkpoints SET last_end_time = ");
+        checkpoints
+            .push_bind(to)
+            .push(" WHERE dataset = ")
+            .push_bind(name.to_string());
+        checkpoints.build().execute(&mut *tx).await?;
+
+        tx.commit().await?;
+    });
+
+    Ok(())
+}
+
+// Only clears what was handled, a request or rewind that came in meanwhile is kept for the
+// next run
+async fn clear_if_unchanged(
+    db: &Database,
+    name: &str,
+    column: &str,
+    handled: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    with_pool!(db, pool => {
+        let mut query = QueryBuilder::new(format!(
+            "UPDATE scheduled_jobs SET {column} = NULL WHERE name = "
+        ));
+        query
+            .push_bind(name.to_string())
+            .push(format!(" AND {column} = "))
+            .push_bind(handled);
+
+        query.build().execute(pool).await?;
+    });
+
+    Ok(())
+}
+
+pub async fn clear_run_request(
+    db: &Database,
+    name: &str,
+    requested_at: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    clear_if_unchanged(db, name, "run_requested_at", requested_at).await
+}
+
+pub async fn clear_rewind(
+    db: &Database,
+    name: &str,
+    rewind_to: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    clear_if_unchanged(db, name, "rewind_to", rewind_to).await
+}
+
+pub async fn record_started(
+    db: &Database,
+    name: &str,
+    at: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    with_pool!(db, pool => {
+        upsert_query(db.backend(), name, "last_started_at", at)
+            .build()
+            .execute(pool)
+            .await?;
+    });
+
+    Ok(())
+}
+
+pub async fn record_finished(
+    db: &Database,
+    name: &str,
+    at: DateTime<Utc>,
+    error: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    with_pool!(db, pool => {
+        let mut query = QueryBuilder::new("UPDATE scheduled_jobs SET last_finished_at = ");
+        query
+            .push_bind(at)
+            .push(", last_error = ")
+            .push_bind(error.map(str::to_string))
+            .push(" WHERE name = ")
+            .push_bind(name.to_string());
+
+        query.build().execute(pool).await?;
+    });
+
+    Ok(())
+}
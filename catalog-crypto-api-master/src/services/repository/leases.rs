@@ -0,0 +1,84 @@
+use crate::config::connect::{with_pool, Backend, Database};
+use chrono::{DateTime, Utc};
+use sqlx::QueryBuilder;
+
+// Takes the lease or extends it when `holder` already has it. The row is created already expired
+// the first time, after that a single conditional UPDATE decides, so two instances racing for an
+// expired lease can't both get it. Expiry is compared against the caller's clock, replicas are
+// expected to keep theirs in sync
+pub async fn try_acquire_lease(
+    db: &Database,
+    name: &str,
+    holder: &str,
+    expires_at: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> Result<bool, sqlx::Error> {
+    let affected = with_pool!(db, pool => {
+        let mut insert = QueryBuilder::new(match db.backend() {
+            Backend::MySql => "INSERT IGNORE INTO job_leases (name, expires_at) VALUES (",
+            Backend::Postgres | Backend::Sqlite => "INSERT INTO job_leases (name, expires_at) VALUES (",
+        });
+        insert
+            .push_bind(name.to_string())
+            .push(", ")
+            .push_bind(now)
+            .push(")");
+        if db.backend() != Backend::MySql {
+            insert.push(" ON CONFLICT (name) DO NOTHING");
+        }
+        insert.build().execute(pool).await?;
+
+        let mut update = QueryBuilder::new("UPDATE job_leases SET holder = ");
+        update
+            .push_bind(holder.to_string())
+            .push(", expires_at = ")
+            .push_bind(expires_at)
+            .push(", renewed_at = ")
+            .push_bind(now)
+            .push(" WHERE name = ")
+            .push_bind(name.to_string())
+            .push(" AND (holder = ")
+            .push_bind(holder.to_string())
+            .push(" OR expires_at <= ")
+            .push_bind(now)
+            .push(")");
+        update.build().execute(pool).await?.rows_affected()
+    });
+
+    Ok(affected == 1)
+}
+
+// Lets the lease run out right away so another instance doesn't have to wait for the expiry
+pub async fn release_lease(
+    db: &Database,
+    name: &str,
+    holder: &str,
+    now: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    with_pool!(db, pool => {
+        let mut query = QueryBuilder::new("UPDATE job_leases SET expires_at = ");
+        query
+            .push_bind(now)
+            .push(" WHERE name = ")
+            .push_bind(name.to_string())
+            .push(" AND holder = ")
+            .push_bind(holder.to_string());
+
+        query.build().execute(pool).await?;
+    });
+
+    Ok(())
+}
+
+// Who holds the lease and until when, none before anyone asked for it
+pub async fn get_lease(
+    db: &Database,
+    name: &str,
+) -> Result<Option<(String, DateTime<Utc>)>, sqlx::Error> {
+    with_pool!(db, pool => {
+        let mut query = QueryBuilder::new("SELECT holder, expires_at FROM job_leases WHERE name = ");
+        query.push_bind(name.to_string());
+
+        query.build_query_as().fetch_optional(pool).await
+    })
+}
@@ -0,0 +1,229 @@
+use super::traits::{GapRepository, JobRepository, PoolRepository, StatusRepository};
+use crate::core::models::{
+    common::Dataset,
+    depth_history::DepthInterval,
+    gaps::{Gap, GapKind},
+    jobs::JobState,
+    pools::{PoolDetail, PoolRecord},
+    runepool_units_history::RunepoolUnitsInterval,
+    status::{DatasetStatus, IngestionState},
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+// Backs the pools, gaps, status and jobs handlers in their tests. The history handlers are tested
+// against sqlite instead, so only the intervals those handlers look at can be seeded here and the
+// other datasets read as empty
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    depth: RwLock<Vec<DepthInterval>>,
+    runepool: RwLock<Vec<RunepoolUnitsInterval>>,
+    pools: RwLock<Vec<PoolRecord>>,
+    ingestion: RwLock<HashMap<Dataset, IngestionState>>,
+    jobs: RwLock<HashMap<String, JobState>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_depth(&self, intervals: &[DepthInterval]) {
+        self.depth.write().unwrap().extend_from_slice(intervals);
+    }
+
+    pub fn add_runepool(&self, intervals: &[RunepoolUnitsInterval]) {
+        self.runepool.write().unwrap().extend_from_slice(intervals);
+    }
+
+    // Stands in for the hourly fetcher recording how its fetches went
+    pub fn set_ingestion_state(&self, dataset: Dataset, state: IngestionState) {
+        self.ingestion.write().unwrap().insert(dataset, state);
+    }
+
+    fn update_job(&self, name: &str, update: impl FnOnce(&mut JobState)) {
+        update(
+            self.jobs
+                .write()
+                .unwrap()
+                .entry(name.to_string())
+                .or_default(),
+        );
+    }
+}
+
+#[async_trait]
+impl PoolRepository for MemoryStore {
+    async fn list_pools(&self, status: Option<&str>) -> Result<Vec<PoolRecord>, sqlx::Error> {
+        let mut pools: Vec<PoolRecord> = self
+            .pools
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|pool| status.is_none_or(|status| pool.status == status))
+            .cloned()
+            .collect();
+        pools.sort_by(|a, b| a.asset.cmp(&b.asset));
+        Ok(pools)
+    }
+
+    async fn get_pool(&self, asset: &str) -> Result<Option<PoolRecord>, sqlx::Error> {
+        let pools = self.pools.read().unwrap();
+        Ok(pools.iter().find(|pool| pool.asset == asset).cloned())
+    }
+
+    async fn is_known_pool(&self, asset: &str) -> Result<bool, sqlx::Error> {
+        let pools = self.pools.read().unwrap();
+        Ok(pools.is_empty() || pools.iter().any(|pool| pool.asset == asset))
+    }
+
+    async fn store_pools(&self, details: &[PoolDetail]) -> Result<(), sqlx::Error> {
+        let now = Utc::now();
+        let mut pools = self.pools.write().unwrap();
+
+        for detail in details {
+            match pools.iter_mut().find(|pool| pool.asset == detail.asset) {
+                Some(pool) => {
+                    pool.status = detail.status.clone();
+                    pool.decimals = detail.decimals();
+                    pool.last_synced = now;
+                }
+                None => pools.push(PoolRecord {
+                    asset: detail.asset.clone(),
+                    status: detail.status.clone(),
+                    decimals: detail.decimals(),
+                    first_seen: now,
+                    last_synced: now,
+                }),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl GapRepository for MemoryStore {
+    async fn find_gaps(
+        &self,
+        dataset: Dataset,
+        pool: Option<&str>,
+    ) -> Result<Vec<Gap>, sqlx::Error> {
+        let mut series: Vec<(String, DateTime<Utc>, DateTime<Utc>)> = match dataset {
+            Dataset::Depth => self
+                .depth
+                .read()
+                .unwrap()
+                .iter()
+                .filter(|interval| pool.is_none_or(|pool| interval.pool == pool))
+                .map(|interval| {
+                    (
+                        interval.pool.clone(),
+                        interval.start_time,
+                        interval.end_time,
+                    )
+                })
+                .collect(),
+            Dataset::Earnings | Dataset::Swap => Vec::new(),
+            Dataset::Runepool => self
+                .runepool
+                .read()
+                .unwrap()
+                .iter()
+                .map(|interval| (String::new(), interval.start_time, interval.end_time))
+                .collect(),
+        };
+        series.sort();
+
+        Ok(series
+            .windows(2)
+            .filter(|pair| pair[0].0 == pair[1].0 && pair[0].2 != pair[1].1)
+            .map(|pair| {
+                let (pool, previous_end, start_time) = (&pair[1].0, pair[0].2, pair[1].1);
+                let pool = Some(pool.clone()).filter(|_| dataset == Dataset::Depth);
+                if previous_end < start_time {
+                    Gap {
+                        pool,
+                        kind: GapKind::Missing,
+                        start_time: previous_end,
+                        end_time: start_time,
+                    }
+                } else {
+                    Gap {
+                        pool,
+                        kind: GapKind::Overlap,
+                        start_time,
+                        end_time: previous_end,
+                    }
+                }
+            })
+            .collect())
+    }
+}
+
+#[async_trait]
+impl StatusRepository for MemoryStore {
+    async fn dataset_status(&self, dataset: Dataset) -> Result<DatasetStatus, sqlx::Error> {
+        let end_times: Vec<DateTime<Utc>> = match dataset {
+            Dataset::Depth => self
+                .depth
+                .read()
+                .unwrap()
+                .iter()
+                .map(|row| row.end_time)
+                .collect(),
+            Dataset::Earnings | Dataset::Swap => Vec::new(),
+            Dataset::Runepool => self
+                .runepool
+                .read()
+                .unwrap()
+                .iter()
+                .map(|row| row.end_time)
+                .collect(),
+        };
+        let state = self
+            .ingestion
+            .read()
+            .unwrap()
+            .get(&dataset)
+            .cloned()
+            .unwrap_or_default();
+
+        Ok(DatasetStatus::new(
+            dataset,
+            end_times.iter().max().copied(),
+            end_times.len() as i64,
+            state,
+        ))
+    }
+}
+
+#[async_trait]
+impl JobRepository for MemoryStore {
+    async fn job_state(&self, name: &str) -> Result<JobState, sqlx::Error> {
+        Ok(self
+            .jobs
+            .read()
+            .unwrap()
+            .get(name)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn set_paused(&self, name: &str, paused: bool) -> Result<(), sqlx::Error> {
+        self.update_job(name, |job| job.paused = paused);
+        Ok(())
+    }
+
+    async fn request_run(&self, name: &str, at: DateTime<Utc>) -> Result<(), sqlx::Error> {
+        self.update_job(name, |job| job.run_requested_at = Some(at));
+        Ok(())
+    }
+
+    async fn set_rewind(&self, name: &str, to: DateTime<Utc>) -> Result<(), sqlx::Error> {
+        self.update_job(name, |job| job.rewind_to = Some(to));
+        Ok(())
+    }
+}
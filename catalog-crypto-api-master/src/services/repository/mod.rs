@@ -1,4 +1,73 @@
+pub mod checkpoints;
 pub mod depth;
 pub mod earnings;
+pub mod gaps;
+pub mod jobs;
+pub mod leases;
+#[cfg(test)]
+pub mod memory;
+pub mod pools;
 pub mod runepool;
+pub mod sql;
+pub mod status;
 pub mod swap;
+pub mod traits;
+
+use crate::config::connect::Backend;
+use crate::core::models::common::Dataset;
+
+// Rows per INSERT, keeps the widest table (swaps) well under mysql's 65535 placeholders
+pub(crate) const BATCH_SIZE: usize = 500;
+
+pub(crate) fn dataset_table(dataset: Dataset) -> &'static str {
+    match dataset {
+        Dataset::Depth => "depth_intervals",
+        Dataset::Earnings => "earning_intervals",
+        Dataset::Swap => "swap_intervals",
+        Dataset::Runepool => "runepool_unit_intervals",
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StoreSummary {
+    pub inserted: u64,
+    pub updated: u64,
+    pub unchanged: u64,
+}
+
+impl StoreSummary {
+    // sqlx connects to mysql with CLIENT_FOUND_ROWS, so an upsert counts 1 per inserted or
+    // unchanged row and 2 per changed one. Postgres and sqlite skip unchanged rows and count 1 for the rest
+    pub(crate) fn from_upsert(backend: Backend, rows: u64, existing: u64, affected: u64) -> Self {
+        let changed = match backend {
+            Backend::MySql => affected.saturating_sub(rows),
+            Backend::Postgres | Backend::Sqlite => {
+                affected.saturating_sub(rows.saturating_sub(existing))
+            }
+        };
+        let updated = changed.min(existing);
+        Self {
+            inserted: rows.saturating_sub(existing),
+            updated,
+            unchanged: existing - updated,
+        }
+    }
+}
+
+impl std::ops::AddAssign for StoreSummary {
+    fn add_assign(&mut self, other: Self) {
+        self.inserted += other.inserted;
+        self.updated += other.updated;
+        self.unchanged += other.unchanged;
+    }
+}
+
+impl std::fmt::Display for StoreSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} inserted, {} updated, {} unchanged",
+            self.inserted, self.updated, self.unchanged
+        )
+    }
+}
@@ -0,0 +1,136 @@
+use crate::config::connect::{with_pool, Backend, Database};
+use crate::core::models::pools::{PoolDetail, PoolRecord};
+use crate::services::client::get_depth_pools;
+use chrono::Utc;
+use sqlx::QueryBuilder;
+
+pub async fn store_pools(db: &Database, pools: &[PoolDetail]) -> Result<(), sqlx::Error> {
+    let now = Utc::now();
+    let backend = db.backend();
+
+    if pools.is_empty() {
+        return Ok(());
+    }
+
+    with_pool!(db, pool => {
+        let mut query = QueryBuilder::new(
+            "INSERT INTO pools (asset, status, decimals, first_seen, last_synced) ",
+        );
+        query.push_values(pools, |mut row, detail| {
+            row.push_bind(detail.asset.clone())
+                .push_bind(detail.status.clone())
+                .push_bind(detail.decimals())
+                .push_bind(now)
+                .push_bind(now);
+        });
+        // first_seen is only written once, later syncs just refresh the status
+        query.push(match backend {
+            Backend::MySql => {
+                " ON DUPLICATE KEY UPDATE status = VALUES(status), decimals = VALUES(decimals), \
+                 last_synced = VALUES(last_synced)"
+            }
+            Backend::Postgres | Backend::Sqlite => {
+                " ON CONFLICT (asset) DO UPDATE SET status = EXCLUDED.status, \
+                 decimals = EXCLUDED.decimals, last_synced = EXCLUDED.last_synced"
+            }
+        });
+
+        query.build().execute(pool).await?;
+    });
+
+    Ok(())
+}
+
+pub async fn list_pools(
+    db: &Database,
+    status: Option<&str>,
+) -> Result<Vec<PoolRecord>, sqlx::Error> {
+    with_pool!(db, pool => {
+        let mut query = QueryBuilder::new(
+            "SELECT asset, status, decimals, first_seen, last_synced FROM pools WHERE 1=1",
+        );
+
+        if let Some(status) = status {
+            query.push(" AND status = ").push_bind(status.to_string());
+        }
+
+        query.push(" ORDER BY asset ASC");
+
+        query.build_query_as::<PoolRecord>().fetch_all(pool).await
+    })
+}
+
+pub async fn get_pool(db: &Database, asset: &str) -> Result<Option<PoolRecord>, sqlx::Error> {
+    with_pool!(db, pool => {
+        let mut query = QueryBuilder::new(
+            "SELECT asset, status, decimals, first_seen, last_synced FROM pools WHERE asset = ",
+        );
+        query.push_bind(asset.to_string());
+
+        query.build_query_as::<PoolRecord>().fetch_optional(pool).await
+    })
+}
+
+pub async fn get_pool_names(
+    db: &Database,
+    status: Option<&str>,
+) -> Result<Vec<String>, sqlx::Error> {
+    with_pool!(db, pool => {
+        let mut query = QueryBuilder::new("SELECT asset FROM pools WHERE 1=1");
+
+        if let Some(status) = status {
+            query.push(" AND status = ").push_bind(status.to_string());
+        }
+
+        query.push(" ORDER BY asset ASC");
+
+        query.build_query_scalar::<String>().fetch_all(pool).await
+    })
+}
+
+// An empty registry means the pools cron didn't run yet, so there is nothing to validate against
+pub async fn is_known_pool(db: &Database, asset: &str) -> Result<bool, sqlx::Error> {
+    let (known, populated): (i64, i64) = with_pool!(db, pool => {
+        let mut query = QueryBuilder::new(
+            "SELECT (SELECT COUNT(*) FROM pools WHERE asset = ",
+        );
+        query
+            .push_bind(asset.to_string())
+            .push("), (SELECT COUNT(*) FROM pools)");
+
+        query.build_query_as().fetch_one(pool).await?
+    });
+
+    Ok(known > 0 || populated == 0)
+}
+
+// Pools to ingest depth history for: the configured list (checked against the registry) or
+// every available pool in the registry when nothing is configured
+pub async fn get_tracked_pools(db: &Database) -> Result<Vec<String>, sqlx::Error> {
+    let registry = get_pool_names(db, None).await?;
+
+    let pools = match get_depth_pools() {
+        Some(configured) if registry.is_empty() => configured,
+        Some(configured) => configured
+            .into_iter()
+            .filter(|pool_name| {
+                let known = registry.contains(pool_name);
+                if !known {
+                    tracing::warn!(
+                        "Skipping unknown pool {} from MIDGARD_DEPTH_POOLS",
+                        pool_name
+                    );
+                }
+                known
+            })
+            .collect(),
+        None => get_pool_names(db, Some("available")).await?,
+    };
+
+    if pools.is_empty() {
+        tracing::warn!("No pools to track yet, falling back to ETH.ETH");
+        return Ok(vec!["ETH.ETH".to_string()]);
+    }
+
+    Ok(pools)
+}
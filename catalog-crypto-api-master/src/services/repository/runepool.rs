@@ -1,42 +1,256 @@
-use crate::core::models::runepool_units_history::RunepoolUnitsInterval;
-use sqlx::MySqlPool;
+use super::{
+    checkpoints::{save_checkpoint_query, BackfillRange, Checkpoint},
+    sql, StoreSummary, BATCH_SIZE,
+};
+use crate::config::connect::{with_pool, Backend, Database};
+use crate::core::models::{
+    common::Dataset,
+    pagination::Cursor,
+    runepool_units_history::{MetaStats, RunepoolUnitsHistoryQueryParams, RunepoolUnitsInterval},
+};
+use chrono::{DateTime, Utc};
+use sqlx::{Encode, QueryBuilder, Type};
+use tracing::debug;
+
+const KEY_COLUMNS: [&str; 2] = ["start_time", "end_time"];
+const VALUE_COLUMNS: [&str; 2] = ["count", "units"];
 
 pub async fn store_intervals(
-    pool: &MySqlPool,
+    db: &Database,
+    intervals: &[RunepoolUnitsInterval],
+) -> Result<StoreSummary, sqlx::Error> {
+    store(db, intervals, None).await
+}
+
+pub async fn store_page(
+    db: &Database,
+    intervals: &[RunepoolUnitsInterval],
+) -> Result<StoreSummary, sqlx::Error> {
+    store(db, intervals, page_checkpoint(intervals, None)).await
+}
+
+// Same for a page of the `backfill` command, which keeps its progress per requested range
+pub async fn store_backfill_page(
+    db: &Database,
+    intervals: &[RunepoolUnitsInterval],
+    range: BackfillRange,
+) -> Result<StoreSummary, sqlx::Error> {
+    store(db, intervals, page_checkpoint(intervals, Some(range))).await
+}
+
+fn page_checkpoint(
+    intervals: &[RunepoolUnitsInterval],
+    range: Option<BackfillRange>,
+) -> Option<Checkpoint> {
+    intervals.last().map(|last| Checkpoint {
+        dataset: Dataset::Runepool,
+        pool: String::new(),
+        last_end_time: last.end_time,
+        range,
+    })
+}
+
+async fn store(
+    db: &Database,
     intervals: &[RunepoolUnitsInterval],
-) -> Result<(), sqlx::Error> {
-    for interval in intervals {
-        // Check if record exists
-        let exists = sqlx::query!(
-            r#"
-            SELECT COUNT(*) as count 
-            FROM `runepool_unit_intervals` 
-            WHERE start_time = ? AND end_time = ?
-            "#,
-            interval.start_time.naive_utc(),
-            interval.end_time.naive_utc()
-        )
-        .fetch_one(pool)
-        .await?
-        .count
-            > 0;
-
-        if !exists {
-            sqlx::query!(
-                r#"
-                INSERT INTO `runepool_unit_intervals` (
-                    start_time, end_time, count, units
-                ) VALUES (?, ?, ?, ?)
-                "#,
-                interval.start_time.naive_utc(),
-                interval.end_time.naive_utc(),
-                interval.count as i64,
-                interval.units as i64,
-            )
-            .execute(pool)
-            .await?;
+    checkpoint: Option<Checkpoint>,
+) -> Result<StoreSummary, sqlx::Error> {
+    let backend = db.backend();
+    let mut summary = StoreSummary::default();
+
+    with_pool!(db, pool => {
+        let mut tx = pool.begin().await?;
+
+        for batch in intervals.chunks(BATCH_SIZE) {
+            let mut existing = QueryBuilder::new(
+                "SELECT COUNT(*) FROM runepool_unit_intervals WHERE (start_time, end_time) IN ",
+            );
+            sql::push_keys(&mut existing, backend, batch, |mut row, interval| {
+                row.push_bind(interval.start_time)
+                    .push_bind(interval.end_time);
+            });
+            let existing: i64 = existing.build_query_scalar().fetch_one(&mut *tx).await?;
+
+            let mut query = QueryBuilder::new(
+                "INSERT INTO runepool_unit_intervals (start_time, end_time, count, units) ",
+            );
+            query.push_values(batch, |mut row, interval| {
+                row.push_bind(interval.start_time)
+                    .push_bind(interval.end_time)
+                    .push_bind(interval.count as i64)
+                    .push_bind(interval.units as i64);
+            });
+            query.push(sql::upsert(
+                backend,
+                "runepool_unit_intervals",
+                &KEY_COLUMNS,
+                &VALUE_COLUMNS,
+            ));
+            let affected = query.build().execute(&mut *tx).await?.rows_affected();
+
+            summary += StoreSummary::from_upsert(
+                backend,
+                batch.len() as u64,
+                existing as u64,
+                affected,
+            );
+        }
+
+        if let Some(checkpoint) = &checkpoint {
+            save_checkpoint_query(backend, checkpoint)
+                .build()
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+    });
+
+    Ok(summary)
+}
+
+// Every interval the params select, rolled up when asked, before sorting and paging
+fn push_intervals<'a, DB>(
+    query: &mut QueryBuilder<'a, DB>,
+    backend: Backend,
+    params: &RunepoolUnitsHistoryQueryParams,
+    columns: &str,
+) where
+    DB: sqlx::Database,
+    DateTime<Utc>: Encode<'a, DB> + Type<DB>,
+    i64: Encode<'a, DB> + Type<DB>,
+    f64: Encode<'a, DB> + Type<DB>,
+{
+    match &params.interval {
+        // Members and units are running totals, a bucket reports where they ended up
+        Some(interval) => {
+            let bucket = sql::bucket(backend, interval, "start_time");
+            debug!("Rolling up by {}", bucket);
+            query.push(format!(
+                "SELECT {columns} FROM (
+                    SELECT r.id, b.start_time, b.end_time, r.count, r.units, r.revision
+                    FROM (
+                        SELECT {bucket} AS start_time, MAX(end_time) AS end_time,
+                            MAX(start_time) AS last_start_time
+                        FROM runepool_unit_intervals WHERE 1=1"
+            ));
+            push_filters(query, params);
+            query.push(format!(
+                " GROUP BY {bucket}
+                    ) AS b
+                    JOIN runepool_unit_intervals AS r ON r.start_time = b.last_start_time
+                ) AS runepool_unit_intervals WHERE 1=1"
+            ));
         }
+        None => {
+            query.push(format!(
+                "SELECT {columns} FROM runepool_unit_intervals WHERE 1=1"
+            ));
+            push_filters(query, params);
+        }
+    }
+}
+
+pub async fn query_intervals(
+    db: &Database,
+    params: &RunepoolUnitsHistoryQueryParams,
+    limit: u32,
+    offset: u32,
+    cursor: Option<&Cursor>,
+) -> Result<Vec<RunepoolUnitsInterval>, sqlx::Error> {
+    let backend = db.backend();
+    let columns = sql::select_columns(params.projection().unwrap_or_default().as_deref());
+
+    with_pool!(db, pool => {
+        let mut query = QueryBuilder::new("");
+        push_intervals(&mut query, backend, params, &columns);
+        sql::push_page(
+            &mut query,
+            backend,
+            params.get_sort_field(),
+            params.order.as_deref(),
+            cursor,
+            limit,
+            offset,
+        );
+
+        debug!("Executing query: {}", query.sql());
+
+        query
+            .build_query_as::<RunepoolUnitsInterval>()
+            .fetch_all(pool)
+            .await
+    })
+}
+
+pub async fn count_intervals(
+    db: &Database,
+    params: &RunepoolUnitsHistoryQueryParams,
+) -> Result<u64, sqlx::Error> {
+    let backend = db.backend();
+
+    let total: i64 = with_pool!(db, pool => {
+        let mut query = QueryBuilder::new("SELECT COUNT(*) FROM (");
+        push_intervals(&mut query, backend, params, "*");
+        query.push(") AS matching");
+
+        query.build_query_scalar().fetch_one(pool).await?
+    });
+
+    Ok(total as u64)
+}
+
+// Start and end of the whole filtered range rather than of the current page
+pub async fn fetch_meta(
+    db: &Database,
+    params: &RunepoolUnitsHistoryQueryParams,
+) -> Result<MetaStats, sqlx::Error> {
+    let mut edges = Vec::with_capacity(2);
+
+    for order in ["ASC", "DESC"] {
+        let edge = with_pool!(db, pool => {
+            let mut query = QueryBuilder::new("SELECT * FROM runepool_unit_intervals WHERE 1=1");
+            push_filters(&mut query, params);
+            query.push(format!(" ORDER BY start_time {order} LIMIT 1"));
+
+            query
+                .build_query_as::<RunepoolUnitsInterval>()
+                .fetch_one(pool)
+                .await?
+        });
+        edges.push(edge);
+    }
+
+    Ok(meta_from_edges(&edges[0], &edges[1]))
+}
+
+pub(crate) fn meta_from_edges(
+    first: &RunepoolUnitsInterval,
+    last: &RunepoolUnitsInterval,
+) -> MetaStats {
+    MetaStats {
+        start_time: first.start_time,
+        end_time: last.end_time,
+        start_count: first.count,
+        end_count: last.count,
+        start_units: first.units,
+        end_units: last.units,
+    }
+}
+
+fn push_filters<'a, DB>(query: &mut QueryBuilder<'a, DB>, params: &RunepoolUnitsHistoryQueryParams)
+where
+    DB: sqlx::Database,
+    DateTime<Utc>: Encode<'a, DB> + Type<DB>,
+    i64: Encode<'a, DB> + Type<DB>,
+    f64: Encode<'a, DB> + Type<DB>,
+{
+    sql::push_time_range(query, params.time_range().unwrap_or_default());
+
+    if let Some(min_units) = params.units_gt {
+        debug!("Units filter: > {}", min_units);
+        query.push(" AND units > ").push_bind(min_units as i64);
     }
 
-    Ok(())
+    sql::push_column_filters(query, &params.filters().unwrap_or_default());
 }
@@ -0,0 +1,275 @@
+use crate::config::connect::Backend;
+use crate::core::models::common::{Interval, TimeRange};
+use crate::core::models::filters::{Column, Filter, FilterOp, FilterValue};
+use crate::core::models::pagination::Cursor;
+use chrono::{DateTime, Utc};
+use sqlx::query_builder::Separated;
+use sqlx::{Encode, QueryBuilder, Type};
+use tracing::debug;
+
+// SQL expression truncating `column` to the start of its bucket
+pub fn bucket(backend: Backend, interval: &Interval, column: &str) -> String {
+    match backend {
+        Backend::MySql => {
+            let bucket = match interval {
+                Interval::FiveMin | Interval::Hour => {
+                    format!("DATE_FORMAT({column}, '%Y-%m-%d %H:00:00')")
+                }
+                Interval::Day => format!("DATE({column})"),
+                Interval::Week => {
+                    format!("DATE_SUB(DATE({column}), INTERVAL WEEKDAY({column}) DAY)")
+                }
+                Interval::Month => format!("DATE_FORMAT({column}, '%Y-%m-01')"),
+                Interval::Quarter => {
+                    format!(
+                        "MAKEDATE(YEAR({column}), 1) + INTERVAL (QUARTER({column}) - 1) QUARTER"
+                    )
+                }
+                Interval::Year => format!("MAKEDATE(YEAR({column}), 1)"),
+            };
+            format!("CAST({} AS DATETIME)", bucket)
+        }
+        Backend::Postgres => {
+            let unit = match interval {
+                Interval::FiveMin | Interval::Hour => "hour",
+                Interval::Day => "day",
+                Interval::Week => "week",
+                Interval::Month => "month",
+                Interval::Quarter => "quarter",
+                Interval::Year => "year",
+            };
+            format!("date_trunc('{unit}', {column})")
+        }
+        // Timestamps are stored as rfc3339 text, datetime() normalises every bucket to the same
+        // `YYYY-MM-DD HH:MM:SS` form so they sort and compare as text
+        Backend::Sqlite => {
+            let bucket = match interval {
+                Interval::FiveMin | Interval::Hour => {
+                    format!("strftime('%Y-%m-%d %H:00:00', {column})")
+                }
+                Interval::Day => format!("date({column})"),
+                Interval::Week => format!("date({column}, '-6 days', 'weekday 1')"),
+                Interval::Month => format!("strftime('%Y-%m-01', {column})"),
+                Interval::Quarter => format!(
+                    "printf('%04d-%02d-01', strftime('%Y', {column}), \
+                     (strftime('%m', {column}) - 1) / 3 * 3 + 1)"
+                ),
+                Interval::Year => format!("strftime('%Y-01-01', {column})"),
+            };
+            format!("datetime({bucket})")
+        }
+    }
+}
+
+// SUM over an integer column, cast back so it decodes as a 64 bit integer instead of a decimal
+pub fn sum_int(backend: Backend, column: &str) -> String {
+    match backend {
+        Backend::MySql => format!("CAST(SUM({column}) AS SIGNED)"),
+        Backend::Postgres => format!("CAST(SUM({column}) AS BIGINT)"),
+        Backend::Sqlite => format!("CAST(SUM({column}) AS INTEGER)"),
+    }
+}
+
+// AVG over `column` weighted by how long each interval lasts, so a long interval counts for more
+// than an hour next to it. Falls back to the plain average when every interval is empty
+pub fn time_weighted_avg(backend: Backend, column: &str) -> String {
+    let seconds = match backend {
+        Backend::MySql => "TIMESTAMPDIFF(SECOND, start_time, end_time)",
+        Backend::Postgres => "CAST(EXTRACT(EPOCH FROM end_time - start_time) AS DOUBLE PRECISION)",
+        Backend::Sqlite => "((julianday(end_time) - julianday(start_time)) * 86400)",
+    };
+    format!("COALESCE(SUM({column} * {seconds}) / NULLIF(SUM({seconds}), 0), AVG({column}))")
+}
+
+// What a history query selects: everything, or the `fields` asked for plus the id and start time
+// the cursors are built from
+pub fn select_columns(fields: Option<&[&str]>) -> String {
+    let Some(fields) = fields else {
+        return "*".to_string();
+    };
+
+    let mut columns = vec!["id", "start_time"];
+    for field in fields {
+        if !columns.contains(field) {
+            columns.push(field);
+        }
+    }
+    columns.join(", ")
+}
+
+// Keeps the intervals starting within the range, so an hour belongs to the range it starts in
+pub fn push_time_range<'a, DB>(query: &mut QueryBuilder<'a, DB>, range: TimeRange)
+where
+    DB: sqlx::Database,
+    DateTime<Utc>: Encode<'a, DB> + Type<DB>,
+{
+    debug!("Time range filter: {:?}", range);
+    if let Some(start) = range.start {
+        query.push(" AND start_time >= ").push_bind(start);
+    }
+    if let Some(end) = range.end {
+        query.push(" AND start_time < ").push_bind(end);
+    }
+}
+
+// The `filter` conditions, the handlers have checked the columns against the dataset's list
+pub fn push_column_filters<'a, DB, C>(query: &mut QueryBuilder<'a, DB>, filters: &[Filter<C>])
+where
+    DB: sqlx::Database,
+    C: Column,
+    i64: Encode<'a, DB> + Type<DB>,
+    f64: Encode<'a, DB> + Type<DB>,
+{
+    for filter in filters {
+        debug!("Column filter: {:?}", filter);
+        let op = match filter.op {
+            FilterOp::Lt => "<",
+            FilterOp::Lte => "<=",
+            FilterOp::Gt => ">",
+            FilterOp::Gte => ">=",
+            FilterOp::Eq => "=",
+            FilterOp::Between => "BETWEEN",
+        };
+        query.push(format!(" AND {} {op} ", filter.column.column()));
+        push_filter_value(query, filter.value);
+        if let Some(upper) = filter.upper {
+            query.push(" AND ");
+            push_filter_value(query, upper);
+        }
+    }
+}
+
+fn push_filter_value<'a, DB>(query: &mut QueryBuilder<'a, DB>, value: FilterValue)
+where
+    DB: sqlx::Database,
+    i64: Encode<'a, DB> + Type<DB>,
+    f64: Encode<'a, DB> + Type<DB>,
+{
+    match value {
+        FilterValue::Int(value) => query.push_bind(value),
+        FilterValue::Float(value) => query.push_bind(value),
+    };
+}
+
+// Tail of a history query: the rows past `cursor`, the ordering and the page. id breaks ties so
+// rows sharing a value come back in the same order on every page
+pub fn push_page<'a, DB>(
+    query: &mut QueryBuilder<'a, DB>,
+    backend: Backend,
+    sort_field: &str,
+    order: Option<&str>,
+    cursor: Option<&Cursor>,
+    limit: u32,
+    offset: u32,
+) where
+    DB: sqlx::Database,
+    DateTime<Utc>: Encode<'a, DB> + Type<DB>,
+    i64: Encode<'a, DB> + Type<DB>,
+{
+    let descending = Cursor::descending(cursor, order);
+
+    if let Some(cursor) = cursor {
+        let comparison = if descending { "<" } else { ">" };
+        debug!("Reading {} the cursor {:?}", comparison, cursor.key());
+        // Stored and rolled up timestamps are text in different forms on sqlite
+        match backend {
+            Backend::Sqlite => query
+                .push(format!(
+                    " AND (datetime(start_time), id) {comparison} (datetime("
+                ))
+                .push_bind(cursor.start_time)
+                .push(")"),
+            Backend::MySql | Backend::Postgres => query
+                .push(format!(" AND (start_time, id) {comparison} ("))
+                .push_bind(cursor.start_time),
+        };
+        query.push(", ").push_bind(cursor.id).push(")");
+    }
+
+    let sort_order = if descending { "DESC" } else { "ASC" };
+    debug!("Sorting by {} {}", sort_field, sort_order);
+    query.push(format!(
+        " ORDER BY {sort_field} {sort_order}, id {sort_order}"
+    ));
+
+    query.push(" LIMIT ").push_bind(limit as i64);
+    query.push(" OFFSET ").push_bind(offset as i64);
+}
+
+// Tail of a multi-row INSERT that overwrites the values of rows already stored under `keys`.
+// revision and updated_at only move when one of `columns` actually changed
+pub fn upsert(backend: Backend, table: &str, keys: &[&str], columns: &[&str]) -> String {
+    match backend {
+        Backend::MySql => {
+            let unchanged = columns
+                .iter()
+                .map(|column| format!("{column} <=> VALUES({column})"))
+                .collect::<Vec<_>>()
+                .join(" AND ");
+            let assignments = columns
+                .iter()
+                .map(|column| format!("{column} = VALUES({column})"))
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            // revision has to come first, mysql applies the assignments in order and the
+            // comparison needs the old values. updated_at follows through ON UPDATE
+            format!(
+                " ON DUPLICATE KEY UPDATE revision = IF({unchanged}, revision, revision + 1), {assignments}"
+            )
+        }
+        Backend::Postgres | Backend::Sqlite => {
+            let assignments = columns
+                .iter()
+                .map(|column| format!("{column} = EXCLUDED.{column}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let current = columns
+                .iter()
+                .map(|column| format!("{table}.{column}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let incoming = columns
+                .iter()
+                .map(|column| format!("EXCLUDED.{column}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            // sqlite spells IS DISTINCT FROM for row values as IS NOT
+            let distinct = match backend {
+                Backend::Sqlite => "IS NOT",
+                _ => "IS DISTINCT FROM",
+            };
+
+            format!(
+                " ON CONFLICT ({}) DO UPDATE SET {assignments}, revision = {table}.revision + 1, \
+                 updated_at = CURRENT_TIMESTAMP WHERE ({current}) {distinct} ({incoming})",
+                keys.join(", ")
+            )
+        }
+    }
+}
+
+// Right-hand side of `(a, b) IN ...` for a batch of keys. sqlite only takes a subquery there, so
+// it gets a VALUES list instead of the row value list mysql and postgres accept
+pub fn push_keys<'args, DB, I, F>(
+    query: &mut QueryBuilder<'args, DB>,
+    backend: Backend,
+    keys: I,
+    push_key: F,
+) where
+    DB: sqlx::Database,
+    I: IntoIterator,
+    F: FnMut(Separated<'_, 'args, DB, &'static str>, I::Item),
+{
+    match backend {
+        Backend::Sqlite => {
+            query.push("(");
+            query.push_values(keys, push_key);
+            query.push(")");
+        }
+        Backend::MySql | Backend::Postgres => {
+            query.push_tuples(keys, push_key);
+        }
+    }
+}
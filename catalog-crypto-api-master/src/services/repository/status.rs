@@ -0,0 +1,152 @@
+use super::dataset_table;
+use crate::config::connect::{with_pool, Backend, Database};
+use crate::core::models::{
+    common::Dataset,
+    status::{DatasetStatus, IngestionState},
+};
+use chrono::{DateTime, Utc};
+use sqlx::QueryBuilder;
+
+pub async fn record_success(
+    db: &Database,
+    dataset: Dataset,
+    at: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    with_pool!(db, pool => {
+        let mut query = QueryBuilder::new(
+            "INSERT INTO ingestion_status (dataset, last_success_at, consecutive_failures) VALUES (",
+        );
+        query
+            .push_bind(dataset.to_string())
+            .push(", ")
+            .push_bind(at)
+            .push(", 0)");
+        query.push(match db.backend() {
+            Backend::MySql => {
+                " ON DUPLICATE KEY UPDATE \
+                 last_success_at = VALUES(last_success_at), consecutive_failures = 0"
+            }
+            Backend::Postgres | Backend::Sqlite => {
+                " ON CONFLICT (dataset) DO UPDATE \
+                 SET last_success_at = EXCLUDED.last_success_at, consecutive_failures = 0"
+            }
+        });
+
+        query.build().execute(pool).await?;
+    });
+
+    Ok(())
+}
+
+pub async fn record_failure(
+    db: &Database,
+    dataset: Dataset,
+    error: &str,
+    at: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    with_pool!(db, pool => {
+        let mut query = QueryBuilder::new(
+            "INSERT INTO ingestion_status \
+             (dataset, last_error, last_error_at, consecutive_failures) VALUES (",
+        );
+        query
+            .push_bind(dataset.to_string())
+            .push(", ")
+            .push_bind(error.to_string())
+            .push(", ")
+            .push_bind(at)
+            .push(", 1)");
+        query.push(match db.backend() {
+            Backend::MySql => {
+                " ON DUPLICATE KEY UPDATE \
+                 last_error = VALUES(last_error), last_error_at = VALUES(last_error_at), \
+                 consecutive_failures = consecutive_failures + 1"
+            }
+            Backend::Postgres | Backend::Sqlite => {
+                " ON CONFLICT (dataset) DO UPDATE \
+                 SET last_error = EXCLUDED.last_error, last_error_at = EXCLUDED.last_error_at, \
+                 consecutive_failures = ingestion_status.consecutive_failures + 1"
+            }
+        });
+
+        query.build().execute(pool).await?;
+    });
+
+    Ok(())
+}
+
+// last_success_at, last_error, last_error_at, consecutive_failures
+type StateRow = (
+    Option<DateTime<Utc>>,
+    Option<String>,
+    Option<DateTime<Utc>>,
+    i32,
+);
+
+pub async fn get_ingestion_state(
+    db: &Database,
+    dataset: Dataset,
+) -> Result<IngestionState, sqlx::Error> {
+    let row: Option<StateRow> = with_pool!(db, pool => {
+        let mut query = QueryBuilder::new(
+            "SELECT last_success_at, last_error, last_error_at, consecutive_failures \
+             FROM ingestion_status WHERE dataset = ",
+        );
+        query.push_bind(dataset.to_string());
+
+        query.build_query_as().fetch_optional(pool).await
+    })?;
+
+    Ok(row
+        .map(
+            |(last_success_at, last_error, last_error_at, consecutive_failures)| IngestionState {
+                last_success_at,
+                last_error,
+                last_error_at,
+                consecutive_failures: consecutive_failures.max(0) as u32,
+            },
+        )
+        .unwrap_or_default())
+}
+
+// Depth history is stored per pool, with a pool only its intervals count
+pub async fn get_latest_end_time(
+    db: &Database,
+    dataset: Dataset,
+    pool_name: Option<&str>,
+) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+    with_pool!(db, pool => {
+        let mut query =
+            QueryBuilder::new(format!("SELECT MAX(end_time) FROM {}", dataset_table(dataset)));
+        if let Some(pool_name) = pool_name {
+            query.push(" WHERE pool = ").push_bind(pool_name.to_string());
+        }
+
+        query.build_query_scalar().fetch_one(pool).await
+    })
+}
+
+// Newest stored interval and row count next to what the hourly fetcher recorded. Freshness is
+// left to the caller, it depends on when it's asked
+pub async fn get_dataset_status(
+    db: &Database,
+    dataset: Dataset,
+) -> Result<DatasetStatus, sqlx::Error> {
+    let (latest_end_time, row_count): (Option<DateTime<Utc>>, i64) = with_pool!(db, pool => {
+        QueryBuilder::new(format!(
+            "SELECT MAX(end_time), COUNT(*) FROM {}",
+            dataset_table(dataset)
+        ))
+        .build_query_as()
+        .fetch_one(pool)
+        .await
+    })?;
+    let state = get_ingestion_state(db, dataset).await?;
+
+    Ok(DatasetStatus::new(
+        dataset,
+        latest_end_time,
+        row_count,
+        state,
+    ))
+}
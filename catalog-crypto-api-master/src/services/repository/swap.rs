@@ -1,87 +1,334 @@
-use crate::core::models::swap_history::SwapInterval;
-use sqlx::MySqlPool;
+use super::{
+    checkpoints::{save_checkpoint_query, BackfillRange, Checkpoint},
+    sql, StoreSummary, BATCH_SIZE,
+};
+use crate::config::connect::{with_pool, Backend, Database};
+use crate::core::models::{
+    common::Dataset,
+    pagination::Cursor,
+    swap_history::{SwapHistoryQueryParams, SwapInterval, SwapMeta},
+};
+use chrono::{DateTime, Utc};
+use sqlx::{Encode, QueryBuilder, Type};
+use tracing::debug;
+
+const KEY_COLUMNS: [&str; 2] = ["start_time", "end_time"];
+const VALUE_COLUMNS: [&str; 36] = [
+    "average_slip",
+    "from_trade_average_slip",
+    "from_trade_count",
+    "from_trade_fees",
+    "from_trade_volume",
+    "from_trade_volume_usd",
+    "rune_price_usd",
+    "synth_mint_average_slip",
+    "synth_mint_count",
+    "synth_mint_fees",
+    "synth_mint_volume",
+    "synth_mint_volume_usd",
+    "synth_redeem_average_slip",
+    "synth_redeem_count",
+    "synth_redeem_fees",
+    "synth_redeem_volume",
+    "synth_redeem_volume_usd",
+    "to_asset_average_slip",
+    "to_asset_count",
+    "to_asset_fees",
+    "to_asset_volume",
+    "to_asset_volume_usd",
+    "to_rune_average_slip",
+    "to_rune_count",
+    "to_rune_fees",
+    "to_rune_volume",
+    "to_rune_volume_usd",
+    "to_trade_average_slip",
+    "to_trade_count",
+    "to_trade_fees",
+    "to_trade_volume",
+    "to_trade_volume_usd",
+    "total_count",
+    "total_fees",
+    "total_volume",
+    "total_volume_usd",
+];
+
+// Each slip with the count of swaps it averages over
+const WEIGHTED_SLIPS: [(&str, &str); 7] = [
+    ("average_slip", "total_count"),
+    ("from_trade_average_slip", "from_trade_count"),
+    ("synth_mint_average_slip", "synth_mint_count"),
+    ("synth_redeem_average_slip", "synth_redeem_count"),
+    ("to_asset_average_slip", "to_asset_count"),
+    ("to_rune_average_slip", "to_rune_count"),
+    ("to_trade_average_slip", "to_trade_count"),
+];
 
 pub async fn store_intervals(
-    pool: &MySqlPool,
+    db: &Database,
+    intervals: &[SwapInterval],
+) -> Result<StoreSummary, sqlx::Error> {
+    store(db, intervals, None).await
+}
+
+pub async fn store_page(
+    db: &Database,
+    intervals: &[SwapInterval],
+) -> Result<StoreSummary, sqlx::Error> {
+    store(db, intervals, page_checkpoint(intervals, None)).await
+}
+
+// Same for a page of the `backfill` command, which keeps its progress per requested range
+pub async fn store_backfill_page(
+    db: &Database,
     intervals: &[SwapInterval],
-) -> Result<(), sqlx::Error> {
-    for interval in intervals {
-        // Check if record exists
-        let exists = sqlx::query!(
-            r#"
-            SELECT COUNT(*) as count 
-            FROM `swap_intervals` 
-            WHERE start_time = ? AND end_time = ?
-            "#,
-            interval.start_time.naive_utc(),
-            interval.end_time.naive_utc()
-        )
-        .fetch_one(pool)
-        .await?
-        .count
-            > 0;
-
-        if !exists {
-            sqlx::query!(
-                r#"
-                INSERT INTO `swap_intervals` (
-                    start_time, end_time, average_slip, from_trade_average_slip,
-                    from_trade_count, from_trade_fees, from_trade_volume,
-                    from_trade_volume_usd, rune_price_usd, synth_mint_average_slip,
-                    synth_mint_count, synth_mint_fees, synth_mint_volume,
-                    synth_mint_volume_usd, synth_redeem_average_slip, synth_redeem_count,
-                    synth_redeem_fees, synth_redeem_volume, synth_redeem_volume_usd,
-                    to_asset_average_slip, to_asset_count, to_asset_fees,
-                    to_asset_volume, to_asset_volume_usd, to_rune_average_slip,
-                    to_rune_count, to_rune_fees, to_rune_volume, to_rune_volume_usd,
-                    to_trade_average_slip, to_trade_count, to_trade_fees,
-                    to_trade_volume, to_trade_volume_usd, total_count, total_fees,
-                    total_volume, total_volume_usd
-                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
-                "#,
-                interval.start_time.naive_utc(),
-                interval.end_time.naive_utc(),
-                interval.average_slip,
-                interval.from_trade_average_slip,
-                interval.from_trade_count as i64,
-                interval.from_trade_fees as i64,
-                interval.from_trade_volume as i64,
-                interval.from_trade_volume_usd as i64,
-                interval.rune_price_usd,
-                interval.synth_mint_average_slip,
-                interval.synth_mint_count as i64,
-                interval.synth_mint_fees as i64,
-                interval.synth_mint_volume as i64,
-                interval.synth_mint_volume_usd as i64,
-                interval.synth_redeem_average_slip,
-                interval.synth_redeem_count as i64,
-                interval.synth_redeem_fees as i64,
-                interval.synth_redeem_volume as i64,
-                interval.synth_redeem_volume_usd as i64,
-                interval.to_asset_average_slip,
-                interval.to_asset_count as i64,
-                interval.to_asset_fees as i64,
-                interval.to_asset_volume as i64,
-                interval.to_asset_volume_usd as i64,
-                interval.to_rune_average_slip,
-                interval.to_rune_count as i64,
-                interval.to_rune_fees as i64,
-                interval.to_rune_volume as i64,
-                interval.to_rune_volume_usd as i64,
-                interval.to_trade_average_slip,
-                interval.to_trade_count as i64,
-                interval.to_trade_fees as i64,
-                interval.to_trade_volume as i64,
-                interval.to_trade_volume_usd as i64,
-                interval.total_count as i64,
-                interval.total_fees as i64,
-                interval.total_volume as i64,
-                interval.total_volume_usd as i64,
-            )
-            .execute(pool)
-            .await?;
+    range: BackfillRange,
+) -> Result<StoreSummary, sqlx::Error> {
+    store(db, intervals, page_checkpoint(intervals, Some(range))).await
+}
+
+fn page_checkpoint(intervals: &[SwapInterval], range: Option<BackfillRange>) -> Option<Checkpoint> {
+    intervals.last().map(|last| Checkpoint {
+        dataset: Dataset::Swap,
+        pool: String::new(),
+        last_end_time: last.end_time,
+        range,
+    })
+}
+
+async fn store(
+    db: &Database,
+    intervals: &[SwapInterval],
+    checkpoint: Option<Checkpoint>,
+) -> Result<StoreSummary, sqlx::Error> {
+    let backend = db.backend();
+    let mut summary = StoreSummary::default();
+
+    with_pool!(db, pool => {
+        let mut tx = pool.begin().await?;
+
+        for batch in intervals.chunks(BATCH_SIZE) {
+            let mut existing = QueryBuilder::new(
+                "SELECT COUNT(*) FROM swap_intervals WHERE (start_time, end_time) IN ",
+            );
+            sql::push_keys(&mut existing, backend, batch, |mut row, interval| {
+                row.push_bind(interval.start_time)
+                    .push_bind(interval.end_time);
+            });
+            let existing: i64 = existing.build_query_scalar().fetch_one(&mut *tx).await?;
+
+            let mut query = QueryBuilder::new(format!(
+                "INSERT INTO swap_intervals ({}, {}) ",
+                KEY_COLUMNS.join(", "),
+                VALUE_COLUMNS.join(", ")
+            ));
+            query.push_values(batch, |mut row, interval| {
+                row.push_bind(interval.start_time)
+                    .push_bind(interval.end_time)
+                    .push_bind(interval.average_slip)
+                    .push_bind(interval.from_trade_average_slip)
+                    .push_bind(interval.from_trade_count as i64)
+                    .push_bind(interval.from_trade_fees as i64)
+                    .push_bind(interval.from_trade_volume as i64)
+                    .push_bind(interval.from_trade_volume_usd as i64)
+                    .push_bind(interval.rune_price_usd)
+                    .push_bind(interval.synth_mint_average_slip)
+                    .push_bind(interval.synth_mint_count as i64)
+                    .push_bind(interval.synth_mint_fees as i64)
+                    .push_bind(interval.synth_mint_volume as i64)
+                    .push_bind(interval.synth_mint_volume_usd as i64)
+                    .push_bind(interval.synth_redeem_average_slip)
+                    .push_bind(interval.synth_redeem_count as i64)
+                    .push_bind(interval.synth_redeem_fees as i64)
+                    .push_bind(interval.synth_redeem_volume as i64)
+                    .push_bind(interval.synth_redeem_volume_usd as i64)
+                    .push_bind(interval.to_asset_average_slip)
+                    .push_bind(interval.to_asset_count as i64)
+                    .push_bind(interval.to_asset_fees as i64)
+                    .push_bind(interval.to_asset_volume as i64)
+                    .push_bind(interval.to_asset_volume_usd as i64)
+                    .push_bind(interval.to_rune_average_slip)
+                    .push_bind(interval.to_rune_count as i64)
+                    .push_bind(interval.to_rune_fees as i64)
+                    .push_bind(interval.to_rune_volume as i64)
+                    .push_bind(interval.to_rune_volume_usd as i64)
+                    .push_bind(interval.to_trade_average_slip)
+                    .push_bind(interval.to_trade_count as i64)
+                    .push_bind(interval.to_trade_fees as i64)
+                    .push_bind(interval.to_trade_volume as i64)
+                    .push_bind(interval.to_trade_volume_usd as i64)
+                    .push_bind(interval.total_count as i64)
+                    .push_bind(interval.total_fees as i64)
+                    .push_bind(interval.total_volume as i64)
+                    .push_bind(interval.total_volume_usd as i64);
+            });
+            query.push(sql::upsert(backend, "swap_intervals", &KEY_COLUMNS, &VALUE_COLUMNS));
+            let affected = query.build().execute(&mut *tx).await?.rows_affected();
+
+            summary += StoreSummary::from_upsert(
+                backend,
+                batch.len() as u64,
+                existing as u64,
+                affected,
+            );
+        }
+
+        if let Some(checkpoint) = &checkpoint {
+            save_checkpoint_query(backend, checkpoint)
+                .build()
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+    });
+
+    Ok(summary)
+}
+
+// Volumes, fees and counts add up over a bucket (or the whole range for the meta), slips are
+// weighted by the swaps behind them and the rune price by the time each interval lasts
+fn rollup_columns(backend: Backend) -> String {
+    let mut columns = vec!["MAX(end_time) AS end_time".to_string()];
+
+    for (slip, count) in WEIGHTED_SLIPS {
+        columns.push(format!(
+            "COALESCE(SUM({slip} * {count}) / NULLIF(SUM({count}), 0), 0) AS {slip}"
+        ));
+    }
+
+    for column in VALUE_COLUMNS {
+        if column.ends_with("_slip") || column == "rune_price_usd" {
+            continue;
         }
+        columns.push(format!("{} AS {column}", sql::sum_int(backend, column)));
+    }
+
+    columns.push(format!(
+        "{} AS rune_price_usd",
+        sql::time_weighted_avg(backend, "rune_price_usd")
+    ));
+    columns.push("MAX(revision) AS revision".to_string());
+    columns.push("MAX(id) AS id".to_string());
+
+    columns.join(", ")
+}
+
+// Every interval the params select, rolled up when asked, before sorting and paging
+fn push_intervals<'a, DB>(
+    query: &mut QueryBuilder<'a, DB>,
+    backend: Backend,
+    params: &SwapHistoryQueryParams,
+    columns: &str,
+) where
+    DB: sqlx::Database,
+    DateTime<Utc>: Encode<'a, DB> + Type<DB>,
+    i64: Encode<'a, DB> + Type<DB>,
+    f64: Encode<'a, DB> + Type<DB>,
+{
+    match &params.interval {
+        Some(interval) => {
+            let bucket = sql::bucket(backend, interval, "start_time");
+            debug!("Rolling up by {}", bucket);
+            query.push(format!(
+                "SELECT {columns} FROM (
+                    SELECT {bucket} AS start_time, {}
+                    FROM swap_intervals WHERE 1=1",
+                rollup_columns(backend)
+            ));
+            push_filters(query, params);
+            query.push(format!(" GROUP BY {bucket}) AS swap_intervals WHERE 1=1"));
+        }
+        None => {
+            query.push(format!("SELECT {columns} FROM swap_intervals WHERE 1=1"));
+            push_filters(query, params);
+        }
+    }
+}
+
+pub async fn query_intervals(
+    db: &Database,
+    params: &SwapHistoryQueryParams,
+    limit: u32,
+    offset: u32,
+    cursor: Option<&Cursor>,
+) -> Result<Vec<SwapInterval>, sqlx::Error> {
+    let backend = db.backend();
+    let columns = sql::select_columns(params.projection().unwrap_or_default().as_deref());
+
+    with_pool!(db, pool => {
+        let mut query = QueryBuilder::new("");
+        push_intervals(&mut query, backend, params, &columns);
+        sql::push_page(
+            &mut query,
+            backend,
+            params.get_sort_field(),
+            params.order.as_deref(),
+            cursor,
+            limit,
+            offset,
+        );
+
+        debug!("Executing query: {}", query.sql());
+
+        query.build_query_as::<SwapInterval>().fetch_all(pool).await
+    })
+}
+
+pub async fn count_intervals(
+    db: &Database,
+    params: &SwapHistoryQueryParams,
+) -> Result<u64, sqlx::Error> {
+    let backend = db.backend();
+
+    let total: i64 = with_pool!(db, pool => {
+        let mut query = QueryBuilder::new("SELECT COUNT(*) FROM (");
+        push_intervals(&mut query, backend, params, "*");
+        query.push(") AS matching");
+
+        query.build_query_scalar().fetch_one(pool).await?
+    });
+
+    Ok(total as u64)
+}
+
+// Meta covers the whole filtered range, so it doesn't move with `page`/`limit`
+pub async fn fetch_meta(
+    db: &Database,
+    params: &SwapHistoryQueryParams,
+) -> Result<SwapMeta, sqlx::Error> {
+    let backend = db.backend();
+
+    with_pool!(db, pool => {
+        let mut query = QueryBuilder::new(format!(
+            "SELECT MIN(start_time) AS start_time, {} FROM swap_intervals WHERE 1=1",
+            rollup_columns(backend)
+        ));
+        push_filters(&mut query, params);
+
+        query.build_query_as::<SwapMeta>().fetch_one(pool).await
+    })
+}
+
+fn push_filters<'a, DB>(query: &mut QueryBuilder<'a, DB>, params: &SwapHistoryQueryParams)
+where
+    DB: sqlx::Database,
+    DateTime<Utc>: Encode<'a, DB> + Type<DB>,
+    i64: Encode<'a, DB> + Type<DB>,
+    f64: Encode<'a, DB> + Type<DB>,
+{
+    sql::push_time_range(query, params.time_range().unwrap_or_default());
+
+    if let Some(min_volume) = params.volume_gt {
+        debug!("Volume filter: > {}", min_volume);
+        query
+            .push(" AND total_volume > ")
+            .push_bind(min_volume as i64);
+    }
+
+    if let Some(min_fees) = params.fees_gt {
+        debug!("Fees filter: > {}", min_fees);
+        query.push(" AND total_fees > ").push_bind(min_fees as i64);
     }
 
-    Ok(())
+    sql::push_column_filters(query, &params.filters().unwrap_or_default());
 }
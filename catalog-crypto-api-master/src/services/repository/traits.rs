@@ -0,0 +1,340 @@
+use super::{depth, earnings, gaps, jobs, pools, runepool, status, swap, StoreSummary};
+use crate::config::connect::Database;
+use crate::core::models::{
+    common::Dataset,
+    depth_history::{self, DepthHistoryQueryParams, DepthInterval},
+    earnings_history::{self, EarningsHistoryQueryParams, IntervalData},
+    gaps::Gap,
+    jobs::JobState,
+    pagination::Cursor,
+    pools::{PoolDetail, PoolRecord},
+    runepool_units_history::{self, RunepoolUnitsHistoryQueryParams, RunepoolUnitsInterval},
+    status::DatasetStatus,
+    swap_history::{SwapHistoryQueryParams, SwapInterval, SwapMeta},
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+// What the handlers need from storage, one trait per dataset. `query_intervals` applies the
+// filters, rollup, sort and page, `count_intervals` counts what it pages through, `fetch_meta`
+// aggregates the whole filtered range and `store_intervals` upserts by time range like the crons
+// do. A page read before a cursor comes back in reverse, nearest to the cursor first
+#[async_trait]
+pub trait DepthRepository: Send + Sync {
+    async fn query_intervals(
+        &self,
+        params: &DepthHistoryQueryParams,
+        limit: u32,
+        offset: u32,
+        cursor: Option<&Cursor>,
+    ) -> Result<Vec<DepthInterval>, sqlx::Error>;
+
+    async fn count_intervals(&self, params: &DepthHistoryQueryParams) -> Result<u64, sqlx::Error>;
+
+    async fn fetch_meta(
+        &self,
+        params: &DepthHistoryQueryParams,
+    ) -> Result<depth_history::MetaStats, sqlx::Error>;
+
+    async fn store_intervals(
+        &self,
+        intervals: &[DepthInterval],
+    ) -> Result<StoreSummary, sqlx::Error>;
+}
+
+#[async_trait]
+pub trait EarningsRepository: Send + Sync {
+    async fn query_intervals(
+        &self,
+        params: &EarningsHistoryQueryParams,
+        limit: u32,
+        offset: u32,
+        cursor: Option<&Cursor>,
+    ) -> Result<Vec<IntervalData>, sqlx::Error>;
+
+    async fn count_intervals(
+        &self,
+        params: &EarningsHistoryQueryParams,
+    ) -> Result<u64, sqlx::Error>;
+
+    async fn fetch_meta(
+        &self,
+        params: &EarningsHistoryQueryParams,
+    ) -> Result<earnings_history::MetaStats, sqlx::Error>;
+
+    async fn store_intervals(
+        &self,
+        intervals: &[IntervalData],
+    ) -> Result<StoreSummary, sqlx::Error>;
+}
+
+#[async_trait]
+pub trait SwapRepository: Send + Sync {
+    async fn query_intervals(
+        &self,
+        params: &SwapHistoryQueryParams,
+        limit: u32,
+        offset: u32,
+        cursor: Option<&Cursor>,
+    ) -> Result<Vec<SwapInterval>, sqlx::Error>;
+
+    async fn count_intervals(&self, params: &SwapHistoryQueryParams) -> Result<u64, sqlx::Error>;
+
+    async fn fetch_meta(&self, params: &SwapHistoryQueryParams) -> Result<SwapMeta, sqlx::Error>;
+
+    async fn store_intervals(
+        &self,
+        intervals: &[SwapInterval],
+    ) -> Result<StoreSummary, sqlx::Error>;
+}
+
+#[async_trait]
+pub trait RunepoolRepository: Send + Sync {
+    async fn query_intervals(
+        &self,
+        params: &RunepoolUnitsHistoryQueryParams,
+        limit: u32,
+        offset: u32,
+        cursor: Option<&Cursor>,
+    ) -> Result<Vec<RunepoolUnitsInterval>, sqlx::Error>;
+
+    async fn count_intervals(
+        &self,
+        params: &RunepoolUnitsHistoryQueryParams,
+    ) -> Result<u64, sqlx::Error>;
+
+    async fn fetch_meta(
+        &self,
+        params: &RunepoolUnitsHistoryQueryParams,
+    ) -> Result<runepool_units_history::MetaStats, sqlx::Error>;
+
+    async fn store_intervals(
+        &self,
+        intervals: &[RunepoolUnitsInterval],
+    ) -> Result<StoreSummary, sqlx::Error>;
+}
+
+#[async_trait]
+pub trait PoolRepository: Send + Sync {
+    async fn list_pools(&self, status: Option<&str>) -> Result<Vec<PoolRecord>, sqlx::Error>;
+
+    async fn get_pool(&self, asset: &str) -> Result<Option<PoolRecord>, sqlx::Error>;
+
+    async fn is_known_pool(&self, asset: &str) -> Result<bool, sqlx::Error>;
+
+    async fn store_pools(&self, pools: &[PoolDetail]) -> Result<(), sqlx::Error>;
+}
+
+// Holes and overlaps in the stored series, for the admin routes and the repair job
+#[async_trait]
+pub trait GapRepository: Send + Sync {
+    async fn find_gaps(
+        &self,
+        dataset: Dataset,
+        pool: Option<&str>,
+    ) -> Result<Vec<Gap>, sqlx::Error>;
+}
+
+// How far each dataset got and how its fetches went, for GET /status
+#[async_trait]
+pub trait StatusRepository: Send + Sync {
+    async fn dataset_status(&self, dataset: Dataset) -> Result<DatasetStatus, sqlx::Error>;
+}
+
+// Commands for the scheduled jobs from the admin routes, the replica running the scheduler picks
+// them up on its next look at the job
+#[async_trait]
+pub trait JobRepository: Send + Sync {
+    async fn job_state(&self, name: &str) -> Result<JobState, sqlx::Error>;
+
+    async fn set_paused(&self, name: &str, paused: bool) -> Result<(), sqlx::Error>;
+
+    async fn request_run(&self, name: &str, at: DateTime<Utc>) -> Result<(), sqlx::Error>;
+
+    async fn set_rewind(&self, name: &str, to: DateTime<Utc>) -> Result<(), sqlx::Error>;
+}
+
+// The sql implementations, which backend runs the query is decided by the pool `Database` holds
+
+#[async_trait]
+impl DepthRepository for Database {
+    async fn query_intervals(
+        &self,
+        params: &DepthHistoryQueryParams,
+        limit: u32,
+        offset: u32,
+        cursor: Option<&Cursor>,
+    ) -> Result<Vec<DepthInterval>, sqlx::Error> {
+        depth::query_intervals(self, params, limit, offset, cursor).await
+    }
+
+    async fn count_intervals(&self, params: &DepthHistoryQueryParams) -> Result<u64, sqlx::Error> {
+        depth::count_intervals(self, params).await
+    }
+
+    async fn fetch_meta(
+        &self,
+        params: &DepthHistoryQueryParams,
+    ) -> Result<depth_history::MetaStats, sqlx::Error> {
+        depth::fetch_meta(self, params).await
+    }
+
+    async fn store_intervals(
+        &self,
+        intervals: &[DepthInterval],
+    ) -> Result<StoreSummary, sqlx::Error> {
+        depth::store_intervals(self, intervals).await
+    }
+}
+
+#[async_trait]
+impl EarningsRepository for Database {
+    async fn query_intervals(
+        &self,
+        params: &EarningsHistoryQueryParams,
+        limit: u32,
+        offset: u32,
+        cursor: Option<&Cursor>,
+    ) -> Result<Vec<IntervalData>, sqlx::Error> {
+        earnings::query_intervals(self, params, limit, offset, cursor).await
+    }
+
+    async fn count_intervals(
+        &self,
+        params: &EarningsHistoryQueryParams,
+    ) -> Result<u64, sqlx::Error> {
+        earnings::count_intervals(self, params).await
+    }
+
+    async fn fetch_meta(
+        &self,
+        params: &EarningsHistoryQueryParams,
+    ) -> Result<earnings_history::MetaStats, sqlx::Error> {
+        earnings::fetch_meta(self, params).await
+    }
+
+    async fn store_intervals(
+        &self,
+        intervals: &[IntervalData],
+    ) -> Result<StoreSummary, sqlx::Error> {
+        earnings::store_intervals(self, intervals).await
+    }
+}
+
+#[async_trait]
+impl SwapRepository for Database {
+    async fn query_intervals(
+        &self,
+        params: &SwapHistoryQueryParams,
+        limit: u32,
+        offset: u32,
+        cursor: Option<&Cursor>,
+    ) -> Result<Vec<SwapInterval>, sqlx::Error> {
+        swap::query_intervals(self, params, limit, offset, cursor).await
+    }
+
+    async fn count_intervals(&self, params: &SwapHistoryQueryParams) -> Result<u64, sqlx::Error> {
+        swap::count_intervals(self, params).await
+    }
+
+    async fn fetch_meta(&self, params: &SwapHistoryQueryParams) -> Result<SwapMeta, sqlx::Error> {
+        swap::fetch_meta(self, params).await
+    }
+
+    async fn store_intervals(
+        &self,
+        intervals: &[SwapInterval],
+    ) -> Result<StoreSummary, sqlx::Error> {
+        swap::store_intervals(self, intervals).await
+    }
+}
+
+#[async_trait]
+impl RunepoolRepository for Database {
+    async fn query_intervals(
+        &self,
+        params: &RunepoolUnitsHistoryQueryParams,
+        limit: u32,
+        offset: u32,
+        cursor: Option<&Cursor>,
+    ) -> Result<Vec<RunepoolUnitsInterval>, sqlx::Error> {
+        runepool::query_intervals(self, params, limit, offset, cursor).await
+    }
+
+    async fn count_intervals(
+        &self,
+        params: &RunepoolUnitsHistoryQueryParams,
+    ) -> Result<u64, sqlx::Error> {
+        runepool::count_intervals(self, params).await
+    }
+
+    async fn fetch_meta(
+        &self,
+        params: &RunepoolUnitsHistoryQueryParams,
+    ) -> Result<runepool_units_history::MetaStats, sqlx::Error> {
+        runepool::fetch_meta(self, params).await
+    }
+
+    async fn store_intervals(
+        &self,
+        intervals: &[RunepoolUnitsInterval],
+    ) -> Result<StoreSummary, sqlx::Error> {
+        runepool::store_intervals(self, intervals).await
+    }
+}
+
+#[async_trait]
+impl PoolRepository for Database {
+    async fn list_pools(&self, status: Option<&str>) -> Result<Vec<PoolRecord>, sqlx::Error> {
+        pools::list_pools(self, status).await
+    }
+
+    async fn get_pool(&self, asset: &str) -> Result<Option<PoolRecord>, sqlx::Error> {
+        pools::get_pool(self, asset).await
+    }
+
+    async fn is_known_pool(&self, asset: &str) -> Result<bool, sqlx::Error> {
+        pools::is_known_pool(self, asset).await
+    }
+
+    async fn store_pools(&self, pools: &[PoolDetail]) -> Result<(), sqlx::Error> {
+        pools::store_pools(self, pools).await
+    }
+}
+
+#[async_trait]
+impl GapRepository for Database {
+    async fn find_gaps(
+        &self,
+        dataset: Dataset,
+        pool: Option<&str>,
+    ) -> Result<Vec<Gap>, sqlx::Error> {
+        gaps::find_gaps(self, dataset, pool).await
+    }
+}
+
+#[async_trait]
+impl StatusRepository for Database {
+    async fn dataset_status(&self, dataset: Dataset) -> Result<DatasetStatus, sqlx::Error> {
+        status::get_dataset_status(self, dataset).await
+    }
+}
+
+#[async_trait]
+impl JobRepository for Database {
+    async fn job_state(&self, name: &str) -> Result<JobState, sqlx::Error> {
+        jobs::get_job_state(self, name).await
+    }
+
+    async fn set_paused(&self, name: &str, paused: bool) -> Result<(), sqlx::Error> {
+        jobs::set_paused(self, name, paused).await
+    }
+
+    async fn request_run(&self, name: &str, at: DateTime<Utc>) -> Result<(), sqlx::Error> {
+        jobs::request_run(self, name, at).await
+    }
+
+    async fn set_rewind(&self, name: &str, to: DateTime<Utc>) -> Result<(), sqlx::Error> {
+        jobs::set_rewind(self, name, to).await
+    }
+}
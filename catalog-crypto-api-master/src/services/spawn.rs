@@ -1,38 +1,116 @@
+use crate::config::connect::Database;
+use crate::core::models::common::Dataset;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::task::JoinSet;
+
+use super::client::get_schedule;
 use super::jobs::cron::{
     depth_history_cron::DepthHistoryCron, earnings_history_cron::EarningsHistoryCron,
+    gap_repair_cron::GapRepairCron, hourly_fetcher::HourlyFetcher, pools_cron::PoolsCron,
     runepool_units_history_cron::RunepoolUnitsHistoryCron, swap_history_cron::SwapHistoryCron,
 };
+use super::jobs::scheduler::{parse_schedule, Job, Scheduler};
+
+// The backfill crons for BACKFILL_ON_START, dropping the set stops them and pausing a dataset's
+// job holds its backfill until it is resumed
+pub fn spawn_cron_jobs(db: Database) -> JoinSet<()> {
+    let mut jobs = JoinSet::new();
 
-pub fn spawn_cron_jobs(pool: sqlx::MySqlPool) {
-    let depth_pool = pool.clone();
-    tokio::spawn(async move {
+    let depth_pool = db.clone();
+    jobs.spawn(async move {
         let mut depth_cron = DepthHistoryCron::new(depth_pool);
         if let Err(e) = depth_cron.start().await {
             tracing::error!("Depth history cron failed: {}", e);
         }
     });
 
-    let earnings_pool = pool.clone();
-    tokio::spawn(async move {
+    let earnings_pool = db.clone();
+    jobs.spawn(async move {
         let mut earnings_cron = EarningsHistoryCron::new(earnings_pool);
         if let Err(e) = earnings_cron.start().await {
             tracing::error!("Earnings history cron failed: {}", e);
         }
     });
 
-    let swap_pool = pool.clone();
-    tokio::spawn(async move {
+    let swap_pool = db.clone();
+    jobs.spawn(async move {
         let mut swap_cron = SwapHistoryCron::new(swap_pool);
         if let Err(e) = swap_cron.start().await {
             tracing::error!("Swap history cron failed: {}", e);
         }
     });
 
-    let runepool_pool = pool.clone();
-    tokio::spawn(async move {
+    let runepool_pool = db.clone();
+    jobs.spawn(async move {
         let mut runepool_cron = RunepoolUnitsHistoryCron::new(runepool_pool);
         if let Err(e) = runepool_cron.start().await {
             tracing::error!("Runepool units history cron failed: {}", e);
         }
     });
+
+    jobs
+}
+
+// Every recurring job with its default schedule, SCHEDULE_<JOB> replaces it. The datasets are
+// fetched on the hour when midgard closes their intervals, the pool registry half an hour earlier
+// so new pools are known by then
+const JOBS: [(&str, &str); 6] = [
+    ("pools", "30 * * * *"),
+    ("depth", "0 * * * *"),
+    ("earnings", "0 * * * *"),
+    ("swap", "0 * * * *"),
+    ("runepool", "0 * * * *"),
+    ("gap_repair", "15 */6 * * *"),
+];
+
+// How often the scheduled jobs look for commands from the admin routes
+const CONTROL_POLL: std::time::Duration = std::time::Duration::from_secs(5);
+
+// The scheduled jobs and the schedule each one runs on, for the admin routes
+pub fn job_schedules() -> Vec<(&'static str, String)> {
+    JOBS.iter()
+        .map(|(job, default)| (*job, get_schedule(job, default)))
+        .collect()
+}
+
+// All but the gap repair catch up as soon as the scheduler starts
+pub fn schedule_jobs(db: Database) -> Result<Scheduler, anyhow::Error> {
+    let schedules: HashMap<&str, String> = job_schedules().into_iter().collect();
+    let schedule = |job: &str| parse_schedule(&schedules[job]);
+
+    let pools_db = db.clone();
+    let mut scheduler = Scheduler::new()
+        .with_controls(db.clone(), CONTROL_POLL)
+        .with_job(
+            Job::new("pools", schedule("pools")?, move || {
+                let mut pools_cron = PoolsCron::new(pools_db.clone());
+                async move { pools_cron.sync().await }
+            })
+            .with_catch_up(),
+        );
+
+    let fetcher = Arc::new(HourlyFetcher::new(db.clone()));
+    for dataset in [
+        Dataset::Depth,
+        Dataset::Earnings,
+        Dataset::Swap,
+        Dataset::Runepool,
+    ] {
+        let name = dataset.to_string();
+        let fetcher = fetcher.clone();
+        let job = Job::new(&name, schedule(&name)?, move || {
+            let fetcher = fetcher.clone();
+            async move { fetcher.fetch_dataset(dataset).await }
+        });
+        scheduler = scheduler.with_job(job.with_catch_up());
+    }
+
+    let gap_repair_cron = Arc::new(GapRepairCron::new(db));
+    Ok(
+        scheduler.with_job(Job::new("gap_repair", schedule("gap_repair")?, move || {
+            let gap_repair_cron = gap_repair_cron.clone();
+            async move { gap_repair_cron.repair().await }
+        })),
+    )
 }
@@ -1,12 +1,26 @@
 // !I don't know why but the this is working but i need to import the __path_ to make it work wise words from the compiler
+use crate::api::error::ErrorResponse;
+use crate::api::routes::admin::__path_get_gaps;
 use crate::api::routes::depth::__path_get_depth_history;
 use crate::api::routes::earnings::__path_get_earnings_history;
+use crate::api::routes::jobs::{
+    __path_list_jobs, __path_pause_job, __path_reset_checkpoint, __path_resume_job, __path_run_job,
+};
+use crate::api::routes::pools::{__path_get_pool, __path_get_pools};
 use crate::api::routes::runepool::__path_get_runepool_units_history;
+use crate::api::routes::status::__path_get_status;
 use crate::api::routes::swap::__path_get_swap_history;
 use crate::core::models::{
-    depth_history::DepthHistoryResponse, earnings_history::EarningsHistoryResponse,
-    runepool_units_history::RunepoolUnitsHistoryResponse, swap_history::SwapHistoryResponse,
+    depth_history::DepthHistoryResponse,
+    earnings_history::EarningsHistoryResponse,
+    gaps::GapsResponse,
+    jobs::{CheckpointRequest, JobInfo, JobsResponse},
+    pools::PoolRecord,
+    runepool_units_history::RunepoolUnitsHistoryResponse,
+    status::StatusResponse,
+    swap_history::SwapHistoryResponse,
 };
+use utoipa::openapi::security::{Http, HttpAuthScheme, SecurityScheme};
 
 // ! Don't format the description it will break the swagger ui description it looks better this way
 #[derive(utoipa::OpenApi)]
@@ -21,6 +35,8 @@ use crate::core::models::{
         - Swap transaction analytics and metrics
         - Network earnings data across different pools
         - Runepool units historical data
+        - Registry of the pools known to midgard
+        - Ingestion status and data freshness per dataset
 
 'The API supports pagination, filtering, sorting, and date range queries. Data is continuously synchronized through background cron jobs with rate limiting protection. Built with Rust using Axum framework and MySQL for persistence.'
 ",
@@ -41,39 +57,54 @@ use crate::core::models::{
         (name = "depth", description = "Depth history operations"),
         (name = "swap", description = "Swap history operations"),
         (name = "earnings", description = "Earnings history operations"),
-        (name = "runepool", description = "Runepool units history operations")
+        (name = "runepool", description = "Runepool units history operations"),
+        (name = "pools", description = "Pool registry operations"),
+        (name = "admin", description = "Checks on the stored data and control of the ingestion jobs, needs the ADMIN_TOKEN bearer token"),
+        (name = "status", description = "Ingestion health and data freshness")
     ),
     paths(
         get_depth_history,
         get_swap_history,
         get_runepool_units_history,
-        get_earnings_history
+        get_earnings_history,
+        get_pools,
+        get_pool,
+        get_gaps,
+        list_jobs,
+        pause_job,
+        resume_job,
+        run_job,
+        reset_checkpoint,
+        get_status
     ),
     components(
         schemas(
             DepthHistoryResponse,
             SwapHistoryResponse,
             RunepoolUnitsHistoryResponse,
-            EarningsHistoryResponse
+            EarningsHistoryResponse,
+            PoolRecord,
+            GapsResponse,
+            JobsResponse,
+            JobInfo,
+            CheckpointRequest,
+            StatusResponse,
+            ErrorResponse
         )
     ),
-    // modifiers(&SecurityAddon)
+    modifiers(&SecurityAddon)
 )]
 pub struct SwaggerApiDoc;
 
-// struct SecurityAddon;
+struct SecurityAddon;
 
-// impl utoipa::Modify for SecurityAddon {
-//     fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
-//         if let Some(components) = openapi.components.as_mut() {
-//             components.add_security_scheme(
-//                 "api_key",
-//                 utoipa::openapi::security::SecurityScheme::ApiKey(
-//                     utoipa::openapi::security::ApiKey::Header(
-//                         utoipa::openapi::security::ApiKeyValue::new("x-api-key"),
-//                     ),
-//                 ),
-//             );
-//         }
-//     }
-// }
+impl utoipa::Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "admin_token",
+                SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)),
+            );
+        }
+    }
+}
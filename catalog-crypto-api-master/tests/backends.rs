@@ -0,0 +1,267 @@
+mod common;
+
+// The repositories against a real Postgres and MySQL, where the SQL differs the most from
+// sqlite. They need a database of their own that every table gets emptied in, and are left out
+// of a plain `cargo test`:
+//
+//   TEST_POSTGRES_URL=postgres://... TEST_MYSQL_URL=mysql://... \
+//       cargo test --test backends -- --ignored
+
+use catalog_crypto_api::config::connect::{connect_database, Database};
+use catalog_crypto_api::core::models::common::Dataset;
+use catalog_crypto_api::core::models::depth_history::{DepthHistoryQueryParams, DepthInterval};
+use catalog_crypto_api::core::models::earnings_history::EarningsHistoryQueryParams;
+use catalog_crypto_api::core::models::pagination::{cursor_page, Cursor};
+use catalog_crypto_api::core::models::runepool_units_history::RunepoolUnitsHistoryQueryParams;
+use catalog_crypto_api::core::models::swap_history::SwapHistoryQueryParams;
+use catalog_crypto_api::services::jobs::{backfill::Backfill, cron::pools_cron::PoolsCron};
+use catalog_crypto_api::services::repository::{
+    checkpoints::{get_backfill_progress, BackfillRange},
+    gaps::find_gaps,
+    jobs::{clear_rewind, get_job_state, set_rewind},
+    leases::try_acquire_lease,
+    traits::{DepthRepository, EarningsRepository, RunepoolRepository, SwapRepository},
+};
+use chrono::{DateTime, Duration, Utc};
+use common::MockMidgard;
+use serde_json::json;
+
+// End of the last interval in every history fixture
+const LAST_END_TIME: i64 = 1704088800;
+
+const TABLES: [&str; 10] = [
+    "depth_intervals",
+    "earning_intervals",
+    "swap_intervals",
+    "runepool_unit_intervals",
+    "pools",
+    "ingestion_checkpoints",
+    "backfill_progress",
+    "ingestion_status",
+    "job_leases",
+    "scheduled_jobs",
+];
+
+// Connecting runs the migrations, what earlier runs left behind is cleared out
+async fn connect(var: &str) -> Database {
+    let url = std::env::var(var).unwrap_or_else(|_| panic!("{var} is not set"));
+    let db = connect_database(&url).await.unwrap();
+
+    for table in TABLES {
+        let sql = format!("DELETE FROM {table}");
+        match &db {
+            Database::MySql(pool) => sqlx::query(&sql).execute(pool).await.map(|_| ()),
+            Database::Postgres(pool) => sqlx::query(&sql).execute(pool).await.map(|_| ()),
+            Database::Sqlite(_) => unreachable!("sqlite is covered by the other tests"),
+        }
+        .unwrap();
+    }
+
+    db
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_POSTGRES_URL"]
+async fn repositories_on_postgres() {
+    exercise(connect("TEST_POSTGRES_URL").await).await;
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_MYSQL_URL"]
+async fn repositories_on_mysql() {
+    exercise(connect("TEST_MYSQL_URL").await).await;
+}
+
+async fn exercise(db: Database) {
+    let midgard = MockMidgard::start().await;
+    PoolsCron::new(db.clone())
+        .with_midgard_url(&midgard.url)
+        .sync()
+        .await
+        .unwrap();
+
+    stores_every_dataset(&db, &midgard).await;
+    upserts_by_revision(&db).await;
+    rolls_up_by_day(&db).await;
+    pages_with_cursors(&db).await;
+    keeps_job_state(&db).await;
+}
+
+// Row-value IN for the existing rows, the upserts and the progress rows
+async fn stores_every_dataset(db: &Database, midgard: &MockMidgard) {
+    let range = BackfillRange {
+        from: DateTime::from_timestamp(1704067200, 0).unwrap(),
+        to: DateTime::from_timestamp(LAST_END_TIME, 0).unwrap(),
+    };
+    for dataset in [
+        Dataset::Depth,
+        Dataset::Earnings,
+        Dataset::Swap,
+        Dataset::Runepool,
+    ] {
+        Backfill::new(db.clone(), dataset, range)
+            .with_midgard_url(&midgard.url)
+            .run()
+            .await
+            .unwrap();
+
+        let progress = get_backfill_progress(db, dataset, None, &range)
+            .await
+            .unwrap();
+        if dataset != Dataset::Depth {
+            assert_eq!(progress, Some(range.to), "{dataset}");
+        }
+        assert!(find_gaps(db, dataset, None).await.unwrap().is_empty());
+    }
+
+    let depth: DepthHistoryQueryParams = serde_json::from_value(json!({})).unwrap();
+    assert_eq!(
+        DepthRepository::count_intervals(db, &depth).await.unwrap(),
+        12
+    );
+    let earnings: EarningsHistoryQueryParams = serde_json::from_value(json!({})).unwrap();
+    assert_eq!(
+        EarningsRepository::count_intervals(db, &earnings)
+            .await
+            .unwrap(),
+        6
+    );
+    let swaps: SwapHistoryQueryParams = serde_json::from_value(json!({})).unwrap();
+    assert_eq!(
+        SwapRepository::count_intervals(db, &swaps).await.unwrap(),
+        6
+    );
+    let runepool: RunepoolUnitsHistoryQueryParams = serde_json::from_value(json!({})).unwrap();
+    assert_eq!(
+        RunepoolRepository::count_intervals(db, &runepool)
+            .await
+            .unwrap(),
+        6
+    );
+}
+
+// Storing the same rows again leaves them be, a changed one gets a new revision
+async fn upserts_by_revision(db: &Database) {
+    let params: SwapHistoryQueryParams = serde_json::from_value(json!({})).unwrap();
+    let mut swaps = SwapRepository::query_intervals(db, &params, 100, 0, None)
+        .await
+        .unwrap();
+
+    let summary = SwapRepository::store_intervals(db, &swaps).await.unwrap();
+    assert_eq!(summary.inserted, 0);
+    assert_eq!(summary.updated, 0);
+    assert_eq!(summary.unchanged, 6);
+
+    let revision = swaps[0].revision;
+    swaps[0].total_count += 1;
+    let summary = SwapRepository::store_intervals(db, &swaps[..1])
+        .await
+        .unwrap();
+    assert_eq!(summary.updated, 1);
+
+    let stored = SwapRepository::query_intervals(db, &params, 100, 0, None)
+        .await
+        .unwrap();
+    let changed = stored
+        .iter()
+        .find(|row| row.start_time == swaps[0].start_time)
+        .unwrap();
+    assert_eq!(changed.total_count, swaps[0].total_count);
+    assert_eq!(changed.revision, revision + 1);
+}
+
+// Bucketing, the per-pool earnings unpacked from json and the metas over the whole range
+async fn rolls_up_by_day(db: &Database) {
+    let params: EarningsHistoryQueryParams =
+        serde_json::from_value(json!({ "interval": "day" })).unwrap();
+    let days = EarningsRepository::query_intervals(db, &params, 100, 0, None)
+        .await
+        .unwrap();
+    let hours: EarningsHistoryQueryParams = serde_json::from_value(json!({})).unwrap();
+    let hours = EarningsRepository::query_intervals(db, &hours, 100, 0, None)
+        .await
+        .unwrap();
+    assert_eq!(days.len(), 1);
+    assert_eq!(days[0].start_time.timestamp(), 1704067200);
+    assert_eq!(
+        days[0].earnings,
+        hours.iter().map(|row| row.earnings).sum::<u64>()
+    );
+    assert_eq!(days[0].pools.len(), 2);
+    let meta = EarningsRepository::fetch_meta(db, &params).await.unwrap();
+    assert_eq!(meta.earnings, days[0].earnings);
+
+    let params: DepthHistoryQueryParams =
+        serde_json::from_value(json!({ "interval": "day" })).unwrap();
+    let days = DepthRepository::query_intervals(db, &params, 100, 0, None)
+        .await
+        .unwrap();
+    assert_eq!(days.len(), 2);
+
+    let params: SwapHistoryQueryParams =
+        serde_json::from_value(json!({ "interval": "day" })).unwrap();
+    let days = SwapRepository::query_intervals(db, &params, 100, 0, None)
+        .await
+        .unwrap();
+    assert_eq!(days.len(), 1);
+    let meta = SwapRepository::fetch_meta(db, &params).await.unwrap();
+    assert_eq!(meta.total_count, days[0].total_count);
+}
+
+// Keyset pages over the (start_time, id) pairs, both ways and once rolled up
+async fn pages_with_cursors(db: &Database) {
+    for query in [
+        json!({ "order": "asc" }),
+        json!({ "order": "desc", "interval": "day" }),
+    ] {
+        let params: DepthHistoryQueryParams = serde_json::from_value(query).unwrap();
+        let key = |row: &DepthInterval| (row.pool.clone(), row.start_time);
+        let all: Vec<_> = DepthRepository::query_intervals(db, &params, 100, 0, None)
+            .await
+            .unwrap()
+            .iter()
+            .map(key)
+            .collect();
+
+        let mut seen = Vec::new();
+        let mut cursor: Option<Cursor> = None;
+        loop {
+            let rows = DepthRepository::query_intervals(db, &params, 2, 0, cursor.as_ref())
+                .await
+                .unwrap();
+            let page = cursor_page(rows, 1, 0, cursor.as_ref(), true, |row| {
+                (row.start_time, row.id)
+            });
+            seen.extend(page.rows.iter().map(key));
+            let Some(next) = page.next_cursor else {
+                break;
+            };
+            cursor = Cursor::decode(&next);
+        }
+
+        assert_eq!(seen, all);
+    }
+}
+
+async fn keeps_job_state(db: &Database) {
+    let now = Utc::now();
+    assert!(
+        try_acquire_lease(db, "ingestion", "a", now + Duration::seconds(30), now)
+            .await
+            .unwrap()
+    );
+    assert!(
+        !try_acquire_lease(db, "ingestion", "b", now + Duration::seconds(30), now)
+            .await
+            .unwrap()
+    );
+
+    let rewind_to = DateTime::from_timestamp(1704070800, 0).unwrap();
+    set_rewind(db, "swap", rewind_to).await.unwrap();
+    assert_eq!(
+        get_job_state(db, "swap").await.unwrap().rewind_to,
+        Some(rewind_to)
+    );
+    clear_rewind(db, "swap", rewind_to).await.unwrap();
+    assert_eq!(get_job_state(db, "swap").await.unwrap().rewind_to, None);
+}
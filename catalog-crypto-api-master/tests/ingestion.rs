@@ -0,0 +1,1059 @@
+mod common;
+
+use catalog_crypto_api::config::connect::Database;
+use catalog_crypto_api::core::models::common::{Dataset, HISTORY_START_TIMESTAMP};
+use catalog_crypto_api::core::models::depth_history::{DepthHistoryQueryParams, DepthInterval};
+use catalog_crypto_api::core::models::earnings_history::EarningsHistoryQueryParams;
+use catalog_crypto_api::core::models::gaps::GapKind;
+use catalog_crypto_api::core::models::pagination::{cursor_page, Cursor, CursorPage};
+use catalog_crypto_api::core::models::swap_history::SwapHistoryQueryParams;
+use catalog_crypto_api::services::jobs::cron::{
+    depth_history_cron::DepthHistoryCron, earnings_history_cron::EarningsHistoryCron,
+    gap_repair_cron::GapRepairCron, hourly_fetcher::HourlyFetcher, pools_cron::PoolsCron,
+    runepool_units_history_cron::RunepoolUnitsHistoryCron, swap_history_cron::SwapHistoryCron,
+};
+use catalog_crypto_api::services::jobs::{
+    backfill::Backfill,
+    leader::LeaderElection,
+    scheduler::{parse_schedule, Job, Scheduler},
+};
+use catalog_crypto_api::services::repository::{
+    checkpoints::{get_backfill_progress, get_checkpoint, BackfillRange},
+    gaps::find_gaps,
+    jobs::{get_job_state, request_run, set_paused, set_rewind},
+    leases::{release_lease, try_acquire_lease},
+    pools,
+    traits::{DepthRepository, EarningsRepository, StatusRepository, SwapRepository},
+};
+use chrono::{DateTime, Duration, Utc};
+use common::{count_rows, fresh_db, Fault, MockMidgard};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+// End of the last interval in every history fixture
+const LAST_END_TIME: i64 = 1704088800;
+
+async fn scalar(db: &Database, sql: &str) -> i64 {
+    let Database::Sqlite(pool) = db else {
+        unreachable!("tests run against sqlite")
+    };
+    sqlx::query_scalar(sql).fetch_one(pool).await.unwrap()
+}
+
+async fn execute(db: &Database, sql: &str) {
+    let Database::Sqlite(pool) = db else {
+        unreachable!("tests run against sqlite")
+    };
+    sqlx::query(sql).execute(pool).await.unwrap();
+}
+
+#[tokio::test]
+async fn syncs_the_pool_registry() {
+    let midgard = MockMidgard::start().await;
+    let db = fresh_db().await;
+
+    PoolsCron::new(db.clone())
+        .with_midgard_url(&midgard.url)
+        .sync()
+        .await
+        .unwrap();
+
+    assert_eq!(count_rows(&db, "pools").await, 3);
+    let available = pools::get_pool_names(&db, Some("available")).await.unwrap();
+    assert_eq!(available, vec!["BTC.BTC", "ETH.ETH"]);
+}
+
+#[tokio::test]
+async fn rejects_a_malformed_pool_list() {
+    let midgard = MockMidgard::start().await;
+    let db = fresh_db().await;
+    midgard.fail_next("/pools", Fault::Malformed);
+
+    let result = PoolsCron::new(db.clone())
+        .with_midgard_url(&midgard.url)
+        .sync()
+        .await;
+
+    assert!(result.is_err());
+    assert_eq!(count_rows(&db, "pools").await, 0);
+}
+
+#[tokio::test]
+async fn backfills_depths_for_every_available_pool() {
+    let midgard = MockMidgard::start().await;
+    let db = fresh_db().await;
+    PoolsCron::new(db.clone())
+        .with_midgard_url(&midgard.url)
+        .sync()
+        .await
+        .unwrap();
+
+    let mut cron = DepthHistoryCron::new(db.clone()).with_midgard_url(&midgard.url);
+    cron.refresh_pools().await.unwrap();
+    // Each fixture fits on one page, so both pools are caught up after it
+    assert!(cron.fetch_and_store().await.unwrap());
+
+    assert_eq!(count_rows(&db, "depth_intervals").await, 12);
+    for pool in ["BTC.BTC", "ETH.ETH"] {
+        let requests = midgard.requests(&format!("/history/depths/{pool}"));
+        assert_eq!(requests[0]["from"], HISTORY_START_TIMESTAMP.to_string());
+        assert_eq!(requests[0]["interval"], "hour");
+
+        let checkpoint = get_checkpoint(&db, Dataset::Depth, Some(pool))
+            .await
+            .unwrap();
+        assert_eq!(checkpoint.unwrap().timestamp(), LAST_END_TIME);
+    }
+    // Staged pools are not walked
+    assert!(midgard.requests("/history/depths/DOGE.DOGE").is_empty());
+}
+
+#[tokio::test]
+async fn resumes_earnings_from_the_checkpoint() {
+    let midgard = MockMidgard::start().await;
+    let db = fresh_db().await;
+
+    let mut cron = EarningsHistoryCron::new(db.clone()).with_midgard_url(&midgard.url);
+    cron.load_checkpoint().await.unwrap();
+    cron.fetch_and_store().await.unwrap();
+
+    assert_eq!(count_rows(&db, "earning_intervals").await, 6);
+    let pools_per_interval = scalar(
+        &db,
+        "SELECT MIN(json_array_length(pools)) FROM earning_intervals",
+    )
+    .await;
+    assert_eq!(pools_per_interval, 2);
+
+    // A restarted cron picks up where the last one stopped instead of starting over
+    let mut restarted = EarningsHistoryCron::new(db.clone()).with_midgard_url(&midgard.url);
+    restarted.load_checkpoint().await.unwrap();
+    restarted.fetch_and_store().await.unwrap();
+
+    let requests = midgard.requests("/history/earnings");
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[1]["from"], LAST_END_TIME.to_string());
+    assert_eq!(count_rows(&db, "earning_intervals").await, 6);
+}
+
+#[tokio::test]
+async fn waits_out_rate_limits_during_backfill() {
+    let midgard = MockMidgard::start().await;
+    let db = fresh_db().await;
+    midgard.fail_next("/history/swaps", Fault::SlowDown);
+
+    let mut cron = SwapHistoryCron::new(db.clone()).with_midgard_url(&midgard.url);
+    cron.load_checkpoint().await.unwrap();
+    cron.fetch_and_store().await.unwrap();
+
+    let requests = midgard.requests("/history/swaps");
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0]["from"], requests[1]["from"]);
+    assert_eq!(count_rows(&db, "swap_intervals").await, 6);
+}
+
+#[tokio::test]
+async fn retries_malformed_bodies_during_backfill() {
+    let midgard = MockMidgard::start().await;
+    let db = fresh_db().await;
+    midgard.fail_next("/history/runepool", Fault::Malformed);
+
+    let mut cron = RunepoolUnitsHistoryCron::new(db.clone()).with_midgard_url(&midgard.url);
+    cron.load_checkpoint().await.unwrap();
+    cron.fetch_and_store().await.unwrap();
+
+    assert_eq!(midgard.requests("/history/runepool").len(), 2);
+    assert_eq!(count_rows(&db, "runepool_unit_intervals").await, 6);
+    let checkpoint = get_checkpoint(&db, Dataset::Runepool, None).await.unwrap();
+    assert_eq!(checkpoint.unwrap().timestamp(), LAST_END_TIME);
+}
+
+#[tokio::test]
+async fn hourly_fetch_stores_the_latest_intervals_of_every_dataset() {
+    let midgard = MockMidgard::start().await;
+    let db = fresh_db().await;
+    PoolsCron::new(db.clone())
+        .with_midgard_url(&midgard.url)
+        .sync()
+        .await
+        .unwrap();
+
+    HourlyFetcher::new(db.clone())
+        .with_midgard_url(&midgard.url)
+        .fetch_cycle()
+        .await;
+
+    // Three refetched intervals per dataset, depths once for each available pool
+    assert_eq!(count_rows(&db, "depth_intervals").await, 6);
+    assert_eq!(count_rows(&db, "earning_intervals").await, 3);
+    assert_eq!(count_rows(&db, "swap_intervals").await, 3);
+    assert_eq!(count_rows(&db, "runepool_unit_intervals").await, 3);
+
+    let requests = midgard.requests("/history/swaps");
+    assert_eq!(requests[0]["count"], "3");
+    assert!(requests[0].contains_key("to"));
+}
+
+#[tokio::test]
+async fn hourly_fetch_overwrites_revised_intervals() {
+    let midgard = MockMidgard::start().await;
+    let db = fresh_db().await;
+    let mut cron = SwapHistoryCron::new(db.clone()).with_midgard_url(&midgard.url);
+    cron.fetch_latest_hour().await.unwrap();
+
+    midgard.edit_fixture("/history/swaps", |fixture| {
+        let last = fixture["intervals"]
+            .as_array_mut()
+            .unwrap()
+            .last_mut()
+            .unwrap();
+        last["totalVolume"] = Value::String("123456789".to_string());
+    });
+    cron.fetch_latest_hour().await.unwrap();
+
+    assert_eq!(count_rows(&db, "swap_intervals").await, 3);
+    let revised = scalar(
+        &db,
+        "SELECT CAST(strftime('%s', end_time) AS INTEGER) FROM swap_intervals WHERE revision = 1",
+    )
+    .await;
+    assert_eq!(revised, LAST_END_TIME);
+    assert_eq!(
+        scalar(&db, "SELECT SUM(revision) FROM swap_intervals").await,
+        1
+    );
+    assert_eq!(
+        scalar(
+            &db,
+            "SELECT total_volume FROM swap_intervals WHERE revision = 1"
+        )
+        .await,
+        123456789
+    );
+}
+
+#[tokio::test]
+async fn hourly_fetch_fails_on_malformed_bodies() {
+    let midgard = MockMidgard::start().await;
+    let db = fresh_db().await;
+    midgard.fail_next("/history/runepool", Fault::Malformed);
+
+    let result = RunepoolUnitsHistoryCron::new(db.clone())
+        .with_midgard_url(&midgard.url)
+        .fetch_latest_hour()
+        .await;
+
+    assert!(result.is_err());
+    assert_eq!(count_rows(&db, "runepool_unit_intervals").await, 0);
+}
+
+#[tokio::test]
+async fn hourly_fetch_records_the_outcome_of_every_dataset() {
+    let midgard = MockMidgard::start().await;
+    let db = fresh_db().await;
+    let fetcher = HourlyFetcher::new(db.clone()).with_midgard_url(&midgard.url);
+
+    for _ in 0..2 {
+        midgard.fail_next("/history/runepool", Fault::Malformed);
+        fetcher.fetch_cycle().await;
+    }
+
+    let swap = db.dataset_status(Dataset::Swap).await.unwrap();
+    assert_eq!(swap.row_count, 3);
+    assert_eq!(swap.latest_end_time.unwrap().timestamp(), LAST_END_TIME);
+    assert!(swap.last_success_at.is_some());
+    assert_eq!(swap.consecutive_failures, 0);
+
+    let runepool = db.dataset_status(Dataset::Runepool).await.unwrap();
+    assert_eq!(runepool.row_count, 0);
+    assert_eq!(runepool.last_success_at, None);
+    assert!(runepool.last_error.is_some());
+    assert_eq!(runepool.consecutive_failures, 2);
+
+    // A successful fetch clears the failure count, the last error stays for reference
+    fetcher.fetch_cycle().await;
+    let runepool = db.dataset_status(Dataset::Runepool).await.unwrap();
+    assert!(runepool.last_success_at.is_some());
+    assert!(runepool.last_error.is_some());
+    assert_eq!(runepool.consecutive_failures, 0);
+}
+
+#[tokio::test]
+async fn hourly_fetch_catches_up_after_downtime() {
+    let midgard = MockMidgard::start().await;
+    let db = fresh_db().await;
+    // Only the first hour was stored before the service went down
+    let range = BackfillRange {
+        from: DateTime::from_timestamp(1704067200, 0).unwrap(),
+        to: DateTime::from_timestamp(1704070800, 0).unwrap(),
+    };
+    Backfill::new(db.clone(), Dataset::Swap, range)
+        .with_midgard_url(&midgard.url)
+        .run()
+        .await
+        .unwrap();
+
+    HourlyFetcher::new(db.clone())
+        .with_midgard_url(&midgard.url)
+        .fetch_dataset(Dataset::Swap)
+        .await
+        .unwrap();
+
+    // The hours between the first one and the re-fetched ones came from the catch-up
+    assert_eq!(count_rows(&db, "swap_intervals").await, 6);
+    let requests = midgard.requests("/history/swaps");
+    assert_eq!(requests[1]["from"], "1704070800");
+}
+
+#[tokio::test]
+async fn hourly_fetch_fetches_again_from_a_reset_checkpoint() {
+    let midgard = MockMidgard::start().await;
+    let db = fresh_db().await;
+    let range = BackfillRange {
+        from: DateTime::from_timestamp(1704067200, 0).unwrap(),
+        to: DateTime::from_timestamp(LAST_END_TIME, 0).unwrap(),
+    };
+    Backfill::new(db.clone(), Dataset::Swap, range)
+        .with_midgard_url(&midgard.url)
+        .run()
+        .await
+        .unwrap();
+    let rewind_to = DateTime::from_timestamp(1704070800, 0).unwrap();
+    set_rewind(&db, "swap", rewind_to).await.unwrap();
+
+    HourlyFetcher::new(db.clone())
+        .with_midgard_url(&midgard.url)
+        .fetch_dataset(Dataset::Swap)
+        .await
+        .unwrap();
+
+    // Instead of catching up from the newest stored interval
+    let requests = midgard.requests("/history/swaps");
+    assert_eq!(requests[1]["from"], "1704070800");
+    let state = get_job_state(&db, "swap").await.unwrap();
+    assert_eq!(state.rewind_to, None);
+}
+
+#[tokio::test]
+async fn hourly_fetch_resumes_an_interrupted_rewind() {
+    let midgard = MockMidgard::start().await;
+    let db = fresh_db().await;
+    let range = BackfillRange {
+        from: DateTime::from_timestamp(1704067200, 0).unwrap(),
+        to: DateTime::from_timestamp(1704070800, 0).unwrap(),
+    };
+    Backfill::new(db.clone(), Dataset::Swap, range)
+        .with_midgard_url(&midgard.url)
+        .run()
+        .await
+        .unwrap();
+    let fetcher = HourlyFetcher::new(db.clone()).with_midgard_url(&midgard.url);
+    fetcher.fetch_dataset(Dataset::Swap).await.unwrap();
+
+    let rewind_to = DateTime::from_timestamp(1704070800, 0).unwrap();
+    set_rewind(&db, "swap", rewind_to).await.unwrap();
+    let checkpoint = get_checkpoint(&db, Dataset::Swap, None).await.unwrap();
+    assert_eq!(checkpoint, Some(rewind_to));
+    // As if the run had stored the second hour before it was stopped
+    execute(
+        &db,
+        "UPDATE ingestion_checkpoints SET last_end_time =          (SELECT end_time FROM swap_intervals ORDER BY start_time LIMIT 1 OFFSET 1)",
+    )
+    .await;
+    let sent = midgard.requests("/history/swaps").len();
+
+    fetcher.fetch_dataset(Dataset::Swap).await.unwrap();
+
+    let requests = midgard.requests("/history/swaps");
+    assert_eq!(requests[sent]["from"], "1704074400");
+    // The catch-ups keep no progress of their own, only the first backfill did
+    assert_eq!(count_rows(&db, "backfill_progress").await, 1);
+    let state = get_job_state(&db, "swap").await.unwrap();
+    assert_eq!(state.rewind_to, None);
+}
+
+#[tokio::test]
+async fn hourly_fetch_catches_up_each_pool_on_its_own() {
+    let midgard = MockMidgard::start().await;
+    let db = fresh_db().await;
+    PoolsCron::new(db.clone())
+        .with_midgard_url(&midgard.url)
+        .sync()
+        .await
+        .unwrap();
+    // BTC.BTC is complete, ETH.ETH only has its first hour
+    for (pool, to) in [("BTC.BTC", LAST_END_TIME), ("ETH.ETH", 1704070800)] {
+        let range = BackfillRange {
+            from: DateTime::from_timestamp(1704067200, 0).unwrap(),
+            to: DateTime::from_timestamp(to, 0).unwrap(),
+        };
+        Backfill::new(db.clone(), Dataset::Depth, range)
+            .with_midgard_url(&midgard.url)
+            .with_pools(vec![pool.to_string()])
+            .run()
+            .await
+            .unwrap();
+    }
+
+    HourlyFetcher::new(db.clone())
+        .with_midgard_url(&midgard.url)
+        .fetch_dataset(Dataset::Depth)
+        .await
+        .unwrap();
+
+    // Each pool continues from its own newest interval, not from the newest of all pools
+    assert_eq!(count_rows(&db, "depth_intervals").await, 12);
+    let requests = midgard.requests("/history/depths/ETH.ETH");
+    assert_eq!(requests[1]["from"], "1704070800");
+    let requests = midgard.requests("/history/depths/BTC.BTC");
+    assert_eq!(requests[1]["from"], LAST_END_TIME.to_string());
+}
+
+#[tokio::test]
+async fn scheduler_follows_pause_and_run_commands() {
+    let db = fresh_db().await;
+    let runs = Arc::new(AtomicUsize::new(0));
+    set_paused(&db, "swap", true).await.unwrap();
+
+    let job = {
+        let runs = runs.clone();
+        // Every second
+        Job::new("swap", parse_schedule("* * * * * *").unwrap(), move || {
+            let runs = runs.clone();
+            async move {
+                runs.fetch_add(1, Ordering::SeqCst);
+                Err(anyhow::anyhow!("midgard is down"))
+            }
+        })
+        .with_catch_up()
+    };
+    let scheduler = Scheduler::new()
+        .with_controls(db.clone(), std::time::Duration::from_millis(50))
+        .with_job(job);
+    let scheduler = tokio::spawn(scheduler.run());
+
+    // Neither the catch-up nor the schedule runs a paused job
+    tokio::time::sleep(std::time::Duration::from_millis(1500)).await;
+    assert_eq!(runs.load(Ordering::SeqCst), 0);
+
+    // Asking for a run still starts one
+    request_run(&db, "swap", Utc::now()).await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    assert_eq!(runs.load(Ordering::SeqCst), 1);
+    let state = get_job_state(&db, "swap").await.unwrap();
+    assert_eq!(state.run_requested_at, None);
+    assert!(state.last_finished_at >= state.last_started_at);
+    assert_eq!(state.last_error.as_deref(), Some("midgard is down"));
+
+    set_paused(&db, "swap", false).await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(2500)).await;
+    assert!(runs.load(Ordering::SeqCst) >= 2);
+
+    scheduler.abort();
+}
+
+#[tokio::test]
+async fn pausing_a_job_holds_its_backfill_on_start() {
+    let midgard = MockMidgard::start().await;
+    let db = fresh_db().await;
+    set_paused(&db, "swap", true).await.unwrap();
+
+    let backfill = {
+        let (db, url) = (db.clone(), midgard.url.clone());
+        tokio::spawn(async move { SwapHistoryCron::new(db).with_midgard_url(url).start().await })
+    };
+
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    assert!(midgard.requests("/history/swaps").is_empty());
+
+    set_paused(&db, "swap", false).await.unwrap();
+    tokio::time::timeout(std::time::Duration::from_secs(10), backfill)
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert_eq!(count_rows(&db, "swap_intervals").await, 6);
+}
+
+// One interval per page, read like the depth handler does
+async fn depth_page(
+    db: &Database,
+    params: &DepthHistoryQueryParams,
+    cursor: Option<Cursor>,
+) -> CursorPage<DepthInterval> {
+    let rows = DepthRepository::query_intervals(db, params, 2, 0, cursor.as_ref())
+        .await
+        .unwrap();
+    cursor_page(rows, 1, 0, cursor.as_ref(), true, |row| {
+        (row.start_time, row.id)
+    })
+}
+
+#[tokio::test]
+async fn pages_through_stored_depths_with_cursors() {
+    let midgard = MockMidgard::start().await;
+    let db = fresh_db().await;
+    PoolsCron::new(db.clone())
+        .with_midgard_url(&midgard.url)
+        .sync()
+        .await
+        .unwrap();
+    HourlyFetcher::new(db.clone())
+        .with_midgard_url(&midgard.url)
+        .fetch_cycle()
+        .await;
+
+    // Both pools share every hour, and every day once rolled up, so the id decides between them
+    for query in [
+        json!({ "order": "asc" }),
+        json!({ "order": "desc" }),
+        json!({ "order": "desc", "interval": "day" }),
+    ] {
+        let params: DepthHistoryQueryParams = serde_json::from_value(query).unwrap();
+        let key = |row: &DepthInterval| (row.pool.clone(), row.start_time);
+
+        let all: Vec<_> = DepthRepository::query_intervals(&db, &params, 100, 0, None)
+            .await
+            .unwrap()
+            .iter()
+            .map(key)
+            .collect();
+
+        let mut seen = Vec::new();
+        let mut page = depth_page(&db, &params, None).await;
+        loop {
+            seen.extend(page.rows.iter().map(key));
+            let Some(next) = &page.next_cursor else {
+                break;
+            };
+            let previous = page.rows.iter().map(key).collect::<Vec<_>>();
+            page = depth_page(&db, &params, Cursor::decode(next)).await;
+
+            let back = depth_page(
+                &db,
+                &params,
+                Cursor::decode(page.prev_cursor.as_ref().unwrap()),
+            )
+            .await;
+            assert_eq!(back.rows.iter().map(key).collect::<Vec<_>>(), previous);
+        }
+
+        assert_eq!(seen, all);
+        assert_eq!(
+            DepthRepository::count_intervals(&db, &params)
+                .await
+                .unwrap(),
+            all.len() as u64
+        );
+    }
+}
+
+#[tokio::test]
+async fn filters_stored_depths_on_column_ranges() {
+    let midgard = MockMidgard::start().await;
+    let db = fresh_db().await;
+    PoolsCron::new(db.clone())
+        .with_midgard_url(&midgard.url)
+        .sync()
+        .await
+        .unwrap();
+    HourlyFetcher::new(db.clone())
+        .with_midgard_url(&midgard.url)
+        .fetch_cycle()
+        .await;
+
+    let params: DepthHistoryQueryParams = serde_json::from_value(json!({})).unwrap();
+    let all = DepthRepository::query_intervals(&db, &params, 100, 0, None)
+        .await
+        .unwrap();
+    let mut units: Vec<u64> = all.iter().map(|row| row.units).collect();
+    units.sort();
+    let (low, high) = (units[0], units[units.len() / 2]);
+    let price = all[0].asset_price_usd;
+
+    let params: DepthHistoryQueryParams = serde_json::from_value(json!({
+        "filter": format!("units:between:{low}:{high},asset_price_usd:lte:{price}")
+    }))
+    .unwrap();
+    let filtered = DepthRepository::query_intervals(&db, &params, 100, 0, None)
+        .await
+        .unwrap();
+
+    let expected = all
+        .iter()
+        .filter(|row| (low..=high).contains(&row.units) && row.asset_price_usd <= price)
+        .count();
+    assert!(expected > 0);
+    assert_eq!(filtered.len(), expected);
+    assert_eq!(
+        DepthRepository::count_intervals(&db, &params)
+            .await
+            .unwrap(),
+        expected as u64
+    );
+}
+
+#[tokio::test]
+async fn reads_stored_depths_within_a_time_range() {
+    let midgard = MockMidgard::start().await;
+    let db = fresh_db().await;
+    PoolsCron::new(db.clone())
+        .with_midgard_url(&midgard.url)
+        .sync()
+        .await
+        .unwrap();
+    HourlyFetcher::new(db.clone())
+        .with_midgard_url(&midgard.url)
+        .fetch_cycle()
+        .await;
+
+    let params: DepthHistoryQueryParams = serde_json::from_value(json!({})).unwrap();
+    let all = DepthRepository::query_intervals(&db, &params, 100, 0, None)
+        .await
+        .unwrap();
+    let mut hours: Vec<_> = all.iter().map(|row| row.start_time).collect();
+    hours.sort();
+    hours.dedup();
+    assert!(hours.len() > 2);
+    let (from, to) = (hours[1], hours[hours.len() - 1]);
+
+    let params: DepthHistoryQueryParams = serde_json::from_value(json!({
+        "from": from.timestamp().to_string(),
+        "to": to.to_rfc3339(),
+    }))
+    .unwrap();
+    let within = DepthRepository::query_intervals(&db, &params, 100, 0, None)
+        .await
+        .unwrap();
+
+    let expected = all
+        .iter()
+        .filter(|row| row.start_time >= from && row.start_time < to)
+        .count();
+    assert_eq!(within.len(), expected);
+    assert!(within
+        .iter()
+        .all(|row| row.start_time >= from && row.start_time < to));
+}
+
+#[tokio::test]
+async fn selects_only_the_requested_fields() {
+    let midgard = MockMidgard::start().await;
+    let db = fresh_db().await;
+    HourlyFetcher::new(db.clone())
+        .with_midgard_url(&midgard.url)
+        .fetch_cycle()
+        .await;
+
+    for interval in [None, Some("day")] {
+        let params: SwapHistoryQueryParams =
+            serde_json::from_value(json!({ "interval": interval })).unwrap();
+        let all = SwapRepository::query_intervals(&db, &params, 100, 0, None)
+            .await
+            .unwrap();
+        let params: SwapHistoryQueryParams = serde_json::from_value(json!({
+            "interval": interval,
+            "fields": "total_volume,rune_price_usd",
+            "sort_by": "total_fees",
+        }))
+        .unwrap();
+        let narrowed = SwapRepository::query_intervals(&db, &params, 100, 0, None)
+            .await
+            .unwrap();
+
+        assert!(!all.is_empty());
+        let mut all: Vec<_> = all
+            .iter()
+            .map(|row| (row.start_time, row.total_volume, row.rune_price_usd))
+            .collect();
+        let mut picked: Vec<_> = narrowed
+            .iter()
+            .map(|row| (row.start_time, row.total_volume, row.rune_price_usd))
+            .collect();
+        all.sort_by_key(|row| row.0);
+        picked.sort_by_key(|row| row.0);
+        assert_eq!(picked, all);
+        assert!(narrowed.iter().all(|row| row.total_fees == 0 && row.id > 0));
+
+        let params: EarningsHistoryQueryParams = serde_json::from_value(json!({
+            "interval": interval,
+            "fields": "earnings",
+        }))
+        .unwrap();
+        let earnings = EarningsRepository::query_intervals(&db, &params, 100, 0, None)
+            .await
+            .unwrap();
+        assert!(!earnings.is_empty());
+        assert!(earnings
+            .iter()
+            .all(|row| row.earnings > 0 && row.pools.is_empty()));
+    }
+}
+
+#[tokio::test]
+async fn weights_rolled_up_averages_by_duration() {
+    let midgard = MockMidgard::start().await;
+    let db = fresh_db().await;
+    let range = BackfillRange {
+        from: DateTime::from_timestamp(1704067200, 0).unwrap(),
+        to: DateTime::from_timestamp(LAST_END_TIME, 0).unwrap(),
+    };
+    for dataset in [Dataset::Earnings, Dataset::Swap] {
+        Backfill::new(db.clone(), dataset, range)
+            .with_midgard_url(&midgard.url)
+            .run()
+            .await
+            .unwrap();
+    }
+
+    // The first interval covers three hours at a price of 1, the three after it an hour each
+    // at 4. Weighted that's 2.5 over the day, a plain mean of the four rows gives 3.25
+    for table in ["earning_intervals", "swap_intervals"] {
+        execute(
+            &db,
+            &format!(
+                "UPDATE {table} SET end_time = \
+                 (SELECT end_time FROM {table} ORDER BY start_time LIMIT 1 OFFSET 2) \
+                 WHERE start_time = (SELECT MIN(start_time) FROM {table})"
+            ),
+        )
+        .await;
+        execute(
+            &db,
+            &format!(
+                "DELETE FROM {table} WHERE start_time IN \
+                 (SELECT start_time FROM {table} ORDER BY start_time LIMIT 2 OFFSET 1)"
+            ),
+        )
+        .await;
+        execute(&db, &format!("UPDATE {table} SET rune_price_usd = 4")).await;
+        execute(
+            &db,
+            &format!(
+                "UPDATE {table} SET rune_price_usd = 1 \
+                 WHERE start_time = (SELECT MIN(start_time) FROM {table})"
+            ),
+        )
+        .await;
+    }
+    execute(&db, "UPDATE earning_intervals SET avg_node_count = 20").await;
+    execute(
+        &db,
+        "UPDATE earning_intervals SET avg_node_count = 10 \
+         WHERE start_time = (SELECT MIN(start_time) FROM earning_intervals)",
+    )
+    .await;
+
+    let params: EarningsHistoryQueryParams =
+        serde_json::from_value(json!({ "interval": "day" })).unwrap();
+    let earnings = EarningsRepository::query_intervals(&db, &params, 100, 0, None)
+        .await
+        .unwrap();
+    assert_eq!(earnings.len(), 1);
+    assert_eq!(earnings[0].rune_price_usd, 2.5);
+    assert_eq!(earnings[0].avg_node_count, 15.0);
+
+    let params: SwapHistoryQueryParams =
+        serde_json::from_value(json!({ "interval": "day" })).unwrap();
+    let swaps = SwapRepository::query_intervals(&db, &params, 100, 0, None)
+        .await
+        .unwrap();
+    assert_eq!(swaps.len(), 1);
+    assert_eq!(swaps[0].rune_price_usd, 2.5);
+}
+
+#[tokio::test]
+async fn backfills_only_the_requested_range() {
+    let midgard = MockMidgard::start().await;
+    let db = fresh_db().await;
+    // The second to the fourth hour of the fixtures
+    let range = BackfillRange {
+        from: DateTime::from_timestamp(1704070800, 0).unwrap(),
+        to: DateTime::from_timestamp(1704081600, 0).unwrap(),
+    };
+
+    Backfill::new(db.clone(), Dataset::Swap, range)
+        .with_midgard_url(&midgard.url)
+        .run()
+        .await
+        .unwrap();
+
+    assert_eq!(count_rows(&db, "swap_intervals").await, 3);
+    let progress = get_backfill_progress(&db, Dataset::Swap, None, &range)
+        .await
+        .unwrap();
+    assert_eq!(progress, Some(range.to));
+    // The crons keep their own checkpoint
+    let checkpoint = get_checkpoint(&db, Dataset::Swap, None).await.unwrap();
+    assert_eq!(checkpoint, None);
+}
+
+#[tokio::test]
+async fn resumes_a_backfill_from_its_progress() {
+    let midgard = MockMidgard::start().await;
+    let db = fresh_db().await;
+    let range = BackfillRange {
+        from: DateTime::from_timestamp(1704067200, 0).unwrap(),
+        to: DateTime::from_timestamp(LAST_END_TIME, 0).unwrap(),
+    };
+    let backfill = Backfill::new(db.clone(), Dataset::Depth, range)
+        .with_midgard_url(&midgard.url)
+        .with_pools(vec!["BTC.BTC".to_string()]);
+
+    backfill.run().await.unwrap();
+    backfill.run().await.unwrap();
+
+    // The second run starts at the end of the range and has nothing left to fetch
+    let requests = midgard.requests("/history/depths/BTC.BTC");
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0]["from"], "1704067200");
+    assert_eq!(count_rows(&db, "depth_intervals").await, 6);
+    assert!(midgard.requests("/history/depths/ETH.ETH").is_empty());
+}
+
+#[tokio::test]
+async fn rejects_pools_for_datasets_that_are_not_per_pool() {
+    let midgard = MockMidgard::start().await;
+    let db = fresh_db().await;
+    let range = BackfillRange {
+        from: DateTime::from_timestamp(1704067200, 0).unwrap(),
+        to: DateTime::from_timestamp(LAST_END_TIME, 0).unwrap(),
+    };
+
+    let result = Backfill::new(db.clone(), Dataset::Earnings, range)
+        .with_midgard_url(&midgard.url)
+        .with_pools(vec!["BTC.BTC".to_string()])
+        .run()
+        .await;
+
+    assert!(result.is_err());
+    assert!(midgard.requests("/history/earnings").is_empty());
+}
+
+#[tokio::test]
+async fn repairs_missing_hours() {
+    let midgard = MockMidgard::start().await;
+    let db = fresh_db().await;
+    let range = BackfillRange {
+        from: DateTime::from_timestamp(1704067200, 0).unwrap(),
+        to: DateTime::from_timestamp(LAST_END_TIME, 0).unwrap(),
+    };
+    for dataset in [Dataset::Depth, Dataset::Swap] {
+        Backfill::new(db.clone(), dataset, range)
+            .with_midgard_url(&midgard.url)
+            .with_pools(if dataset == Dataset::Depth {
+                vec!["BTC.BTC".to_string(), "ETH.ETH".to_string()]
+            } else {
+                Vec::new()
+            })
+            .run()
+            .await
+            .unwrap();
+    }
+    assert!(find_gaps(&db, Dataset::Depth, None)
+        .await
+        .unwrap()
+        .is_empty());
+
+    // Lose the third and fourth hour of swaps and the second hour of BTC.BTC depths
+    execute(
+        &db,
+        "DELETE FROM swap_intervals WHERE start_time IN \
+         (SELECT start_time FROM swap_intervals ORDER BY start_time LIMIT 2 OFFSET 2)",
+    )
+    .await;
+    execute(
+        &db,
+        "DELETE FROM depth_intervals WHERE pool = 'BTC.BTC' AND start_time IN \
+         (SELECT start_time FROM depth_intervals WHERE pool = 'BTC.BTC' \
+         ORDER BY start_time LIMIT 1 OFFSET 1)",
+    )
+    .await;
+
+    let swap_gaps = find_gaps(&db, Dataset::Swap, None).await.unwrap();
+    assert_eq!(swap_gaps.len(), 1);
+    assert_eq!(swap_gaps[0].kind, GapKind::Missing);
+    assert_eq!(swap_gaps[0].start_time.timestamp(), 1704074400);
+    assert_eq!(swap_gaps[0].end_time.timestamp(), 1704081600);
+    let depth_gaps = find_gaps(&db, Dataset::Depth, None).await.unwrap();
+    assert_eq!(depth_gaps.len(), 1);
+    assert_eq!(depth_gaps[0].pool.as_deref(), Some("BTC.BTC"));
+    assert!(find_gaps(&db, Dataset::Depth, Some("ETH.ETH"))
+        .await
+        .unwrap()
+        .is_empty());
+
+    GapRepairCron::new(db.clone())
+        .with_midgard_url(&midgard.url)
+        .repair()
+        .await
+        .unwrap();
+
+    assert_eq!(count_rows(&db, "swap_intervals").await, 6);
+    assert_eq!(count_rows(&db, "depth_intervals").await, 12);
+    assert!(find_gaps(&db, Dataset::Swap, None)
+        .await
+        .unwrap()
+        .is_empty());
+    assert!(find_gaps(&db, Dataset::Depth, None)
+        .await
+        .unwrap()
+        .is_empty());
+    let requests = midgard.requests("/history/swaps");
+    assert_eq!(requests.last().unwrap()["from"], "1704074400");
+}
+
+#[tokio::test]
+async fn only_one_instance_holds_the_lease() {
+    let db = fresh_db().await;
+    let ttl = Duration::seconds(30);
+    let now = Utc::now();
+
+    assert!(try_acquire_lease(&db, "ingestion", "a", now + ttl, now)
+        .await
+        .unwrap());
+    assert!(!try_acquire_lease(&db, "ingestion", "b", now + ttl, now)
+        .await
+        .unwrap());
+
+    // The holder renews, the other instance keeps waiting
+    let renewed = now + Duration::seconds(10);
+    assert!(
+        try_acquire_lease(&db, "ingestion", "a", renewed + ttl, renewed)
+            .await
+            .unwrap()
+    );
+
+    // Once it stops renewing the lease runs out and goes to whoever asks next
+    let expired = renewed + ttl;
+    assert!(
+        try_acquire_lease(&db, "ingestion", "b", expired + ttl, expired)
+            .await
+            .unwrap()
+    );
+    assert!(
+        !try_acquire_lease(&db, "ingestion", "a", expired + ttl, expired)
+            .await
+            .unwrap()
+    );
+
+    // A released lease can be taken right away
+    release_lease(&db, "ingestion", "b", expired).await.unwrap();
+    assert!(
+        try_acquire_lease(&db, "ingestion", "a", expired + ttl, expired)
+            .await
+            .unwrap()
+    );
+}
+
+#[tokio::test]
+async fn another_instance_takes_over_when_the_leader_dies() {
+    let db = fresh_db().await;
+    let elections = [Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0))];
+
+    let instance = |elected: Arc<AtomicUsize>| {
+        let election = LeaderElection::new(db.clone(), "ingestion").with_ttl(Duration::seconds(3));
+        tokio::spawn(async move {
+            election
+                .run(|| {
+                    let elected = elected.clone();
+                    async move {
+                        elected.fetch_add(1, Ordering::SeqCst);
+                        std::future::pending::<()>().await
+                    }
+                })
+                .await
+        })
+    };
+
+    let first = instance(elections[0].clone());
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    let _second = instance(elections[1].clone());
+    tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+    assert_eq!(elections[0].load(Ordering::SeqCst), 1);
+    assert_eq!(elections[1].load(Ordering::SeqCst), 0);
+
+    // Dies without releasing the lease, the second instance waits for it to run out
+    first.abort();
+    tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+    assert_eq!(elections[1].load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn losing_the_lease_stops_the_runs_in_flight() {
+    let db = fresh_db().await;
+    let ticks = Arc::new(AtomicUsize::new(0));
+
+    let election = LeaderElection::new(db.clone(), "ingestion").with_ttl(Duration::seconds(3));
+    let leader = {
+        let ticks = ticks.clone();
+        tokio::spawn(async move {
+            election
+                .run(|| {
+                    let ticks = ticks.clone();
+                    // A run that never ends on its own, started by the first tick of the schedule
+                    let job = Job::new("swap", parse_schedule("* * * * * *").unwrap(), move || {
+                        let ticks = ticks.clone();
+                        async move {
+                            loop {
+                                ticks.fetch_add(1, Ordering::SeqCst);
+                                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                            }
+                        }
+                    });
+                    Scheduler::new().with_job(job).run()
+                })
+                .await
+        })
+    };
+
+    tokio::time::sleep(std::time::Duration::from_millis(1500)).await;
+    assert!(ticks.load(Ordering::SeqCst) > 0);
+
+    // Another replica took the lease over, the next renewal finds out
+    execute(&db, "UPDATE job_leases SET holder = 'other'").await;
+    tokio::time::sleep(std::time::Duration::from_millis(1500)).await;
+
+    let stopped_at = ticks.load(Ordering::SeqCst);
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    assert_eq!(ticks.load(Ordering::SeqCst), stopped_at);
+
+    leader.abort();
+}
+
+#[tokio::test]
+async fn failing_renewals_stop_the_runs_before_the_lease_runs_out() {
+    let db = fresh_db().await;
+    let ticks = Arc::new(AtomicUsize::new(0));
+
+    let election = LeaderElection::new(db.clone(), "ingestion").with_ttl(Duration::seconds(3));
+    let leader = {
+        let ticks = ticks.clone();
+        tokio::spawn(async move {
+            election
+                .run(|| {
+                    let ticks = ticks.clone();
+                    async move {
+                        loop {
+                            ticks.fetch_add(1, Ordering::SeqCst);
+                            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                        }
+                    }
+                })
+                .await
+        })
+    };
+
+    // Renewed every second, the last one lands at 1s and holds the lease until 4s
+    tokio::time::sleep(std::time::Duration::from_millis(1500)).await;
+    execute(&db, "DROP TABLE job_leases").await;
+
+    // The renewal at 3s would be the last chance to keep it, failing that one stops the runs
+    tokio::time::sleep(std::time::Duration::from_millis(2100)).await;
+    let stopped_at = ticks.load(Ordering::SeqCst);
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    assert_eq!(ticks.load(Ordering::SeqCst), stopped_at);
+
+    leader.abort();
+}
@@ -0,0 +1,514 @@
+mod common;
+
+// The history handlers over the sqlite repositories, the same SQL the service runs. Depth gets
+// two days of made up hours for one pool, the other datasets the recorded midgard fixtures
+
+use axum::{
+    body::to_bytes,
+    extract::{Query, State},
+    http::{StatusCode, Uri},
+    response::IntoResponse,
+};
+use catalog_crypto_api::api::routes::{
+    depth::get_depth_history, earnings::get_earnings_history, runepool::get_runepool_units_history,
+    swap::get_swap_history,
+};
+use catalog_crypto_api::config::connect::Database;
+use catalog_crypto_api::core::models::{
+    common::Dataset, depth_history::DepthInterval, pools::PoolDetail,
+};
+use catalog_crypto_api::services::jobs::{backfill::Backfill, cron::pools_cron::PoolsCron};
+use catalog_crypto_api::services::repository::{
+    checkpoints::BackfillRange,
+    traits::{DepthRepository, PoolRepository},
+};
+use chrono::{DateTime, TimeZone, Utc};
+use common::{fresh_db, MockMidgard};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+// 2023-11-14 00:00 UTC
+const DAY_START: i64 = 1699920000;
+
+// Start and end of the hours in every history fixture
+const FIRST_START_TIME: i64 = 1704067200;
+const LAST_END_TIME: i64 = 1704088800;
+
+async fn read(response: impl IntoResponse) -> (StatusCode, Value) {
+    let response = response.into_response();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+fn uri(path: &str, query: &str) -> Uri {
+    format!("{path}?{query}").parse().unwrap()
+}
+
+fn interval(pool: &str, hour: i64) -> DepthInterval {
+    let start_time = DAY_START + hour * 3600;
+    DepthInterval {
+        asset_depth: 1000 + hour as u64,
+        asset_price: 1.0 + hour as f64,
+        asset_price_usd: 2.0,
+        end_time: Utc.timestamp_opt(start_time + 3600, 0).unwrap(),
+        id: 0,
+        liquidity_units: 500,
+        luvi: 1.0,
+        members_count: 10,
+        pool: pool.to_string(),
+        revision: 0,
+        rune_depth: 2000,
+        start_time: Utc.timestamp_opt(start_time, 0).unwrap(),
+        synth_supply: 0,
+        synth_units: 0,
+        units: 100 + hour as u64,
+    }
+}
+
+async fn depth_db() -> Arc<Database> {
+    let db = fresh_db().await;
+    let intervals: Vec<_> = (0..48).map(|hour| interval("BTC.BTC", hour)).collect();
+    DepthRepository::store_intervals(&db, &intervals)
+        .await
+        .unwrap();
+    db.store_pools(&[PoolDetail {
+        asset: "BTC.BTC".to_string(),
+        status: "available".to_string(),
+        native_decimal: "8".to_string(),
+    }])
+    .await
+    .unwrap();
+    Arc::new(db)
+}
+
+async fn get_depth(query: &str) -> (StatusCode, Value) {
+    let db = depth_db().await;
+    read(
+        get_depth_history(
+            State(db.clone()),
+            State(db),
+            Query::try_from_uri(&uri("/depth_history", query)),
+        )
+        .await,
+    )
+    .await
+}
+
+// The pool registry and every hour of the earnings, swap and runepool fixtures
+async fn fixture_db() -> Arc<Database> {
+    let midgard = MockMidgard::start().await;
+    let db = fresh_db().await;
+    PoolsCron::new(db.clone())
+        .with_midgard_url(&midgard.url)
+        .sync()
+        .await
+        .unwrap();
+    let range = BackfillRange {
+        from: DateTime::from_timestamp(FIRST_START_TIME, 0).unwrap(),
+        to: DateTime::from_timestamp(LAST_END_TIME, 0).unwrap(),
+    };
+    for dataset in [Dataset::Earnings, Dataset::Swap, Dataset::Runepool] {
+        Backfill::new(db.clone(), dataset, range)
+            .with_midgard_url(&midgard.url)
+            .run()
+            .await
+            .unwrap();
+    }
+    Arc::new(db)
+}
+
+async fn get_earnings(db: &Arc<Database>, query: &str) -> (StatusCode, Value) {
+    read(
+        get_earnings_history(
+            State(db.clone()),
+            State(db.clone()),
+            Query::try_from_uri(&uri("/earning_history", query)),
+        )
+        .await,
+    )
+    .await
+}
+
+async fn get_swaps(db: &Arc<Database>, query: &str) -> (StatusCode, Value) {
+    read(
+        get_swap_history(
+            State(db.clone()),
+            Query::try_from_uri(&uri("/swap_history", query)),
+        )
+        .await,
+    )
+    .await
+}
+
+async fn get_runepool(db: &Arc<Database>, query: &str) -> (StatusCode, Value) {
+    read(
+        get_runepool_units_history(
+            State(db.clone()),
+            Query::try_from_uri(&uri("/runepool_units_history", query)),
+        )
+        .await,
+    )
+    .await
+}
+
+#[tokio::test]
+async fn depth_rolls_days_up_to_their_last_hour() {
+    let (status, body) = get_depth("pool=BTC.BTC&interval=day&order=asc").await;
+
+    assert_eq!(status, StatusCode::OK);
+    let intervals = body["intervals"].as_array().unwrap();
+    assert_eq!(intervals.len(), 2);
+    assert_eq!(intervals[0]["startTime"], DAY_START.to_string());
+    assert_eq!(intervals[0]["units"], "123");
+    assert_eq!(intervals[1]["units"], "147");
+    assert_eq!(body["pagination"]["total"], 2);
+    assert_eq!(body["meta"]["startAssetDepth"], "1000");
+    assert_eq!(body["meta"]["endAssetDepth"], "1047");
+}
+
+#[tokio::test]
+async fn depth_pages_through_the_hourly_intervals() {
+    let (status, body) = get_depth("pool=BTC.BTC&order=desc&limit=5&page=1").await;
+
+    assert_eq!(status, StatusCode::OK);
+    let intervals = body["intervals"].as_array().unwrap();
+    assert_eq!(intervals.len(), 5);
+    assert_eq!(intervals[0]["units"], "142");
+    // The meta still covers the whole range, not just the page
+    assert_eq!(body["meta"]["startAssetDepth"], "1000");
+    assert_eq!(
+        body["pagination"],
+        json!({
+            "total": 48,
+            "page": 1,
+            "limit": 5,
+            "has_more": true,
+            "total_pages": 10
+        })
+    );
+
+    let (_, body) = get_depth("order=desc&limit=5&page=9").await;
+    assert_eq!(body["intervals"].as_array().unwrap().len(), 3);
+    assert_eq!(body["pagination"]["has_more"], false);
+    assert_eq!(body["meta"], Value::Null);
+
+    // Past the end the page is empty, the range it was cut from isn't
+    let (_, body) = get_depth("pool=BTC.BTC&limit=5&page=20").await;
+    assert_eq!(body["intervals"], json!([]));
+    assert_eq!(body["meta"]["startAssetDepth"], "1000");
+}
+
+#[tokio::test]
+async fn depth_walks_the_history_with_cursors() {
+    let mut pages = Vec::new();
+    let mut query = "order=desc&limit=20".to_string();
+    loop {
+        let (status, body) = get_depth(&query).await;
+        assert_eq!(status, StatusCode::OK);
+        pages.push(body.clone());
+        match body["next_cursor"].as_str() {
+            Some(cursor) => query = format!("order=desc&limit=20&cursor={cursor}"),
+            None => break,
+        }
+    }
+
+    let units: Vec<&str> = pages
+        .iter()
+        .flat_map(|page| page["intervals"].as_array().unwrap())
+        .map(|interval| interval["units"].as_str().unwrap())
+        .collect();
+    let expected: Vec<String> = (0..48).rev().map(|hour| (100 + hour).to_string()).collect();
+    assert_eq!(units, expected);
+    assert_eq!(pages.len(), 3);
+    assert_eq!(pages[0]["prev_cursor"], Value::Null);
+    assert_eq!(pages[1]["pagination"]["page"], Value::Null);
+    assert_eq!(pages[2]["pagination"]["has_more"], false);
+
+    // Reading back from the second page gives the first one again
+    let cursor = pages[1]["prev_cursor"].as_str().unwrap();
+    let (_, body) = get_depth(&format!("order=desc&limit=20&cursor={cursor}")).await;
+    assert_eq!(body["intervals"], pages[0]["intervals"]);
+    assert_eq!(body["prev_cursor"], Value::Null);
+    assert_eq!(body["next_cursor"], pages[0]["next_cursor"]);
+
+    for query in [
+        format!("page=1&cursor={cursor}"),
+        format!("sort_by=asset_depth&cursor={cursor}"),
+        "cursor=nonsense".to_string(),
+    ] {
+        let (status, body) = get_depth(&query).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["details"]["param"], "cursor");
+    }
+}
+
+#[tokio::test]
+async fn depth_filters_on_column_ranges() {
+    let (status, body) =
+        get_depth("order=asc&filter=asset_price:gte:10,units:between:100:120").await;
+
+    assert_eq!(status, StatusCode::OK);
+    let intervals = body["intervals"].as_array().unwrap();
+    assert_eq!(intervals.len(), 12);
+    assert_eq!(intervals[0]["units"], "109");
+    assert_eq!(intervals[11]["units"], "120");
+    assert_eq!(body["pagination"]["total"], 12);
+
+    for filter in [
+        "pool:eq:1",
+        "units:gt:1.5",
+        "units:lt",
+        "asset_price:like:1",
+    ] {
+        let (status, body) = get_depth(&format!("filter={filter}")).await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["details"]["param"], "filter");
+    }
+}
+
+#[tokio::test]
+async fn depth_returns_only_the_requested_fields() {
+    let (status, body) =
+        get_depth("pool=BTC.BTC&order=asc&fields=timestamp,asset_price_usd,pool").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body["intervals"][0],
+        json!({
+            "startTime": DAY_START.to_string(),
+            "assetPriceUSD": "2",
+            "pool": "BTC.BTC"
+        })
+    );
+    assert_eq!(body["meta"]["startAssetDepth"], "1000");
+
+    let (status, body) = get_depth("fields=start_time,price").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["details"]["param"], "fields");
+}
+
+#[tokio::test]
+async fn depth_rejects_pools_missing_from_the_registry() {
+    let (status, body) = get_depth("pool=ETH.ETH").await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "invalid_params");
+    assert_eq!(body["details"]["param"], "pool");
+}
+
+#[tokio::test]
+async fn depth_rejects_five_minute_rollups() {
+    let (status, _) = get_depth("interval=5min").await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn depth_returns_an_empty_page_when_nothing_matches() {
+    let (status, body) = get_depth("date_range=2024-01-01,2024-01-31").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["intervals"], json!([]));
+    assert_eq!(body["meta"], Value::Null);
+}
+
+#[tokio::test]
+async fn depth_reads_half_open_time_ranges() {
+    let (_, body) = get_depth("order=asc&from=1699956000&to=2023-11-14T20:00:00Z").await;
+    let intervals = body["intervals"].as_array().unwrap();
+    assert_eq!(intervals.len(), 10);
+    assert_eq!(intervals[0]["units"], "110");
+    assert_eq!(intervals[9]["units"], "119");
+
+    let (_, body) = get_depth("from=2023-11-15T00:00:00%2B01:00").await;
+    assert_eq!(body["pagination"]["total"], 25);
+
+    // Every hour of the day, the last one ends at midnight
+    let (_, body) = get_depth("date_range=2023-11-14,2023-11-14").await;
+    assert_eq!(body["pagination"]["total"], 24);
+
+    for (query, param) in [
+        ("from=yesterday", "from"),
+        ("to=1699956000.5", "to"),
+        ("from=1699956000&to=1699956000", "to"),
+        (
+            "date_range=2023-11-14,2023-11-14&to=1699956000",
+            "date_range",
+        ),
+    ] {
+        let (status, body) = get_depth(query).await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["details"]["param"], param);
+    }
+}
+
+#[tokio::test]
+async fn depth_rejects_malformed_date_ranges() {
+    for range in [
+        "2023-11-14",
+        "2023-11-14,yesterday",
+        "2023-11-15,2023-11-14",
+    ] {
+        let (status, body) = get_depth(&format!("date_range={range}")).await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["details"]["param"], "date_range");
+    }
+}
+
+#[tokio::test]
+async fn earnings_roll_up_the_pools_of_a_day() {
+    let db = fixture_db().await;
+    let (status, body) = get_earnings(&db, "interval=day").await;
+
+    // The sums midgard reports for the same six hours
+    assert_eq!(status, StatusCode::OK);
+    let intervals = body["intervals"].as_array().unwrap();
+    assert_eq!(intervals.len(), 1);
+    assert_eq!(intervals[0]["startTime"], FIRST_START_TIME.to_string());
+    assert_eq!(intervals[0]["endTime"], LAST_END_TIME.to_string());
+    assert_eq!(intervals[0]["earnings"], "314785609018");
+    assert_eq!(intervals[0]["pools"][0]["pool"], "BTC.BTC");
+    assert_eq!(intervals[0]["pools"][0]["earnings"], "27453281013");
+    assert_eq!(intervals[0]["pools"][1]["earnings"], "31933461805");
+    assert_eq!(body["meta"]["earnings"], "314785609018");
+    assert_eq!(body["meta"]["pools"], intervals[0]["pools"]);
+}
+
+#[tokio::test]
+async fn earnings_page_filter_and_narrow() {
+    let db = fixture_db().await;
+    let (status, body) = get_earnings(&db, "order=asc&limit=4&fields=timestamp,earnings").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["intervals"].as_array().unwrap().len(), 4);
+    assert_eq!(
+        body["intervals"][0],
+        json!({ "startTime": FIRST_START_TIME.to_string(), "earnings": "51310826450" })
+    );
+    assert_eq!(body["pagination"]["total"], 6);
+    assert_eq!(body["pagination"]["has_more"], true);
+    // The meta still covers the whole range
+    assert_eq!(body["meta"]["earnings"], "314785609018");
+
+    let cursor = body["next_cursor"].as_str().unwrap();
+    let (_, body) = get_earnings(&db, &format!("order=asc&limit=4&cursor={cursor}")).await;
+    assert_eq!(body["intervals"].as_array().unwrap().len(), 2);
+    assert_eq!(body["next_cursor"], Value::Null);
+
+    let (_, body) = get_earnings(&db, "filter=earnings:lt:52000000000").await;
+    assert_eq!(body["pagination"]["total"], 2);
+
+    for (query, param) in [
+        ("pool=XRP.XRP", "pool"),
+        ("filter=earnings:gt:lots", "filter"),
+        ("fields=earnings,price", "fields"),
+        ("limit=2&page=4294967295", "page"),
+    ] {
+        let (status, body) = get_earnings(&db, query).await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["details"]["param"], param);
+    }
+}
+
+#[tokio::test]
+async fn swaps_sum_up_and_weigh_slips_by_count() {
+    let db = fixture_db().await;
+    let (status, body) = get_swaps(&db, "interval=day").await;
+
+    assert_eq!(status, StatusCode::OK);
+    let intervals = body["intervals"].as_array().unwrap();
+    assert_eq!(intervals.len(), 1);
+    assert_eq!(intervals[0]["totalCount"], "7074");
+    assert_eq!(intervals[0]["totalVolume"], "17697584857307");
+    let average_slip: f64 = intervals[0]["averageSlip"]
+        .as_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!((average_slip - 6.9098).abs() < 1e-3, "{average_slip}");
+    assert_eq!(body["meta"]["totalCount"], "7074");
+}
+
+#[tokio::test]
+async fn swaps_sort_and_filter_the_hours() {
+    let db = fixture_db().await;
+    let (status, body) = get_swaps(&db, "sort_by=total_count&order=desc&limit=2").await;
+
+    assert_eq!(status, StatusCode::OK);
+    let counts: Vec<_> = body["intervals"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|interval| interval["totalCount"].as_str().unwrap())
+        .collect();
+    assert_eq!(counts, ["1335", "1291"]);
+    // Cursors only follow the time order
+    assert_eq!(body["next_cursor"], Value::Null);
+    assert_eq!(body["pagination"]["has_more"], true);
+
+    let (_, body) = get_swaps(&db, "filter=total_count:gte:1200").await;
+    assert_eq!(body["pagination"]["total"], 3);
+
+    let (_, body) = get_swaps(&db, "from=1704070800&to=1704078000").await;
+    assert_eq!(body["pagination"]["total"], 2);
+    assert_eq!(body["meta"]["totalCount"], "2227");
+
+    let (status, body) = get_swaps(&db, "sort_by=price").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "invalid_params");
+}
+
+#[tokio::test]
+async fn runepool_days_report_their_last_hour() {
+    let db = fixture_db().await;
+    let (status, body) = get_runepool(&db, "interval=day").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body["intervals"],
+        json!([{
+            "count": "1752",
+            "endTime": LAST_END_TIME.to_string(),
+            "revision": 0,
+            "startTime": FIRST_START_TIME.to_string(),
+            "units": "89432625412933"
+        }])
+    );
+    assert_eq!(body["meta"]["startUnits"], "89170092935735");
+    assert_eq!(body["meta"]["endUnits"], "89432625412933");
+}
+
+#[tokio::test]
+async fn runepool_reads_ranges_and_pages() {
+    let db = fixture_db().await;
+    let (status, body) = get_runepool(&db, "order=asc&from=1704074400&limit=2&page=1").await;
+
+    assert_eq!(status, StatusCode::OK);
+    let intervals = body["intervals"].as_array().unwrap();
+    assert_eq!(intervals.len(), 2);
+    assert_eq!(intervals[0]["startTime"], "1704081600");
+    assert_eq!(body["pagination"]["total"], 4);
+    assert_eq!(body["meta"]["startCount"], "1746");
+    assert_eq!(body["meta"]["endCount"], "1752");
+
+    let (status, body) = get_runepool(&db, "date_range=2023-01-01,2023-01-31").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["intervals"], json!([]));
+    assert_eq!(body["meta"], Value::Null);
+
+    for (query, param) in [
+        ("to=soon", "to"),
+        ("interval=5min", "interval"),
+        ("limit=2&page=4294967295", "page"),
+    ] {
+        let (status, body) = get_runepool(&db, query).await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["details"]["param"], param);
+    }
+}